@@ -1,6 +1,6 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{fs::File, path::PathBuf};
 
-use sevenz_rust2::{Archive, BlockDecoder, Password};
+use sevenz_rust2::{ArchiveReader, Password};
 
 // 0. The simplest way to use multi threading is to use simply the ArchiveReader.
 //    If the compression of the archive blocks supports multi threading, which is supported
@@ -11,46 +11,29 @@ use sevenz_rust2::{Archive, BlockDecoder, Password};
 //    See `ArchiveReader::set_thread_count()` for more information.`
 fn main() {
     let time = std::time::Instant::now();
-    let mut file = std::fs::File::open("examples/data/sample.7z").unwrap();
-    let password = Password::empty();
-    let archive = Archive::read(&mut file, &password).unwrap();
-    let block_count = archive.blocks.len();
+    let mut reader = ArchiveReader::open("examples/data/sample.7z", Password::empty()).unwrap();
+    let block_count = reader.archive().blocks.len();
     if block_count <= 1 {
         println!("block count less than 1, use single thread");
     }
-    let archive = Arc::new(archive);
-    let password = Arc::new(password);
 
-    let mut threads = Vec::new();
-
-    // 1. We multi-thread by decompressing each block itself in parallel.
-    for block_index in 0..block_count {
-        let archive = archive.clone();
-        let password = password.clone();
-
-        let handle = std::thread::spawn(move || {
-            let mut source = std::fs::File::open("examples/data/sample.7z").unwrap();
-
-            // 2. For decoders that supports it, we can set the thread_count on the block decoder
-            //    so that it uses multiple threads to decode the block. Currently only LZMA2 is
-            //    supporting this. In this example we try to use 4 threads.
-            let block_decoder = BlockDecoder::new(4, block_index, &archive, &password, &mut source);
-
-            let dest = PathBuf::from("examples/data/sample_mt/");
-            block_decoder
-                .for_each_entries(&mut |entry, reader| {
-                    let dest = dest.join(entry.name());
-                    sevenz_rust2::default_entry_extract_fn(entry, reader, &dest)?;
-                    Ok(true)
-                })
-                .expect("ok");
-        });
-        threads.push(handle);
-    }
-
-    threads
-        .into_iter()
-        .for_each(|handle| handle.join().unwrap());
+    // 1. We multi-thread by decompressing each block itself in parallel. The thread count is
+    //    split between the blocks and the decoders that support multi threading, so in this
+    //    example 4 threads are used in total.
+    reader.set_thread_count(4);
+
+    // 2. Every worker thread reads the archive through its own file handle.
+    let dest = PathBuf::from("examples/data/sample_mt/");
+    reader
+        .par_for_each_entries(
+            || Ok(File::open("examples/data/sample.7z")?),
+            |entry, reader| {
+                let dest = dest.join(entry.name());
+                sevenz_rust2::default_entry_extract_fn(entry, reader, &dest)?;
+                Ok(true)
+            },
+        )
+        .expect("ok");
 
     println!(
         "multi-thread decompress took {:?} ms",
@@ -1,10 +1,14 @@
 #[cfg(feature = "compress")]
 use crate::encoder_options::EncoderOptions;
-use crate::{NtTime, bitset::BitSet, block::*};
+use crate::{NtTime, ReaderOptions, bitset::BitSet, block::*, codec::registry::CodecRegistry};
 
 pub(crate) const SIGNATURE_HEADER_SIZE: u64 = 32;
 pub(crate) const SEVEN_Z_SIGNATURE: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
 
+/// Default search window used to find the signature header of self-extracting archives,
+/// see [`Archive::read_with_signature_scan`].
+pub const DEFAULT_SIGNATURE_SEARCH_LIMIT: u64 = 4 * 1024 * 1024;
+
 pub(crate) const K_END: u8 = 0x00;
 pub(crate) const K_HEADER: u8 = 0x01;
 pub(crate) const K_ARCHIVE_PROPERTIES: u8 = 0x02;
@@ -28,8 +32,6 @@ pub(crate) const K_A_TIME: u8 = 0x13;
 pub(crate) const K_M_TIME: u8 = 0x14;
 pub(crate) const K_WIN_ATTRIBUTES: u8 = 0x15;
 
-/// TODO: Implement reading & writing comments
-#[allow(unused)]
 pub(crate) const K_COMMENT: u8 = 0x16;
 pub(crate) const K_ENCODED_HEADER: u8 = 0x17;
 pub(crate) const K_START_POS: u8 = 0x18;
@@ -41,12 +43,19 @@ pub(crate) const K_DUMMY: u8 = 0x19;
 /// and internal structure information necessary for decompression.
 #[derive(Debug, Default, Clone)]
 pub struct Archive {
-    /// Offset from beginning of file + SIGNATURE_HEADER_SIZE to packed streams.
+    /// Offset of the signature header from the beginning of the source.
+    /// Non-zero for self-extracting archives or archives with leading data.
+    pub(crate) base_offset: u64,
+    /// Offset from base offset + SIGNATURE_HEADER_SIZE to packed streams.
     pub(crate) pack_pos: u64,
     pub(crate) pack_sizes: Vec<u64>,
     pub(crate) pack_crcs_defined: BitSet,
     pub(crate) pack_crcs: Vec<u64>,
     pub(crate) sub_streams_info: Option<SubStreamsInfo>,
+    /// Absolute position of the end of the header in the source.
+    pub(crate) header_end: u64,
+    /// Whether the header is encrypted.
+    pub(crate) header_encrypted: bool,
     /// Compression blocks in the archive.
     pub blocks: Vec<Block>,
     /// File and directory entries in the archive.
@@ -55,6 +64,25 @@ pub struct Archive {
     pub stream_map: StreamMap,
     /// Whether this is a solid archive (better compression, slower random access).
     pub is_solid: bool,
+    /// Properties of the whole archive stored in the header.
+    pub archive_properties: Vec<ArchiveProperty>,
+    /// Comment of the archive stored in the files info of the header.
+    pub(crate) comment: Option<String>,
+    /// Limits the archive was read with, which also apply when decoding it.
+    pub(crate) options: ReaderOptions,
+    /// Custom codecs used when decoding the blocks of the archive.
+    pub(crate) codecs: CodecRegistry,
+}
+
+/// A property of the whole archive, stored in the archive properties of the header.
+///
+/// The 7z format doesn't define any archive property types, so the data is kept as stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveProperty {
+    /// Type of the property.
+    pub property_type: u8,
+    /// Data of the property.
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -94,6 +122,10 @@ pub struct ArchiveEntry {
     pub has_windows_attributes: bool,
     /// Windows file attributes.
     pub windows_attributes: u32,
+    /// Whether start position is present.
+    pub has_start_position: bool,
+    /// Start position of the data of this entry within the original file.
+    pub start_position: u64,
     /// Whether CRC is present.
     pub has_crc: bool,
     /// CRC32 checksum of uncompressed data.
@@ -414,6 +446,19 @@ impl EncoderMethod {
         &Self::BCJ2_FILTER,
     ];
 
+    /// Creates a custom method with the given `name` and method `id`, which has to be
+    /// encoded and decoded by a registered [`Codec`](crate::Codec).
+    ///
+    /// # Panics
+    /// Panics if `id` is empty or longer than 15 bytes, which is the limit of the 7z format.
+    pub const fn new(name: &'static str, id: &'static [u8]) -> Self {
+        assert!(
+            !id.is_empty() && id.len() <= 0xF,
+            "Method ids have 1 to 15 bytes"
+        );
+        Self(name, id)
+    }
+
     #[inline]
     /// Returns the human-readable name of this encoder method.
     pub const fn name(&self) -> &'static str {
@@ -27,17 +27,19 @@ impl Block {
         self.bind_pairs.iter().find(|bp| bp.out_index == index)
     }
 
+    /// Returns the index of the output stream which isn't bound to another coder and holds
+    /// the decoded data of the block.
+    pub(crate) fn main_out_stream(&self) -> Option<u64> {
+        (0..self.total_output_streams as u64)
+            .rev()
+            .find(|&i| self.find_bind_pair_for_out_stream(i).is_none())
+    }
+
     /// Returns the total uncompressed size of data in this block.
     pub fn get_unpack_size(&self) -> u64 {
-        if self.total_output_streams == 0 {
-            return 0;
-        }
-        for i in (0..self.total_output_streams).rev() {
-            if self.find_bind_pair_for_out_stream(i as u64).is_none() {
-                return self.unpack_sizes[i];
-            }
-        }
-        0
+        self.main_out_stream()
+            .map(|i| self.unpack_sizes[i as usize])
+            .unwrap_or_default()
     }
 
     /// Returns the uncompressed size for a specific coder within this block.
@@ -61,6 +63,95 @@ impl Block {
         self.unpack_sizes.get(index).cloned().unwrap_or_default()
     }
 
+    /// Returns the index of the coder which reads the input stream with the given `index`.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn coder_index_for_in_stream(&self, index: u64) -> Option<usize> {
+        let mut first_in_stream = 0;
+        for (coder_index, coder) in self.coders.iter().enumerate() {
+            if index < first_in_stream + coder.num_in_streams {
+                return Some(coder_index);
+            }
+            first_in_stream += coder.num_in_streams;
+        }
+        None
+    }
+
+    /// Returns the index of the coder which writes the output stream with the given `index`.
+    pub(crate) fn coder_index_for_out_stream(&self, index: u64) -> Option<usize> {
+        let mut first_out_stream = 0;
+        for (coder_index, coder) in self.coders.iter().enumerate() {
+            if index < first_out_stream + coder.num_out_streams {
+                return Some(coder_index);
+            }
+            first_out_stream += coder.num_out_streams;
+        }
+        None
+    }
+
+    /// Returns the index of the first input stream of the coder at `coder_index`.
+    pub(crate) fn first_in_stream_index(&self, coder_index: usize) -> u64 {
+        self.coders[..coder_index]
+            .iter()
+            .map(|coder| coder.num_in_streams)
+            .sum()
+    }
+
+    /// Returns the index of the first output stream of the coder at `coder_index`.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn first_out_stream_index(&self, coder_index: usize) -> u64 {
+        self.coders[..coder_index]
+            .iter()
+            .map(|coder| coder.num_out_streams)
+            .sum()
+    }
+
+    /// Removes the coder at `coder_index`, which must have a single input and output stream,
+    /// and connects its input directly to the coder that consumes its output.
+    ///
+    /// If the output of the coder is the output of the block, the coder is replaced
+    /// by a copy coder instead.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn remove_coder(&mut self, coder_index: usize) {
+        let in_index = self.first_in_stream_index(coder_index);
+        let out_index = self.first_out_stream_index(coder_index);
+
+        let Some(bind_pair_index) = self
+            .bind_pairs
+            .iter()
+            .position(|bp| bp.out_index == out_index)
+        else {
+            self.coders[coder_index] = Coder {
+                id_size: 1,
+                num_in_streams: 1,
+                num_out_streams: 1,
+                ..Default::default()
+            };
+            return;
+        };
+
+        let bind_pair = self.bind_pairs.remove(bind_pair_index);
+        for packed_stream in self.packed_streams.iter_mut() {
+            if *packed_stream == in_index {
+                *packed_stream = bind_pair.in_index;
+            }
+            if *packed_stream > in_index {
+                *packed_stream -= 1;
+            }
+        }
+        for bp in self.bind_pairs.iter_mut() {
+            if bp.in_index > in_index {
+                bp.in_index -= 1;
+            }
+            if bp.out_index > out_index {
+                bp.out_index -= 1;
+            }
+        }
+        self.unpack_sizes.remove(out_index as usize);
+        self.coders.remove(coder_index);
+        self.total_input_streams -= 1;
+        self.total_output_streams -= 1;
+    }
+
     /// Returns an iterator over the coders in their processing order.
     ///
     /// Coders are chained together in blocks, and this iterator follows the chain
@@ -84,6 +175,20 @@ pub struct Coder {
 }
 
 impl Coder {
+    /// Creates a coder with a single output stream.
+    #[cfg(feature = "compress")]
+    pub(crate) fn new(id: &[u8], num_in_streams: u64, properties: Vec<u8>) -> Self {
+        let mut coder = Self {
+            id_size: id.len(),
+            num_in_streams,
+            num_out_streams: 1,
+            properties,
+            ..Default::default()
+        };
+        coder.encoder_method_id[..id.len()].copy_from_slice(id);
+        coder
+    }
+
     /// Returns the encoder method ID for this coder.
     ///
     /// This ID identifies the specific compression method, filter, or encryption
@@ -1,4 +1,13 @@
+#[cfg(feature = "compress")]
+pub mod bcj2;
 #[cfg(feature = "brotli")]
 pub mod brotli;
+#[cfg(feature = "deflate64")]
+pub mod deflate64;
+#[cfg(feature = "lizard")]
+pub mod lizard;
 #[cfg(feature = "lz4")]
 pub mod lz4;
+#[cfg(feature = "lzs")]
+pub mod lzs;
+pub mod registry;
@@ -0,0 +1,233 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// Branches are only converted if their relative target is within this distance, like
+/// 7-Zip does by default.
+const RELATIVE_LIMIT: u32 = 1 << 26;
+
+const NUM_MODEL_BITS: u32 = 11;
+
+const BIT_MODEL_TOTAL: u16 = 1 << NUM_MODEL_BITS;
+
+const NUM_MOVE_BITS: u32 = 5;
+
+const TOP_VALUE: u32 = 1 << 24;
+
+/// The streams of the BCJ2 filter besides the main stream.
+#[derive(Debug, Default)]
+pub(crate) struct Bcj2Streams {
+    /// Absolute targets of the converted `CALL` instructions.
+    pub(crate) call: Vec<u8>,
+    /// Absolute targets of the converted `JMP` and `Jcc` instructions.
+    pub(crate) jump: Vec<u8>,
+    /// Range coded flags whether each branch instruction was converted.
+    pub(crate) rc: Vec<u8>,
+}
+
+/// Encoder of the BCJ2 filter for x86 code, which splits the data into four streams.
+///
+/// The main stream is written to the inner writer. The call, jump and range coder streams
+/// are kept in memory and moved to [`Bcj2Writer::streams`] once the encoder is finished by
+/// an empty write.
+pub(crate) struct Bcj2Writer<W: Write> {
+    main: W,
+    call: Vec<u8>,
+    jump: Vec<u8>,
+    rc: RangeEncoder,
+    probs: [u16; 2 + 256],
+    /// Data which isn't encoded yet, because a branch instruction needs the following
+    /// four bytes.
+    pending: Vec<u8>,
+    main_buf: Vec<u8>,
+    /// Position of the first pending byte in the data.
+    ip: u32,
+    prev: u8,
+    finished: bool,
+    streams: Rc<RefCell<Bcj2Streams>>,
+}
+
+impl<W: Write> Bcj2Writer<W> {
+    pub(crate) fn new(main: W) -> Self {
+        Self {
+            main,
+            call: Vec::new(),
+            jump: Vec::new(),
+            rc: RangeEncoder::new(),
+            probs: [BIT_MODEL_TOTAL >> 1; 2 + 256],
+            pending: Vec::new(),
+            main_buf: Vec::new(),
+            ip: 0,
+            prev: 0,
+            finished: false,
+            streams: Rc::default(),
+        }
+    }
+
+    /// Returns the streams besides the main stream, which are set once the encoder is
+    /// finished.
+    pub(crate) fn streams(&self) -> Rc<RefCell<Bcj2Streams>> {
+        Rc::clone(&self.streams)
+    }
+
+    /// Encodes the pending data. Unless `finish` is set, branch instructions near the end of
+    /// the pending data are kept until the following bytes are known.
+    fn encode(&mut self, finish: bool) -> io::Result<()> {
+        let Self {
+            pending,
+            main_buf,
+            call,
+            jump,
+            rc,
+            probs,
+            ip,
+            prev,
+            ..
+        } = self;
+
+        main_buf.clear();
+        let mut pos = 0;
+        while pos < pending.len() {
+            let b = pending[pos];
+            let is_branch = (b & 0xFE) == 0xE8 || (*prev == 0x0F && (b & 0xF0) == 0x80);
+            if !is_branch {
+                main_buf.push(b);
+                *prev = b;
+                pos += 1;
+                continue;
+            }
+            if pending.len() - pos < 5 && !finish {
+                break;
+            }
+
+            main_buf.push(b);
+            let prob = &mut probs[match b {
+                0xE8 => 2 + *prev as usize,
+                0xE9 => 1,
+                _ => 0,
+            }];
+            let relative = pending
+                .get(pos + 1..pos + 5)
+                .map(|target| u32::from_le_bytes(target.try_into().unwrap()))
+                .filter(|relative| relative.wrapping_add(RELATIVE_LIMIT) < 2 * RELATIVE_LIMIT);
+            match relative {
+                Some(relative) => {
+                    rc.encode_bit(prob, true);
+                    let next_ip = ip.wrapping_add(pos as u32 + 5);
+                    let absolute = relative.wrapping_add(next_ip).to_be_bytes();
+                    match b {
+                        0xE8 => call.extend_from_slice(&absolute),
+                        _ => jump.extend_from_slice(&absolute),
+                    }
+                    *prev = pending[pos + 4];
+                    pos += 5;
+                }
+                None => {
+                    rc.encode_bit(prob, false);
+                    *prev = b;
+                    pos += 1;
+                }
+            }
+        }
+
+        pending.drain(..pos);
+        *ip = ip.wrapping_add(pos as u32);
+        self.main.write_all(&self.main_buf)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.encode(true)?;
+        // Finishes the encoders of the main stream.
+        let _ = self.main.write(&[])?;
+        self.rc.finish();
+        *self.streams.borrow_mut() = Bcj2Streams {
+            call: std::mem::take(&mut self.call),
+            jump: std::mem::take(&mut self.jump),
+            rc: std::mem::take(&mut self.rc.out),
+        };
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Bcj2Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            self.finish()?;
+            return Ok(0);
+        }
+        self.pending.extend_from_slice(buf);
+        self.encode(false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.main.flush()
+    }
+}
+
+/// The range encoder of LZMA, which encodes the flags of the branch instructions.
+struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    out: Vec<u8>,
+}
+
+impl RangeEncoder {
+    fn new() -> Self {
+        Self {
+            low: 0,
+            range: 0xFFFF_FFFF,
+            cache: 0,
+            cache_size: 1,
+            out: Vec::new(),
+        }
+    }
+
+    fn encode_bit(&mut self, prob: &mut u16, bit: bool) {
+        let bound = (self.range >> NUM_MODEL_BITS) * *prob as u32;
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *prob -= *prob >> NUM_MOVE_BITS;
+        } else {
+            self.range = bound;
+            *prob += (BIT_MODEL_TOTAL - *prob) >> NUM_MOVE_BITS;
+        }
+        while self.range < TOP_VALUE {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xFF00_0000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            let mut temp = self.cache;
+            loop {
+                self.out.push(temp.wrapping_add(carry));
+                temp = 0xFF;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    fn finish(&mut self) {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::io::{self, Read};
+
+/// Size of the window of Deflate64, which allows distances of up to 64 KiB.
+const WINDOW_SIZE: usize = 1 << 16;
+
+const MAX_CODE_LENGTH: usize = 15;
+
+const END_OF_BLOCK: u16 = 256;
+
+/// Base lengths of the length codes 257 to 285. Unlike Deflate, the code 285 has 16 extra bits.
+const LENGTH_BASE: [u32; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 3,
+];
+
+const LENGTH_EXTRA_BITS: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 16,
+];
+
+/// Base distances of the distance codes. Unlike Deflate, the codes 30 and 31 are used.
+const DISTANCE_BASE: [u32; 32] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153,
+];
+
+const DISTANCE_EXTRA_BITS: [u32; 32] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13, 14, 14,
+];
+
+/// Order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    BlockHeader,
+    Stored { remaining: u16 },
+    Huffman,
+    Done,
+}
+
+/// Decoder of Deflate64, the variant of Deflate with a 64 KiB window and longer matches.
+pub(crate) struct Deflate64Decoder<R: Read> {
+    input: BitReader<R>,
+    state: State,
+    last_block: bool,
+    literal_table: HuffmanTable,
+    distance_table: HuffmanTable,
+    window: Box<[u8]>,
+    window_pos: usize,
+    /// Number of bytes in the window, which limits the distance of matches.
+    window_len: usize,
+    /// Remaining length of the match which is copied.
+    copy_len: usize,
+    copy_distance: usize,
+}
+
+impl<R: Read> Deflate64Decoder<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            input: BitReader::new(input),
+            state: State::BlockHeader,
+            last_block: false,
+            literal_table: HuffmanTable::default(),
+            distance_table: HuffmanTable::default(),
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            window_pos: 0,
+            window_len: 0,
+            copy_len: 0,
+            copy_distance: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.window[self.window_pos] = byte;
+        self.window_pos = (self.window_pos + 1) % WINDOW_SIZE;
+        self.window_len = (self.window_len + 1).min(WINDOW_SIZE);
+    }
+
+    fn end_block(&mut self) {
+        self.state = if self.last_block {
+            State::Done
+        } else {
+            State::BlockHeader
+        };
+    }
+
+    fn read_block_header(&mut self) -> io::Result<()> {
+        self.last_block = self.input.read_bits(1)? == 1;
+        match self.input.read_bits(2)? {
+            0 => {
+                self.input.align_to_byte()?;
+                let len = self.input.read_bits(16)? as u16;
+                let nlen = self.input.read_bits(16)? as u16;
+                if len != !nlen {
+                    return Err(io::Error::other("Invalid Deflate64 stored block length"));
+                }
+                self.state = State::Stored { remaining: len };
+            }
+            1 => {
+                let mut lengths = [0u8; 288 + 32];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+                self.literal_table = HuffmanTable::new(&lengths[..288])?;
+                self.distance_table = HuffmanTable::new(&lengths[288..])?;
+                self.state = State::Huffman;
+            }
+            2 => {
+                self.read_dynamic_tables()?;
+                self.state = State::Huffman;
+            }
+            _ => return Err(io::Error::other("Invalid Deflate64 block type")),
+        }
+        Ok(())
+    }
+
+    fn read_dynamic_tables(&mut self) -> io::Result<()> {
+        let num_literal_codes = self.input.read_bits(5)? as usize + 257;
+        let num_distance_codes = self.input.read_bits(5)? as usize + 1;
+        let num_code_length_codes = self.input.read_bits(4)? as usize + 4;
+
+        let mut code_length_lengths = [0u8; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..num_code_length_codes] {
+            code_length_lengths[symbol] = self.input.read_bits(3)? as u8;
+        }
+        let code_length_table = HuffmanTable::new(&code_length_lengths)?;
+
+        let num_codes = num_literal_codes + num_distance_codes;
+        let mut lengths = Vec::with_capacity(num_codes);
+        while lengths.len() < num_codes {
+            let symbol = self.input.decode(&code_length_table)?;
+            let (length, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let previous = *lengths
+                        .last()
+                        .ok_or_else(|| io::Error::other("Invalid Deflate64 code lengths"))?;
+                    (previous, 3 + self.input.read_bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.read_bits(3)? as usize),
+                _ => (0, 11 + self.input.read_bits(7)? as usize),
+            };
+            if lengths.len() + repeat > num_codes {
+                return Err(io::Error::other("Invalid Deflate64 code lengths"));
+            }
+            lengths.resize(lengths.len() + repeat, length);
+        }
+        if lengths[END_OF_BLOCK as usize] == 0 {
+            return Err(io::Error::other("Missing Deflate64 end of block code"));
+        }
+
+        self.literal_table = HuffmanTable::new(&lengths[..num_literal_codes])?;
+        self.distance_table = HuffmanTable::new(&lengths[num_literal_codes..])?;
+        Ok(())
+    }
+
+    /// Decodes the next literal or match of a Huffman block and returns the literal.
+    fn decode_symbol(&mut self) -> io::Result<Option<u8>> {
+        let symbol = self.input.decode(&self.literal_table)?;
+        if symbol < END_OF_BLOCK {
+            return Ok(Some(symbol as u8));
+        }
+        if symbol == END_OF_BLOCK {
+            self.end_block();
+            return Ok(None);
+        }
+
+        let length_code = (symbol - 257) as usize;
+        if length_code >= LENGTH_BASE.len() {
+            return Err(io::Error::other("Invalid Deflate64 length code"));
+        }
+        let length =
+            LENGTH_BASE[length_code] + self.input.read_bits(LENGTH_EXTRA_BITS[length_code])?;
+
+        let distance_code = self.input.decode(&self.distance_table)? as usize;
+        if distance_code >= DISTANCE_BASE.len() {
+            return Err(io::Error::other("Invalid Deflate64 distance code"));
+        }
+        let distance = DISTANCE_BASE[distance_code]
+            + self.input.read_bits(DISTANCE_EXTRA_BITS[distance_code])?;
+        if distance as usize > self.window_len {
+            return Err(io::Error::other("Invalid Deflate64 distance"));
+        }
+
+        self.copy_len = length as usize;
+        self.copy_distance = distance as usize;
+        Ok(None)
+    }
+}
+
+impl<R: Read> Read for Deflate64Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.copy_len > 0 {
+                let len = self.copy_len.min(buf.len() - written);
+                for out in &mut buf[written..written + len] {
+                    let byte = self.window
+                        [(self.window_pos + WINDOW_SIZE - self.copy_distance) % WINDOW_SIZE];
+                    self.push(byte);
+                    *out = byte;
+                }
+                self.copy_len -= len;
+                written += len;
+                continue;
+            }
+
+            match self.state {
+                State::BlockHeader => self.read_block_header()?,
+                State::Stored { remaining: 0 } => self.end_block(),
+                State::Stored { remaining } => {
+                    let byte = self.input.read_bits(8)? as u8;
+                    self.push(byte);
+                    buf[written] = byte;
+                    written += 1;
+                    self.state = State::Stored {
+                        remaining: remaining - 1,
+                    };
+                }
+                State::Huffman => {
+                    if let Some(byte) = self.decode_symbol()? {
+                        self.push(byte);
+                        buf[written] = byte;
+                        written += 1;
+                    }
+                }
+                State::Done => break,
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Lookup table of a canonical Huffman code, indexed by the next `bits` input bits.
+#[derive(Default)]
+struct HuffmanTable {
+    /// The symbol shifted by 4 bits and the length of its code, or 0 for invalid codes.
+    entries: Vec<u32>,
+    bits: u32,
+}
+
+impl HuffmanTable {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u32; MAX_CODE_LENGTH + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i64;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i64;
+            if left < 0 {
+                return Err(io::Error::other("Invalid Deflate64 Huffman code"));
+            }
+        }
+
+        let bits = counts
+            .iter()
+            .rposition(|&count| count > 0)
+            .unwrap_or(1)
+            .max(1) as u32;
+        let mut next_code = [0u32; MAX_CODE_LENGTH + 1];
+        let mut code = 0;
+        for length in 1..=MAX_CODE_LENGTH {
+            code = (code + counts[length - 1]) << 1;
+            next_code[length] = code;
+        }
+
+        let mut entries = vec![0; 1 << bits];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length == 0 {
+                continue;
+            }
+            let length = length as usize;
+            let code = next_code[length];
+            next_code[length] += 1;
+            // Huffman codes are stored with the most significant bit first.
+            let reversed = code.reverse_bits() >> (32 - length);
+            let entry = ((symbol as u32) << 4) | length as u32;
+            for index in (reversed as usize..entries.len()).step_by(1 << length) {
+                entries[index] = entry;
+            }
+        }
+        Ok(Self { entries, bits })
+    }
+}
+
+/// Reads the bits of the input starting with the least significant bit of each byte.
+struct BitReader<R: Read> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    bits: u64,
+    count: u32,
+    /// Number of zero bits appended to `bits` after the end of the input.
+    padding: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; 4096].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            bits: 0,
+            count: 0,
+            padding: 0,
+        }
+    }
+
+    fn refill(&mut self) -> io::Result<()> {
+        while self.count <= 56 {
+            if self.pos == self.len {
+                self.pos = 0;
+                self.len = self.inner.read(&mut self.buf)?;
+                if self.len == 0 {
+                    // Pads with zeros, so codes at the end of the input can be looked up.
+                    self.count += 8;
+                    self.padding += 8;
+                    continue;
+                }
+            }
+            self.bits |= (self.buf[self.pos] as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self, count: u32) -> io::Result<u32> {
+        if self.count < count {
+            self.refill()?;
+        }
+        Ok((self.bits & ((1 << count) - 1)) as u32)
+    }
+
+    fn consume(&mut self, count: u32) -> io::Result<()> {
+        self.bits >>= count;
+        self.count -= count;
+        if self.count < self.padding {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    fn read_bits(&mut self, count: u32) -> io::Result<u32> {
+        let value = self.peek(count)?;
+        self.consume(count)?;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) -> io::Result<()> {
+        self.consume(self.count % 8)
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> io::Result<u16> {
+        let entry = table.entries[self.peek(table.bits)? as usize];
+        let length = entry & 0xF;
+        if length == 0 {
+            return Err(io::Error::other("Invalid Deflate64 Huffman code"));
+        }
+        self.consume(length)?;
+        Ok((entry >> 4) as u16)
+    }
+}
@@ -0,0 +1,730 @@
+use std::{
+    borrow::Cow,
+    io::{self, Read},
+};
+
+use crate::ByteReader;
+
+/// Magic bytes of a Lizard frame.
+const LIZARD_MAGIC: u32 = 0x184D2206;
+/// Magic bytes of skippable frames, which zstdmt writes in front of each frame, range from
+/// `0x184D2A50` to `0x184D2A5F`.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D2A50;
+const SKIPPABLE_FRAME_MAGIC_MASK: u32 = 0xFFFFFFF0;
+
+/// Maximum block sizes of a frame by the block size id 1 to 7.
+const BLOCK_SIZES: [usize; 7] = [
+    128 << 10,
+    256 << 10,
+    1 << 20,
+    4 << 20,
+    16 << 20,
+    64 << 20,
+    256 << 20,
+];
+
+/// Matches refer to at most 16 MiB of previous data, which is kept between linked blocks.
+const DICT_SIZE: usize = 1 << 24;
+
+const MIN_LEVEL: u8 = 10;
+const MAX_LEVEL: u8 = 49;
+
+const FLAG_LITERALS: u8 = 1;
+const FLAG_TOKENS: u8 = 2;
+const FLAG_OFFSET16: u8 = 4;
+const FLAG_OFFSET24: u8 = 8;
+const FLAG_LENGTHS: u8 = 16;
+const FLAG_UNCOMPRESSED: u8 = 128;
+
+/// Match lengths of the LZ4 codewords start at 4.
+const LZ4_MIN_MATCH: usize = 4;
+const LZ4_RUN_MASK: usize = 15;
+const LZ4_MATCH_MASK: usize = 15;
+
+const LIZV1_MAX_SHORT_LITERAL_LEN: usize = 7;
+const LIZV1_MAX_SHORT_MATCH_LEN: usize = 15;
+/// Tokens below this value are matches with a 24-bit offset and the length `token + 16`.
+const LIZV1_LAST_LONG_OFFSET: u8 = 31;
+const LIZV1_MIN_LONG_OFFSET_MATCH: usize = 16;
+
+/// Maximum code length of the Huffman coded streams.
+const HUF_MAX_TABLE_LOG: u32 = 12;
+/// Maximum accuracy of the FSE coded Huffman weights.
+const FSE_MAX_WEIGHT_TABLE_LOG: u32 = 6;
+const FSE_MIN_TABLE_LOG: u32 = 5;
+
+#[derive(Debug, Clone, Copy)]
+struct FrameInfo {
+    independent_blocks: bool,
+    block_checksum: bool,
+    content_checksum: bool,
+    max_block_size: usize,
+}
+
+/// Decoder of Lizard frames, including the optional skippable frames of the format first
+/// implemented by zstdmt.
+///
+/// The checksums of the frames aren't verified, the decoded data is verified by the CRC of
+/// the archive.
+pub(crate) struct LizardDecoder<R: Read> {
+    input: R,
+    frame: Option<FrameInfo>,
+    /// Decoded data of the current frame. The data before `pos` was already read, but linked
+    /// blocks may still refer to it.
+    output: Vec<u8>,
+    pos: usize,
+    block: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> LizardDecoder<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            input,
+            frame: None,
+            output: Vec::new(),
+            pos: 0,
+            block: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the header of the next frame and returns false at the end of the input.
+    fn read_frame_header(&mut self) -> io::Result<bool> {
+        loop {
+            let magic = match self.input.read_u32() {
+                Ok(magic) => magic,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            if magic & SKIPPABLE_FRAME_MAGIC_MASK == SKIPPABLE_FRAME_MAGIC {
+                let size = self.input.read_u32()? as u64;
+                let skipped = io::copy(&mut (&mut self.input).take(size), &mut io::sink())?;
+                if skipped != size {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                continue;
+            }
+            if magic != LIZARD_MAGIC {
+                return Err(io::Error::other("Invalid Lizard frame magic"));
+            }
+
+            let flags = self.input.read_u8()?;
+            let block_descriptor = self.input.read_u8()?;
+            if flags >> 6 != 1 {
+                return Err(io::Error::other("Unsupported Lizard frame version"));
+            }
+            if flags & 0x03 != 0 || block_descriptor & 0x8F != 0 {
+                return Err(io::Error::other("Reserved Lizard frame flags are set"));
+            }
+            let max_block_size = match (block_descriptor >> 4) & 0x07 {
+                0 => return Err(io::Error::other("Invalid Lizard block size")),
+                id => BLOCK_SIZES[id as usize - 1],
+            };
+            if flags & 0x08 != 0 {
+                let _content_size = self.input.read_u64()?;
+            }
+            let _header_checksum = self.input.read_u8()?;
+
+            self.frame = Some(FrameInfo {
+                independent_blocks: flags & 0x20 != 0,
+                block_checksum: flags & 0x10 != 0,
+                content_checksum: flags & 0x04 != 0,
+                max_block_size,
+            });
+            self.output.clear();
+            self.pos = 0;
+            return Ok(true);
+        }
+    }
+
+    fn read_block(&mut self, frame: FrameInfo) -> io::Result<()> {
+        let size = self.input.read_u32()?;
+        if size == 0 {
+            if frame.content_checksum {
+                let _content_checksum = self.input.read_u32()?;
+            }
+            self.frame = None;
+            return Ok(());
+        }
+
+        let len = (size & 0x7FFFFFFF) as usize;
+        if len > frame.max_block_size {
+            return Err(io::Error::other("Lizard block is too large"));
+        }
+        self.block.resize(len, 0);
+        self.input.read_exact(&mut self.block)?;
+        if frame.block_checksum {
+            let _block_checksum = self.input.read_u32()?;
+        }
+
+        let history = if frame.independent_blocks {
+            0
+        } else {
+            DICT_SIZE
+        };
+        // The data linked blocks can't refer to anymore is only removed once as much of it
+        // accumulated as the history holds, so the history isn't moved for every block.
+        let unreferenced = self.output.len().saturating_sub(history);
+        if unreferenced > 0 && (history == 0 || unreferenced >= DICT_SIZE) {
+            self.output.drain(..unreferenced);
+        }
+        self.pos = self.output.len();
+
+        if size & 0x80000000 != 0 {
+            self.output.extend_from_slice(&self.block);
+            Ok(())
+        } else {
+            decompress_block(&self.block, &mut self.output, frame.max_block_size)
+        }
+    }
+}
+
+impl<R: Read> Read for LizardDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.output.len() {
+                let len = buf.len().min(self.output.len() - self.pos);
+                buf[..len].copy_from_slice(&self.output[self.pos..self.pos + len]);
+                self.pos += len;
+                return Ok(len);
+            }
+            if buf.is_empty() || self.finished {
+                return Ok(0);
+            }
+            match self.frame {
+                Some(frame) => self.read_block(frame)?,
+                None => self.finished = !self.read_frame_header()?,
+            }
+        }
+    }
+}
+
+fn corrupted() -> io::Error {
+    io::Error::other("Corrupted Lizard block")
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(corrupted());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u24(data: &mut &[u8]) -> io::Result<usize> {
+    let bytes = take(data, 3)?;
+    Ok(bytes[0] as usize | (bytes[1] as usize) << 8 | (bytes[2] as usize) << 16)
+}
+
+/// Reads a stream of a block, which is Huffman coded if `compressed` is set.
+fn read_stream<'a>(data: &mut &'a [u8], compressed: bool) -> io::Result<Cow<'a, [u8]>> {
+    if !compressed {
+        let len = take_u24(data)?;
+        return Ok(Cow::Borrowed(take(data, len)?));
+    }
+    let len = take_u24(data)?;
+    let compressed_len = take_u24(data)?;
+    let compressed = take(data, compressed_len)?;
+    Ok(Cow::Owned(huf_decompress(compressed, len)?))
+}
+
+/// Decodes the data of a compressed block, which consists of the compression level and
+/// sub-blocks with the streams of the codewords, and appends it to `output`.
+fn decompress_block(mut data: &[u8], output: &mut Vec<u8>, max_size: usize) -> io::Result<()> {
+    let limit = output.len() + max_size;
+    let level = take(&mut data, 1)?[0];
+    if !(MIN_LEVEL..=MAX_LEVEL).contains(&level) {
+        return Err(io::Error::other("Invalid Lizard compression level"));
+    }
+    // The levels 10 to 19 and 30 to 39 use the codewords of LZ4, the others those of LIZv1.
+    let lz4_codewords = (level - MIN_LEVEL) / 10 % 2 == 0;
+    let mut last_offset = 0;
+
+    while !data.is_empty() {
+        let flags = take(&mut data, 1)?[0];
+        if flags == FLAG_UNCOMPRESSED {
+            let len = take_u24(&mut data)?;
+            let bytes = take(&mut data, len)?;
+            if output.len() + len > limit {
+                return Err(corrupted());
+            }
+            output.extend_from_slice(bytes);
+            continue;
+        }
+        if flags & FLAG_LENGTHS != 0 {
+            return Err(io::Error::other(
+                "Huffman coded lengths of Lizard blocks are not supported",
+            ));
+        }
+
+        let lengths_len = take_u24(&mut data)?;
+        let lengths = take(&mut data, lengths_len)?;
+        let offsets16 = read_stream(&mut data, flags & FLAG_OFFSET16 != 0)?;
+        let offsets24 = read_stream(&mut data, flags & FLAG_OFFSET24 != 0)?;
+        let tokens = read_stream(&mut data, flags & FLAG_TOKENS != 0)?;
+        let literals = read_stream(&mut data, flags & FLAG_LITERALS != 0)?;
+
+        let mut sequences = Sequences {
+            lengths,
+            offsets16: &offsets16,
+            offsets24: &offsets24,
+            literals: &literals,
+            output: &mut *output,
+            limit,
+        };
+        if lz4_codewords {
+            sequences.decode_lz4(&tokens)?;
+        } else {
+            sequences.decode_lizv1(&tokens, &mut last_offset)?;
+        }
+        let last_literals = sequences.literals.len();
+        sequences.copy_literals(last_literals)?;
+    }
+    Ok(())
+}
+
+/// The streams of a sub-block, from which the codewords read their lengths, offsets and
+/// literals.
+struct Sequences<'a> {
+    lengths: &'a [u8],
+    offsets16: &'a [u8],
+    offsets24: &'a [u8],
+    literals: &'a [u8],
+    output: &'a mut Vec<u8>,
+    limit: usize,
+}
+
+impl Sequences<'_> {
+    /// Decodes the codewords of the levels 10 to 19 and 30 to 39: a token with a 4-bit literal
+    /// length and a 4-bit match length, followed by a 16-bit offset.
+    fn decode_lz4(&mut self, tokens: &[u8]) -> io::Result<()> {
+        for &token in tokens {
+            let mut literal_len = token as usize >> 4;
+            if literal_len == LZ4_RUN_MASK {
+                literal_len += self.read_length()?;
+            }
+            self.copy_literals(literal_len)?;
+
+            let offset = self.read_offset16()?;
+            let mut match_len = token as usize & LZ4_MATCH_MASK;
+            if match_len == LZ4_MATCH_MASK {
+                match_len += self.read_length()?;
+            }
+            self.copy_match(offset, match_len + LZ4_MIN_MATCH)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the codewords of the levels 20 to 29 and 40 to 49:
+    ///
+    /// - `[1_MMMM_LLL]`: literals and a match with the last offset,
+    /// - `[0_MMMM_LLL]`: literals and a match with a 16-bit offset,
+    /// - `[000_MMMMM]` below 31: a match of length 16 to 46 with a 24-bit offset,
+    /// - `31`: a match of length 47 or more with a 24-bit offset.
+    fn decode_lizv1(&mut self, tokens: &[u8], last_offset: &mut usize) -> io::Result<()> {
+        for &token in tokens {
+            let match_len = if token > LIZV1_LAST_LONG_OFFSET {
+                let mut literal_len = token as usize & LIZV1_MAX_SHORT_LITERAL_LEN;
+                if literal_len == LIZV1_MAX_SHORT_LITERAL_LEN {
+                    literal_len += self.read_length()?;
+                }
+                self.copy_literals(literal_len)?;
+
+                if token & 0x80 == 0 {
+                    *last_offset = self.read_offset16()?;
+                }
+                let mut match_len = (token as usize >> 3) & LIZV1_MAX_SHORT_MATCH_LEN;
+                if match_len == LIZV1_MAX_SHORT_MATCH_LEN {
+                    match_len += self.read_length()?;
+                }
+                match_len
+            } else if token < LIZV1_LAST_LONG_OFFSET {
+                *last_offset = self.read_offset24()?;
+                token as usize + LIZV1_MIN_LONG_OFFSET_MATCH
+            } else {
+                *last_offset = self.read_offset24()?;
+                self.read_length()? + LIZV1_LAST_LONG_OFFSET as usize + LIZV1_MIN_LONG_OFFSET_MATCH
+            };
+            if match_len > 0 {
+                self.copy_match(*last_offset, match_len)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a length from the lengths stream: a byte below 254, or 254 followed by a 16-bit
+    /// length, or 255 followed by a 24-bit length.
+    fn read_length(&mut self) -> io::Result<usize> {
+        let first = take(&mut self.lengths, 1)?[0];
+        match first {
+            254 => {
+                let bytes = take(&mut self.lengths, 2)?;
+                Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            }
+            255 => take_u24(&mut self.lengths),
+            len => Ok(len as usize),
+        }
+    }
+
+    fn read_offset16(&mut self) -> io::Result<usize> {
+        let bytes = take(&mut self.offsets16, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    }
+
+    fn read_offset24(&mut self) -> io::Result<usize> {
+        take_u24(&mut self.offsets24)
+    }
+
+    fn copy_literals(&mut self, len: usize) -> io::Result<()> {
+        if self.output.len() + len > self.limit {
+            return Err(corrupted());
+        }
+        let literals = take(&mut self.literals, len)?;
+        self.output.extend_from_slice(literals);
+        Ok(())
+    }
+
+    fn copy_match(&mut self, offset: usize, len: usize) -> io::Result<()> {
+        if offset == 0 || offset > self.output.len() || self.output.len() + len > self.limit {
+            return Err(corrupted());
+        }
+        let start = self.output.len() - offset;
+        if offset >= len {
+            self.output.extend_from_within(start..start + len);
+        } else {
+            for i in start..start + len {
+                let byte = self.output[i];
+                self.output.push(byte);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a stream compressed with Huff0, the Huffman coder of Zstandard, which splits the
+/// data into four bitstreams.
+fn huf_decompress(src: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    if len == 0 || src.len() > len {
+        return Err(corrupted());
+    }
+    if src.len() == len {
+        return Ok(src.to_vec());
+    }
+    if src.len() == 1 {
+        return Ok(vec![src[0]; len]);
+    }
+
+    let (table, header_len) = HuffmanTable::read(src)?;
+    let data = &src[header_len..];
+    if data.len() < 10 || len < 6 {
+        return Err(corrupted());
+    }
+    let mut stream_lens = [0; 4];
+    for (i, stream_len) in stream_lens[..3].iter_mut().enumerate() {
+        *stream_len = u16::from_le_bytes([data[2 * i], data[2 * i + 1]]) as usize;
+    }
+    stream_lens[3] = (data.len() - 6)
+        .checked_sub(stream_lens[..3].iter().sum())
+        .ok_or_else(corrupted)?;
+
+    let segment_len = len.div_ceil(4);
+    let mut output = Vec::with_capacity(len);
+    let mut streams = &data[6..];
+    for (i, &stream_len) in stream_lens.iter().enumerate() {
+        let stream = take(&mut streams, stream_len)?;
+        let count = if i < 3 {
+            segment_len
+        } else {
+            len - 3 * segment_len
+        };
+        let mut bits = BackwardBitReader::new(stream)?;
+        for _ in 0..count {
+            let (symbol, code_len) = table.entries[bits.peek(table.table_log) as usize];
+            bits.consume(code_len as u32);
+            output.push(symbol);
+        }
+        if !bits.is_finished() {
+            return Err(corrupted());
+        }
+    }
+    Ok(output)
+}
+
+/// Decoding table of Huff0 with the symbol and the code length for each value of the next
+/// `table_log` bits.
+struct HuffmanTable {
+    entries: Vec<(u8, u8)>,
+    table_log: u32,
+}
+
+impl HuffmanTable {
+    /// Reads the Huffman weights of the symbols and returns the table with the number of bytes
+    /// read.
+    fn read(src: &[u8]) -> io::Result<(Self, usize)> {
+        let header = src[0] as usize;
+        let (mut weights, header_len) = if header >= 128 {
+            let count = header - 127;
+            let bytes = src.get(1..1 + count.div_ceil(2)).ok_or_else(corrupted)?;
+            let weights = (0..count)
+                .map(|n| match n % 2 {
+                    0 => bytes[n / 2] >> 4,
+                    _ => bytes[n / 2] & 0x0F,
+                })
+                .collect::<Vec<_>>();
+            (weights, 1 + bytes.len())
+        } else {
+            let compressed = src.get(1..1 + header).ok_or_else(corrupted)?;
+            (fse_decompress_weights(compressed)?, 1 + header)
+        };
+        if weights.len() > 255 || weights.iter().any(|&w| w as u32 > HUF_MAX_TABLE_LOG) {
+            return Err(corrupted());
+        }
+
+        // The weight of the last symbol is implied, as the total must be a power of two.
+        let total: u32 = weights.iter().map(|&w| (1 << w) >> 1).sum();
+        if total == 0 {
+            return Err(corrupted());
+        }
+        let table_log = total.ilog2() + 1;
+        if table_log > HUF_MAX_TABLE_LOG {
+            return Err(corrupted());
+        }
+        let rest = (1 << table_log) - total;
+        if !rest.is_power_of_two() {
+            return Err(corrupted());
+        }
+        weights.push(rest.ilog2() as u8 + 1);
+        let weight1_count = weights.iter().filter(|&&w| w == 1).count();
+        if weight1_count < 2 || weight1_count % 2 != 0 {
+            return Err(corrupted());
+        }
+
+        // Codes are assigned from the lowest weight, which has the longest codes.
+        let mut entries = vec![(0, 0); 1 << table_log];
+        let mut pos = 0;
+        for weight in 1..=table_log as u8 {
+            for (symbol, _) in weights.iter().enumerate().filter(|&(_, &w)| w == weight) {
+                let len = 1 << (weight - 1);
+                entries[pos..pos + len].fill((symbol as u8, table_log as u8 + 1 - weight));
+                pos += len;
+            }
+        }
+        Ok((Self { entries, table_log }, header_len))
+    }
+}
+
+/// Decodes the FSE compressed Huffman weights.
+fn fse_decompress_weights(src: &[u8]) -> io::Result<Vec<u8>> {
+    let (counts, table_log, header_len) = read_fse_counts(src)?;
+    let table = build_fse_table(&counts, table_log)?;
+
+    let mut bits = BackwardBitReader::new(&src[header_len..])?;
+    let mut states = [bits.read(table_log) as usize, bits.read(table_log) as usize];
+    let mut weights = Vec::new();
+    // The two states are used alternately, until the bitstream is overflowed.
+    for i in (0..2).cycle() {
+        if weights.len() >= 255 {
+            return Err(corrupted());
+        }
+        let (symbol, nb_bits, baseline) = table[states[i]];
+        weights.push(symbol);
+        states[i] = baseline as usize + bits.read(nb_bits as u32) as usize;
+        if bits.is_overflowed() {
+            weights.push(table[states[1 - i]].0);
+            break;
+        }
+    }
+    Ok(weights)
+}
+
+/// Reads the normalized counts of an FSE table and returns them with the accuracy log and the
+/// number of bytes read.
+fn read_fse_counts(src: &[u8]) -> io::Result<(Vec<i16>, u32, usize)> {
+    let mut bits = ForwardBitReader { data: src, pos: 0 };
+    let table_log = bits.read(4) + FSE_MIN_TABLE_LOG;
+    if table_log > FSE_MAX_WEIGHT_TABLE_LOG {
+        return Err(corrupted());
+    }
+
+    let mut remaining = (1i32 << table_log) + 1;
+    let mut threshold = 1i32 << table_log;
+    let mut nb_bits = table_log + 1;
+    let mut counts = Vec::new();
+    while remaining > 1 {
+        if counts.len() > 255 {
+            return Err(corrupted());
+        }
+        let max = (2 * threshold - 1) - remaining;
+        let low = bits.peek(nb_bits - 1) as i32;
+        let count = if low < max {
+            bits.pos += nb_bits as usize - 1;
+            low
+        } else {
+            let value = bits.read(nb_bits) as i32;
+            if value >= threshold {
+                value - max
+            } else {
+                value
+            }
+        } - 1;
+        remaining -= count.abs();
+        counts.push(count as i16);
+
+        if count == 0 {
+            loop {
+                let repeat = bits.read(2) as usize;
+                counts.resize(counts.len() + repeat, 0);
+                if repeat != 3 {
+                    break;
+                }
+            }
+        }
+        if remaining < threshold {
+            if remaining <= 1 {
+                break;
+            }
+            nb_bits = remaining.ilog2() + 1;
+            threshold = 1 << (nb_bits - 1);
+        }
+    }
+    if remaining != 1 || counts.len() > 256 {
+        return Err(corrupted());
+    }
+    let header_len = bits.pos.div_ceil(8);
+    if header_len > src.len() {
+        return Err(corrupted());
+    }
+    Ok((counts, table_log, header_len))
+}
+
+/// Builds the FSE decoding table with the symbol, the number of bits and the baseline of the
+/// next state for each state.
+fn build_fse_table(counts: &[i16], table_log: u32) -> io::Result<Vec<(u8, u8, u16)>> {
+    let size = 1usize << table_log;
+    let mut symbols = vec![0u8; size];
+    let mut next_states = vec![0u32; counts.len()];
+
+    // Symbols with a probability below 1 get a single state at the end of the table.
+    let mut high_threshold = size;
+    for (symbol, &count) in counts.iter().enumerate() {
+        if count == -1 {
+            high_threshold = high_threshold.checked_sub(1).ok_or_else(corrupted)?;
+            symbols[high_threshold] = symbol as u8;
+            next_states[symbol] = 1;
+        } else {
+            next_states[symbol] = count.max(0) as u32;
+        }
+    }
+
+    let step = (size >> 1) + (size >> 3) + 3;
+    let mask = size - 1;
+    let mut position = 0;
+    for (symbol, &count) in counts.iter().enumerate() {
+        for _ in 0..count.max(0) {
+            symbols[position] = symbol as u8;
+            position = (position + step) & mask;
+            while position >= high_threshold {
+                position = (position + step) & mask;
+            }
+        }
+    }
+    if position != 0 {
+        return Err(corrupted());
+    }
+
+    symbols
+        .iter()
+        .map(|&symbol| {
+            let next_state = next_states[symbol as usize];
+            if next_state == 0 {
+                return Err(corrupted());
+            }
+            next_states[symbol as usize] += 1;
+            let nb_bits = table_log - next_state.ilog2();
+            let baseline = (next_state << nb_bits) - size as u32;
+            Ok((symbol, nb_bits as u8, baseline as u16))
+        })
+        .collect()
+}
+
+/// Reads bits from the least significant bit of the first byte on. Bits past the end of the
+/// data are zero.
+struct ForwardBitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl ForwardBitReader<'_> {
+    fn peek(&self, count: u32) -> u32 {
+        let byte = self.pos / 8;
+        let word = (0..4).fold(0u32, |word, i| {
+            word | (*self.data.get(byte + i).unwrap_or(&0) as u32) << (8 * i)
+        });
+        (word >> (self.pos % 8)) & ((1 << count) - 1)
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = self.peek(count);
+        self.pos += count as usize;
+        value
+    }
+}
+
+/// Reads a bitstream backwards from the end, which is marked by the highest set bit of the
+/// last byte. Bits before the start of the data are zero.
+struct BackwardBitReader<'a> {
+    data: &'a [u8],
+    /// Number of bits left to read, which is negative if more bits were read.
+    remaining: isize,
+}
+
+impl<'a> BackwardBitReader<'a> {
+    fn new(data: &'a [u8]) -> io::Result<Self> {
+        match data.last() {
+            Some(&last) if last != 0 => Ok(Self {
+                data,
+                remaining: (data.len() - 1) as isize * 8 + last.ilog2() as isize,
+            }),
+            _ => Err(corrupted()),
+        }
+    }
+
+    fn bits_at(&self, start: usize, count: u32) -> u32 {
+        let byte = start / 8;
+        let word = (0..4).fold(0u32, |word, i| {
+            word | (*self.data.get(byte + i).unwrap_or(&0) as u32) << (8 * i)
+        });
+        (word >> (start % 8)) & ((1 << count) - 1)
+    }
+
+    fn peek(&self, count: u32) -> u32 {
+        let start = self.remaining - count as isize;
+        if start >= 0 {
+            self.bits_at(start as usize, count)
+        } else if self.remaining > 0 {
+            self.bits_at(0, self.remaining as u32) << -start
+        } else {
+            0
+        }
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.remaining -= count as isize;
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = self.peek(count);
+        self.consume(count);
+        value
+    }
+
+    fn is_finished(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn is_overflowed(&self) -> bool {
+        self.remaining < 0
+    }
+}
@@ -0,0 +1,272 @@
+use std::io::{self, Read};
+
+use crate::ByteReader;
+
+/// Magic bytes of an LZ5 frame.
+const LZ5_MAGIC: u32 = 0x184D2205;
+/// Magic bytes of skippable frames, which zstdmt writes in front of each frame, range from
+/// `0x184D2A50` to `0x184D2A5F`.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D2A50;
+const SKIPPABLE_FRAME_MAGIC_MASK: u32 = 0xFFFFFFF0;
+
+/// Maximum block sizes of a frame by the block size id 1 to 7.
+const BLOCK_SIZES: [usize; 7] = [
+    128 << 10,
+    256 << 10,
+    1 << 20,
+    4 << 20,
+    16 << 20,
+    64 << 20,
+    256 << 20,
+];
+
+/// Matches refer to at most 16 MiB of previous data, which is kept between linked blocks.
+const DICT_SIZE: usize = 1 << 24;
+
+/// Match lengths start at 3.
+const MIN_MATCH: usize = 3;
+const MATCH_MASK: usize = 7;
+/// Literal lengths of the codewords with a 16-bit offset have 3 bits, the others 2 bits.
+const RUN_MASK: usize = 7;
+const RUN_MASK2: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct FrameInfo {
+    independent_blocks: bool,
+    block_checksum: bool,
+    content_checksum: bool,
+    max_block_size: usize,
+}
+
+/// Decoder of the LZ5 v1.5 frames written by the LZS method of 7-Zip ZS, including the
+/// optional skippable frames of the format first implemented by zstdmt.
+///
+/// The checksums of the frames aren't verified, the decoded data is verified by the CRC of
+/// the archive.
+pub(crate) struct LzsDecoder<R: Read> {
+    input: R,
+    frame: Option<FrameInfo>,
+    /// Decoded data of the current frame. The data before `pos` was already read, but linked
+    /// blocks may still refer to it.
+    output: Vec<u8>,
+    pos: usize,
+    block: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> LzsDecoder<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            input,
+            frame: None,
+            output: Vec::new(),
+            pos: 0,
+            block: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the header of the next frame and returns false at the end of the input.
+    fn read_frame_header(&mut self) -> io::Result<bool> {
+        loop {
+            let magic = match self.input.read_u32() {
+                Ok(magic) => magic,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            if magic & SKIPPABLE_FRAME_MAGIC_MASK == SKIPPABLE_FRAME_MAGIC {
+                let size = self.input.read_u32()? as u64;
+                let skipped = io::copy(&mut (&mut self.input).take(size), &mut io::sink())?;
+                if skipped != size {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                continue;
+            }
+            if magic != LZ5_MAGIC {
+                return Err(io::Error::other("Invalid LZ5 frame magic"));
+            }
+
+            let flags = self.input.read_u8()?;
+            let block_descriptor = self.input.read_u8()?;
+            if flags >> 6 != 1 {
+                return Err(io::Error::other("Unsupported LZ5 frame version"));
+            }
+            if flags & 0x03 != 0 || block_descriptor & 0x8F != 0 {
+                return Err(io::Error::other("Reserved LZ5 frame flags are set"));
+            }
+            let max_block_size = match (block_descriptor >> 4) & 0x07 {
+                0 => return Err(io::Error::other("Invalid LZ5 block size")),
+                id => BLOCK_SIZES[id as usize - 1],
+            };
+            if flags & 0x08 != 0 {
+                let _content_size = self.input.read_u64()?;
+            }
+            let _header_checksum = self.input.read_u8()?;
+
+            self.frame = Some(FrameInfo {
+                independent_blocks: flags & 0x20 != 0,
+                block_checksum: flags & 0x10 != 0,
+                content_checksum: flags & 0x04 != 0,
+                max_block_size,
+            });
+            self.output.clear();
+            self.pos = 0;
+            return Ok(true);
+        }
+    }
+
+    fn read_block(&mut self, frame: FrameInfo) -> io::Result<()> {
+        let size = self.input.read_u32()?;
+        if size == 0 {
+            if frame.content_checksum {
+                let _content_checksum = self.input.read_u32()?;
+            }
+            self.frame = None;
+            return Ok(());
+        }
+
+        let len = (size & 0x7FFFFFFF) as usize;
+        if len > frame.max_block_size {
+            return Err(io::Error::other("LZ5 block is too large"));
+        }
+        self.block.resize(len, 0);
+        self.input.read_exact(&mut self.block)?;
+        if frame.block_checksum {
+            let _block_checksum = self.input.read_u32()?;
+        }
+
+        let history = if frame.independent_blocks {
+            0
+        } else {
+            DICT_SIZE
+        };
+        // The data linked blocks can't refer to anymore is only removed once as much of it
+        // accumulated as the history holds, so the history isn't moved for every block.
+        let unreferenced = self.output.len().saturating_sub(history);
+        if unreferenced > 0 && (history == 0 || unreferenced >= DICT_SIZE) {
+            self.output.drain(..unreferenced);
+        }
+        self.pos = self.output.len();
+
+        if size & 0x80000000 != 0 {
+            self.output.extend_from_slice(&self.block);
+            Ok(())
+        } else {
+            decompress_block(&self.block, &mut self.output, frame.max_block_size)
+        }
+    }
+}
+
+impl<R: Read> Read for LzsDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.output.len() {
+                let len = buf.len().min(self.output.len() - self.pos);
+                buf[..len].copy_from_slice(&self.output[self.pos..self.pos + len]);
+                self.pos += len;
+                return Ok(len);
+            }
+            if buf.is_empty() || self.finished {
+                return Ok(0);
+            }
+            match self.frame {
+                Some(frame) => self.read_block(frame)?,
+                None => self.finished = !self.read_frame_header()?,
+            }
+        }
+    }
+}
+
+fn corrupted() -> io::Error {
+    io::Error::other("Corrupted LZ5 block")
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(corrupted());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Reads the continuation of a literal or match length: bytes which are added up until a
+/// byte below 255.
+fn read_length(data: &mut &[u8]) -> io::Result<usize> {
+    let mut len = 0;
+    loop {
+        let byte = take(data, 1)?[0];
+        len += byte as usize;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// Decodes the codewords of a compressed block and appends the data to `output`:
+///
+/// - `[1_OO_LL_MMM]`: a match with a 10-bit offset, whose low byte follows the literals,
+/// - `[00_LLL_MMM]`: a match with a 16-bit offset,
+/// - `[010_LL_MMM]`: a match with a 24-bit offset,
+/// - `[011_LL_MMM]`: a match with the last offset.
+///
+/// Like in LZ4, a codeword is followed by the continuation of the literal length, the
+/// literals, the offset and the continuation of the match length. The block ends with the
+/// literals of its last codeword.
+fn decompress_block(mut data: &[u8], output: &mut Vec<u8>, max_size: usize) -> io::Result<()> {
+    let limit = output.len() + max_size;
+    let mut last_offset = 0;
+
+    while !data.is_empty() {
+        let token = take(&mut data, 1)?[0];
+        let run_mask = if token >> 6 == 0 { RUN_MASK } else { RUN_MASK2 };
+        let mut literal_len = (token as usize >> 3) & run_mask;
+        if literal_len == run_mask {
+            literal_len += read_length(&mut data)?;
+        }
+        if output.len() + literal_len > limit {
+            return Err(corrupted());
+        }
+        output.extend_from_slice(take(&mut data, literal_len)?);
+        if data.is_empty() {
+            break;
+        }
+
+        let offset = match token >> 5 {
+            0b100..=0b111 => (token as usize >> 5 & 0x03) << 8 | take(&mut data, 1)?[0] as usize,
+            0b000 | 0b001 => {
+                let bytes = take(&mut data, 2)?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            }
+            0b010 => {
+                let bytes = take(&mut data, 3)?;
+                bytes[0] as usize | (bytes[1] as usize) << 8 | (bytes[2] as usize) << 16
+            }
+            _ => last_offset,
+        };
+        last_offset = offset;
+
+        let mut match_len = token as usize & MATCH_MASK;
+        if match_len == MATCH_MASK {
+            match_len += read_length(&mut data)?;
+        }
+        copy_match(output, offset, match_len + MIN_MATCH, limit)?;
+    }
+    Ok(())
+}
+
+fn copy_match(output: &mut Vec<u8>, offset: usize, len: usize, limit: usize) -> io::Result<()> {
+    if offset == 0 || offset > output.len() || output.len() + len > limit {
+        return Err(corrupted());
+    }
+    let start = output.len() - offset;
+    if offset >= len {
+        output.extend_from_within(start..start + len);
+    } else {
+        for i in start..start + len {
+            let byte = output[i];
+            output.push(byte);
+        }
+    }
+    Ok(())
+}
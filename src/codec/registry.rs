@@ -0,0 +1,85 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use crate::Error;
+
+/// A codec for a method that isn't supported by this crate, like a private filter.
+///
+/// Codecs are registered with [`ArchiveReader::register_codec`] to decode and with
+/// [`ArchiveWriter::register_codec`] to encode the coders of their method id. To write a
+/// coder with a custom method, use an [`EncoderMethod`] created with [`EncoderMethod::new`]
+/// and pass its properties with [`EncoderOptions::Custom`].
+///
+/// The codecs built into this crate take precedence over registered codecs.
+///
+/// [`ArchiveReader::register_codec`]: crate::ArchiveReader::register_codec
+/// [`ArchiveWriter::register_codec`]: crate::ArchiveWriter::register_codec
+/// [`EncoderMethod`]: crate::EncoderMethod
+/// [`EncoderMethod::new`]: crate::EncoderMethod::new
+/// [`EncoderOptions::Custom`]: crate::encoder_options::EncoderOptions::Custom
+pub trait Codec: Send + Sync {
+    /// Returns the method id of the coders handled by this codec.
+    fn method_id(&self) -> &[u8];
+
+    /// Creates the decoder of the packed data read from `input`, which was encoded with the
+    /// coder `properties` and decodes to `unpack_size` bytes.
+    fn decoder<'r>(
+        &self,
+        input: Box<dyn Read + 'r>,
+        properties: &[u8],
+        unpack_size: u64,
+    ) -> Result<Box<dyn Read + 'r>, Error>;
+
+    /// Creates the encoder writing the encoded data to `output`, which is stored with the
+    /// coder `properties`.
+    ///
+    /// An empty write finishes the encoder: it has to write its remaining data to `output`
+    /// and then pass the empty write on to `output`, which finishes the following coders.
+    ///
+    /// By default the codec only supports decoding.
+    fn encoder<'w>(
+        &self,
+        output: Box<dyn Write + 'w>,
+        properties: &[u8],
+    ) -> Result<Box<dyn Write + 'w>, Error> {
+        let _ = (output, properties);
+        Err(Error::UnsupportedCompressionMethod(format!(
+            "{:?}",
+            self.method_id()
+        )))
+    }
+}
+
+/// The codecs registered with a reader or writer.
+#[derive(Clone, Default)]
+pub(crate) struct CodecRegistry {
+    codecs: Vec<Arc<dyn Codec>>,
+}
+
+impl CodecRegistry {
+    /// Registers `codec`, which replaces a codec registered before for the same method id.
+    pub(crate) fn register(&mut self, codec: Arc<dyn Codec>) {
+        self.codecs
+            .retain(|registered| registered.method_id() != codec.method_id());
+        self.codecs.push(codec);
+    }
+
+    /// Returns the codec registered for the method `id`.
+    pub(crate) fn get(&self, id: &[u8]) -> Option<&dyn Codec> {
+        self.codecs
+            .iter()
+            .find(|codec| codec.method_id() == id)
+            .map(|codec| codec.as_ref())
+    }
+}
+
+impl fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.codecs.iter().map(|codec| codec.method_id()))
+            .finish()
+    }
+}
@@ -7,7 +7,7 @@ use flate2::bufread::DeflateDecoder;
 use lzma_rust2::{
     Lzma2Reader, Lzma2ReaderMt, LzmaReader,
     filter::{bcj::BcjReader, delta::DeltaReader},
-    lzma2_get_memory_usage,
+    lzma_get_memory_usage_by_props, lzma2_get_memory_usage,
 };
 #[cfg(feature = "ppmd")]
 use ppmd_rust::{
@@ -16,13 +16,22 @@ use ppmd_rust::{
 
 #[cfg(feature = "brotli")]
 use crate::codec::brotli::BrotliDecoder;
+#[cfg(feature = "deflate64")]
+use crate::codec::deflate64::Deflate64Decoder;
+#[cfg(feature = "lizard")]
+use crate::codec::lizard::LizardDecoder;
 #[cfg(feature = "lz4")]
 use crate::codec::lz4::Lz4Decoder;
+#[cfg(feature = "lzs")]
+use crate::codec::lzs::LzsDecoder;
 #[cfg(feature = "aes256")]
 use crate::encryption::Aes256Sha256Decoder;
-use crate::{ByteReader, Password, archive::EncoderMethod, block::Coder, error::Error};
+use crate::{
+    ByteReader, Password, archive::EncoderMethod, block::Coder, codec::registry::CodecRegistry,
+    error::Error,
+};
 
-pub enum Decoder<R: Read> {
+pub enum Decoder<'r, R: Read> {
     Copy(R),
     Lzma(Box<LzmaReader<R>>),
     Lzma2(Box<Lzma2Reader<R>>),
@@ -37,15 +46,22 @@ pub enum Decoder<R: Read> {
     Bzip2(BzDecoder<R>),
     #[cfg(feature = "deflate")]
     Deflate(DeflateDecoder<std::io::BufReader<R>>),
+    #[cfg(feature = "deflate64")]
+    Deflate64(Box<Deflate64Decoder<R>>),
+    #[cfg(feature = "lizard")]
+    Lizard(Box<LizardDecoder<R>>),
     #[cfg(feature = "lz4")]
     Lz4(Lz4Decoder<R>),
+    #[cfg(feature = "lzs")]
+    Lzs(Box<LzsDecoder<R>>),
     #[cfg(feature = "zstd")]
     Zstd(zstd::Decoder<'static, std::io::BufReader<R>>),
     #[cfg(feature = "aes256")]
     Aes256Sha256(Box<Aes256Sha256Decoder<R>>),
+    Custom(Box<dyn Read + 'r>),
 }
 
-impl<R: Read> Read for Decoder<R> {
+impl<R: Read> Read for Decoder<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Decoder::Copy(r) => r.read(buf),
@@ -62,32 +78,38 @@ impl<R: Read> Read for Decoder<R> {
             Decoder::Bzip2(r) => r.read(buf),
             #[cfg(feature = "deflate")]
             Decoder::Deflate(r) => r.read(buf),
+            #[cfg(feature = "deflate64")]
+            Decoder::Deflate64(r) => r.read(buf),
+            #[cfg(feature = "lizard")]
+            Decoder::Lizard(r) => r.read(buf),
             #[cfg(feature = "lz4")]
             Decoder::Lz4(r) => r.read(buf),
+            #[cfg(feature = "lzs")]
+            Decoder::Lzs(r) => r.read(buf),
             #[cfg(feature = "zstd")]
             Decoder::Zstd(r) => r.read(buf),
             #[cfg(feature = "aes256")]
             Decoder::Aes256Sha256(r) => r.read(buf),
+            Decoder::Custom(r) => r.read(buf),
         }
     }
 }
 
-pub fn add_decoder<I: Read>(
+pub fn add_decoder<'r, I: Read + 'r>(
     input: I,
     uncompressed_len: usize,
     coder: &Coder,
     #[allow(unused)] password: &Password,
     max_mem_limit_kb: usize,
     threads: u32,
-) -> Result<Decoder<I>, Error> {
+    codecs: &CodecRegistry,
+) -> Result<Decoder<'r, I>, Error> {
     let method = EncoderMethod::by_id(coder.encoder_method_id());
     let method = if let Some(m) = method {
         m
     } else {
-        return Err(Error::UnsupportedCompressionMethod(format!(
-            "{:?}",
-            coder.encoder_method_id()
-        )));
+        let name = format!("{:?}", coder.encoder_method_id());
+        return add_custom_decoder(input, uncompressed_len, coder, codecs, name);
     };
     match method.id() {
         EncoderMethod::ID_COPY => Ok(Decoder::Copy(input)),
@@ -97,6 +119,13 @@ pub fn add_decoder<I: Read>(
                 return Err(Error::Other("LZMA properties too short".into()));
             }
             let props = coder.properties[0];
+            let mem_size = lzma_get_memory_usage_by_props(dict_size, props)? as usize;
+            if mem_size > max_mem_limit_kb {
+                return Err(Error::MaxMemLimited {
+                    max_kb: max_mem_limit_kb,
+                    actaul_kb: mem_size,
+                });
+            }
             let lz =
                 LzmaReader::new_with_props(input, uncompressed_len as _, props, dict_size, None)
                     .map_err(|e| Error::bad_password(e, !password.is_empty()))?;
@@ -143,11 +172,26 @@ pub fn add_decoder<I: Read>(
             let de = DeflateDecoder::new(buf_read);
             Ok(Decoder::Deflate(de))
         }
+        #[cfg(feature = "deflate64")]
+        EncoderMethod::ID_DEFLATE64 => {
+            let de = Deflate64Decoder::new(input);
+            Ok(Decoder::Deflate64(Box::new(de)))
+        }
+        #[cfg(feature = "lizard")]
+        EncoderMethod::ID_LIZARD => {
+            let de = LizardDecoder::new(input);
+            Ok(Decoder::Lizard(Box::new(de)))
+        }
         #[cfg(feature = "lz4")]
         EncoderMethod::ID_LZ4 => {
             let de = Lz4Decoder::new(input)?;
             Ok(Decoder::Lz4(de))
         }
+        #[cfg(feature = "lzs")]
+        EncoderMethod::ID_LZS => {
+            let de = LzsDecoder::new(input);
+            Ok(Decoder::Lzs(Box::new(de)))
+        }
         #[cfg(feature = "zstd")]
         EncoderMethod::ID_ZSTD => {
             let zs = zstd::Decoder::new(input)?;
@@ -202,12 +246,53 @@ pub fn add_decoder<I: Read>(
             let de = Aes256Sha256Decoder::new(input, &coder.properties, password)?;
             Ok(Decoder::Aes256Sha256(Box::new(de)))
         }
-        _ => Err(Error::UnsupportedCompressionMethod(
+        _ => add_custom_decoder(
+            input,
+            uncompressed_len,
+            coder,
+            codecs,
             method.name().to_string(),
-        )),
+        ),
     }
 }
 
+/// Creates the decoder of the codec registered for the method of `coder`, or returns an
+/// unsupported method error with the method `name`.
+fn add_custom_decoder<'r, I: Read + 'r>(
+    input: I,
+    uncompressed_len: usize,
+    coder: &Coder,
+    codecs: &CodecRegistry,
+    name: String,
+) -> Result<Decoder<'r, I>, Error> {
+    let Some(codec) = codecs.get(coder.encoder_method_id()) else {
+        return Err(Error::UnsupportedCompressionMethod(name));
+    };
+    let de = codec.decoder(Box::new(input), &coder.properties, uncompressed_len as u64)?;
+    Ok(Decoder::Custom(de))
+}
+
+/// Returns an estimate of the memory in bytes the decoder of `coder` needs, which is mostly
+/// determined by the dictionary size of LZMA and LZMA2 and the memory size of PPMD.
+pub(crate) fn memory_usage(coder: &Coder) -> usize {
+    let Some(method) = EncoderMethod::by_id(coder.encoder_method_id()) else {
+        return 0;
+    };
+    let memory_kb = match method.id() {
+        EncoderMethod::ID_LZMA => get_lzma_dic_size(coder)
+            .ok()
+            .zip(coder.properties.first())
+            .and_then(|(dict_size, &props)| lzma_get_memory_usage_by_props(dict_size, props).ok()),
+        EncoderMethod::ID_LZMA2 => get_lzma2_dic_size(coder).ok().map(lzma2_get_memory_usage),
+        #[cfg(feature = "ppmd")]
+        EncoderMethod::ID_PPMD => get_ppmd_order_memory_size(coder, usize::MAX)
+            .ok()
+            .map(|(_, memory_size)| memory_size / 1024),
+        _ => None,
+    };
+    memory_kb.map_or(0, |memory_kb| memory_kb as usize * 1024)
+}
+
 #[cfg(feature = "ppmd")]
 fn get_ppmd_order_memory_size(coder: &Coder, max_mem_limit_kb: usize) -> Result<(u32, u32), Error> {
     if coder.properties.len() < 5 {
@@ -241,10 +326,11 @@ fn get_ppmd_order_memory_size(coder: &Coder, max_mem_limit_kb: usize) -> Result<
         ));
     }
 
-    if memory_size as usize > max_mem_limit_kb {
+    let memory_size_kb = memory_size as usize / 1024;
+    if memory_size_kb > max_mem_limit_kb {
         return Err(Error::MaxMemLimited {
             max_kb: max_mem_limit_kb,
-            actaul_kb: memory_size as usize,
+            actaul_kb: memory_size_kb,
         });
     }
 
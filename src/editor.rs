@@ -0,0 +1,622 @@
+use std::{
+    cell::RefCell,
+    io::{self, Read, Seek, Write},
+    num::NonZeroUsize,
+    rc::Rc,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, path::Path};
+
+use crate::{
+    Archive, ArchiveAppender, ArchiveEntry, ArchiveReader, ArchiveWriter, Error, Password, SetLen,
+    SourceReader,
+    bitset::BitSet,
+    reader::{Crc32VerifyingReader, SharedBoundedReader},
+};
+#[cfg(feature = "aes256")]
+use crate::{EncoderMethod, encoder_options::AesEncoderOptions};
+
+/// Edits an existing 7z archive.
+///
+/// Entries can be removed and the metadata of entries (names, timestamps, attributes) can be
+/// changed with [`ArchiveEditor::entries_mut`]. The changes are either written as a modified
+/// copy of the archive to an [`ArchiveWriter`] with [`ArchiveEditor::write_to`], or, if only
+/// metadata was changed, directly into the source with [`ArchiveEditor::update_in_place`].
+///
+/// Packed streams of blocks which are not affected by the changes are copied verbatim,
+/// without decoding or encoding their data. Only solid blocks which contain both kept and
+/// removed entries are decoded and re-encoded with the content methods of the writer.
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::*;
+///
+/// let mut editor = ArchiveEditor::open("path/to/source.7z", Password::empty()).unwrap();
+/// editor.remove(|entry| entry.name().ends_with(".env"));
+///
+/// let mut writer = ArchiveWriter::create("path/to/dest.7z").unwrap();
+/// editor.write_to(&mut writer).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct ArchiveEditor<R: Read + Seek> {
+    source: R,
+    archive: Archive,
+    password: Password,
+    entries: Vec<ArchiveEntry>,
+    removed: BitSet,
+    thread_count: u32,
+    #[cfg(feature = "aes256")]
+    header_encryption: Option<AesEncoderOptions>,
+    /// Password to re-encrypt the encrypted blocks with. An empty password removes the encryption.
+    #[cfg(feature = "aes256")]
+    new_password: Option<Password>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArchiveEditor<File> {
+    /// Opens a 7z archive file at the given `path` and creates a [`ArchiveEditor`] to edit it.
+    pub fn open(path: impl AsRef<Path>, password: Password) -> Result<Self, Error> {
+        let file = File::open(path.as_ref())
+            .map_err(|e| Error::file_open(e, path.as_ref().to_string_lossy().to_string()))?;
+        Self::new(file, password)
+    }
+}
+
+impl<R: Read + Seek> ArchiveEditor<R> {
+    /// Creates a [`ArchiveEditor`] to edit a 7z archive read from the given `source` reader.
+    pub fn new(mut source: R, password: Password) -> Result<Self, Error> {
+        let archive = Archive::read(&mut source, &password)?;
+        Ok(Self::from_archive(archive, source, password))
+    }
+
+    /// Creates an [`ArchiveEditor`] from an existing [`Archive`] instance.
+    pub fn from_archive(archive: Archive, source: R, password: Password) -> Self {
+        let thread_count =
+            std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap());
+        Self {
+            entries: archive.files.clone(),
+            removed: BitSet::with_capacity(archive.files.len()),
+            #[cfg(feature = "aes256")]
+            header_encryption: archive
+                .header_encrypted
+                .then(|| AesEncoderOptions::new(password.clone())),
+            #[cfg(feature = "aes256")]
+            new_password: None,
+            source,
+            archive,
+            password,
+            thread_count: thread_count.get() as u32,
+        }
+    }
+
+    /// Returns a reference to the underlying [`Archive`] structure.
+    #[inline]
+    pub fn archive(&self) -> &Archive {
+        &self.archive
+    }
+
+    /// Returns the entries of the archive including all metadata changes.
+    #[inline]
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// Returns the entries of the archive to change their metadata.
+    ///
+    /// Only the metadata of the entries can be changed, like the name, timestamps, attributes
+    /// and the anti-item flag. Changes to the data related fields (`has_stream`, `size`,
+    /// `has_crc`, `crc`, `compressed_crc` and `compressed_size`) are ignored.
+    #[inline]
+    pub fn entries_mut(&mut self) -> &mut [ArchiveEntry] {
+        &mut self.entries
+    }
+
+    /// Sets the AES options to encrypt the header written by [`ArchiveEditor::update_in_place`]
+    /// or `None` to write an unencrypted header.
+    ///
+    /// Defaults to encrypting the header with the password of the archive,
+    /// if the header of the archive is encrypted.
+    #[cfg(feature = "aes256")]
+    pub fn set_header_encryption(&mut self, options: Option<AesEncoderOptions>) {
+        self.header_encryption = options;
+    }
+
+    /// Changes the password of the encrypted blocks to `new_password`, or removes their
+    /// encryption if `new_password` is `None`. The editor has to be created with the current
+    /// password of the archive.
+    ///
+    /// The change is applied by [`ArchiveEditor::write_to`]: the AES encrypted packed streams
+    /// are decrypted and re-encrypted with a new key, salt and IV, without recompressing their
+    /// data. Since 7z archives don't store a password check value, the start of every block
+    /// is decoded once to verify the current password. Blocks which are re-encoded because of
+    /// removed entries use the content methods of the writer instead, so writing fails if an
+    /// encrypted block would be re-encoded without [`AesEncoderOptions`] in them.
+    ///
+    /// Unless the header encryption of the writer is disabled, the header is encrypted with
+    /// the new password.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut editor = ArchiveEditor::open("path/to/source.7z", "old password".into()).unwrap();
+    /// editor.rekey(Some("new password".into()));
+    ///
+    /// let mut writer = ArchiveWriter::create("path/to/dest.7z").unwrap();
+    /// editor.write_to(&mut writer).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    #[cfg(feature = "aes256")]
+    pub fn rekey(&mut self, new_password: Option<Password>) {
+        self.new_password = Some(new_password.unwrap_or_else(Password::empty));
+    }
+
+    /// Sets the thread count to use when multi-threading is supported by the de-compression
+    /// of blocks which need to be re-encoded.
+    ///
+    /// Defaults to `std::thread::available_parallelism()` if not set manually.
+    pub fn set_thread_count(&mut self, thread_count: u32) {
+        self.thread_count = thread_count.clamp(1, 256);
+    }
+
+    /// Marks all entries for which `predicate` returns `true` as removed.
+    ///
+    /// Returns the number of entries that were newly marked as removed.
+    pub fn remove<F: FnMut(&ArchiveEntry) -> bool>(&mut self, mut predicate: F) -> usize {
+        let mut count = 0;
+        for (index, entry) in self.archive.files.iter().enumerate() {
+            if !self.removed.contains(index) && predicate(entry) {
+                self.removed.insert(index);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Writes all entries which were not removed to `writer`.
+    ///
+    /// The packed streams of blocks without removed entries are copied verbatim, or
+    /// re-encrypted if [`ArchiveEditor::rekey`] was called. Blocks with removed entries are
+    /// re-encoded with the content methods of `writer`.
+    ///
+    /// The writer still needs to be finished afterward, so further entries can be
+    /// added to it before.
+    pub fn write_to<W: Write + Seek>(
+        &mut self,
+        writer: &mut ArchiveWriter<W>,
+    ) -> Result<(), Error> {
+        self.write(writer, None)
+    }
+
+    /// Writes all entries which were not removed to `writer`, re-encoding all data with the
+    /// content methods of `writer`.
+    ///
+    /// The data is streamed from the source to the writer block by block, without using
+    /// temporary files. `grouping` controls how the entries are grouped into blocks. All
+    /// metadata of the entries is preserved, including the order of the entries.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut editor = ArchiveEditor::open("path/to/source.7z", Password::empty()).unwrap();
+    /// let mut writer = ArchiveWriter::create("path/to/dest.7z").unwrap();
+    /// writer.set_content_methods(vec![EncoderMethod::LZMA2.into()]);
+    /// editor
+    ///     .transcode_to(&mut writer, BlockGrouping::Solid)
+    ///     .unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn transcode_to<W: Write + Seek>(
+        &mut self,
+        writer: &mut ArchiveWriter<W>,
+        grouping: BlockGrouping,
+    ) -> Result<(), Error> {
+        self.write(writer, Some(grouping))
+    }
+
+    /// Writes all kept entries to `writer`. Blocks are only re-encoded if they contain
+    /// removed entries or if `transcode` is set.
+    fn write<W: Write + Seek>(
+        &mut self,
+        writer: &mut ArchiveWriter<W>,
+        transcode: Option<BlockGrouping>,
+    ) -> Result<(), Error> {
+        let Self {
+            source,
+            archive,
+            password,
+            entries,
+            removed,
+            thread_count,
+            #[cfg(feature = "aes256")]
+            new_password,
+            ..
+        } = self;
+        let archive = &*archive;
+
+        #[cfg(feature = "aes256")]
+        let encrypting_password = new_password
+            .as_ref()
+            .filter(|password| !password.is_empty());
+        #[cfg(feature = "aes256")]
+        if let Some(password) = encrypting_password {
+            writer.set_header_encryption(Some(AesEncoderOptions::new(password.clone())));
+        }
+
+        // Every block gets its own handle to the source, so blocks can be decoded lazily
+        // when their entries are read.
+        let shared_source = Rc::new(RefCell::new(source));
+        let mut sources: Vec<_> = (0..archive.blocks.len())
+            .map(|_| SharedBoundedReader::new(Rc::clone(&shared_source), (0, u64::MAX)))
+            .collect();
+        let mut sources: Vec<_> = sources.iter_mut().map(Some).collect();
+
+        let first_entry_index = writer.entry_count();
+        let mut kept_index = 0;
+        let mut solid_entries = Vec::new();
+        let mut solid_readers = Vec::new();
+        let mut empty_entries = Vec::new();
+        // The password is verified once, with the first encrypted block which is rekeyed.
+        #[cfg(feature = "aes256")]
+        let mut password_verified = false;
+        for file_index in 0..archive.files.len() {
+            let file = &archive.files[file_index];
+            if !removed.contains(file_index) {
+                if !file.has_stream {
+                    // Entries with data are written in the order of their blocks, so
+                    // entries without data are inserted at their position afterward.
+                    let entry = with_stream_info(&entries[file_index], file);
+                    empty_entries.push((first_entry_index + kept_index, entry));
+                }
+                kept_index += 1;
+            }
+            if !file.has_stream {
+                continue;
+            }
+            let Some(block_index) = archive.stream_map.file_block_index[file_index] else {
+                continue;
+            };
+            let Some(source) = sources[block_index].take() else {
+                // All entries of the block were already written with its first entry.
+                continue;
+            };
+
+            let stream_files: Vec<usize> = archive
+                .block_file_range(block_index)
+                .filter(|&index| archive.files[index].has_stream)
+                .collect();
+            let kept_files: Vec<usize> = stream_files
+                .iter()
+                .copied()
+                .filter(|&index| !removed.contains(index))
+                .collect();
+
+            if kept_files.is_empty() {
+                continue;
+            }
+            let kept_entries = kept_files
+                .iter()
+                .map(|&index| with_stream_info(&entries[index], &archive.files[index]));
+            if transcode.is_none() && kept_files.len() == stream_files.len() {
+                #[cfg(feature = "aes256")]
+                if let Some(new_password) = &*new_password {
+                    if !password_verified && is_encrypted(archive, block_index) {
+                        verify_password(
+                            &mut *source,
+                            archive,
+                            block_index,
+                            password,
+                            *thread_count,
+                        )?;
+                        password_verified = true;
+                    }
+                    writer.push_rekeyed_block(
+                        source,
+                        archive,
+                        block_index,
+                        kept_entries.collect(),
+                        password,
+                        new_password,
+                    )?;
+                    continue;
+                }
+                writer.push_existing_block(source, archive, block_index, kept_entries.collect())?;
+                continue;
+            }
+
+            #[cfg(feature = "aes256")]
+            if encrypting_password.is_some()
+                && is_encrypted(archive, block_index)
+                && !writer.encrypts_content()
+            {
+                return Err(Error::other(format!(
+                    "Encrypted block {block_index} would be re-encoded without encryption"
+                )));
+            }
+
+            let block_decoder = Rc::new(RefCell::new(LazyBlockDecoder {
+                state: LazyBlockState::Pending(source),
+                archive,
+                block_index,
+                password,
+                thread_count: *thread_count,
+            }));
+            let mut readers = Vec::with_capacity(kept_files.len());
+            let mut skip = 0;
+            for &index in stream_files.iter() {
+                let file = &archive.files[index];
+                if removed.contains(index) {
+                    skip += file.size;
+                    continue;
+                }
+                let mut reader: Box<dyn Read> = Box::new(BlockEntryReader {
+                    block_decoder: Rc::clone(&block_decoder),
+                    skip,
+                    remaining: file.size,
+                    is_last: Some(&index) == kept_files.last(),
+                });
+                if file.has_crc {
+                    reader = Box::new(Crc32VerifyingReader::new(
+                        reader,
+                        file.size as usize,
+                        file.crc,
+                    ));
+                }
+                skip = 0;
+                readers.push(reader);
+            }
+
+            match transcode {
+                None | Some(BlockGrouping::Preserve) => {
+                    let readers = readers.into_iter().map(SourceReader::new).collect();
+                    writer.push_archive_entries(kept_entries.collect(), readers)?;
+                }
+                Some(BlockGrouping::NonSolid) => {
+                    for (entry, reader) in kept_entries.zip(readers) {
+                        writer.push_archive_entry(entry, Some(reader))?;
+                    }
+                }
+                Some(BlockGrouping::Solid) => {
+                    solid_entries.extend(kept_entries);
+                    solid_readers.extend(readers.into_iter().map(SourceReader::new));
+                }
+            }
+        }
+
+        if !solid_entries.is_empty() {
+            writer.push_archive_entries(solid_entries, solid_readers)?;
+        }
+        for (index, entry) in empty_entries {
+            writer.insert_empty_entry(index, entry);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Write + Seek + SetLen> ArchiveEditor<R> {
+    /// Writes the changed metadata of the entries directly into the source archive.
+    ///
+    /// The new header replaces the old header behind the packed streams, which are left
+    /// untouched, and the source is truncated behind it. A copy of the new header is written
+    /// and referenced first, so the archive stays readable if the update is interrupted.
+    /// The header is compressed and optionally encrypted, see
+    /// [`ArchiveEditor::set_header_encryption`].
+    ///
+    /// Entries without data (like directories) can be removed this way, but removing entries
+    /// with data requires writing a new archive with [`ArchiveEditor::write_to`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// use sevenz_rust2::*;
+    ///
+    /// let file = OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .open("path/to/archive.7z")
+    ///     .unwrap();
+    /// let mut editor = ArchiveEditor::new(file, Password::empty()).unwrap();
+    /// for entry in editor.entries_mut() {
+    ///     entry.name = entry.name.replace("old_dir/", "new_dir/");
+    /// }
+    /// editor.update_in_place().unwrap();
+    /// ```
+    pub fn update_in_place(self) -> Result<R, Error> {
+        let Self {
+            source,
+            mut archive,
+            entries,
+            removed,
+            #[cfg(feature = "aes256")]
+            header_encryption,
+            #[cfg(feature = "aes256")]
+            new_password,
+            ..
+        } = self;
+
+        #[cfg(feature = "aes256")]
+        if new_password.is_some() {
+            return Err(Error::other(
+                "Changing the password requires writing a new archive",
+            ));
+        }
+
+        let mut files = Vec::with_capacity(entries.len());
+        for (index, (entry, file)) in entries.iter().zip(archive.files.iter()).enumerate() {
+            if !removed.contains(index) {
+                files.push(with_stream_info(entry, file));
+            } else if file.has_stream {
+                return Err(Error::other(
+                    "Entries with data can only be removed by writing a new archive",
+                ));
+            }
+        }
+        archive.files = files;
+
+        #[cfg_attr(not(feature = "aes256"), allow(unused_mut))]
+        let mut writer = ArchiveAppender::from_archive(source, &archive)?;
+        #[cfg(feature = "aes256")]
+        writer.set_header_encryption(header_encryption);
+        Ok(writer.finish()?)
+    }
+}
+
+/// Returns the metadata of `entry` combined with the data related fields of `original`.
+fn with_stream_info(entry: &ArchiveEntry, original: &ArchiveEntry) -> ArchiveEntry {
+    ArchiveEntry {
+        has_stream: original.has_stream,
+        size: original.size,
+        has_crc: original.has_crc,
+        crc: original.crc,
+        compressed_crc: original.compressed_crc,
+        compressed_size: original.compressed_size,
+        ..entry.clone()
+    }
+}
+
+/// Controls how the entries are grouped into blocks by [`ArchiveEditor::transcode_to`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlockGrouping {
+    /// Keeps the entries of each block together in one block.
+    #[default]
+    Preserve,
+    /// Writes every entry into its own block.
+    NonSolid,
+    /// Writes all entries into a single solid block.
+    Solid,
+}
+
+/// Decodes a block on the first read, so only the blocks which are currently read
+/// are held in memory.
+struct LazyBlockDecoder<'h, 's, R: Read + Seek> {
+    state: LazyBlockState<'h, 's, R>,
+    archive: &'h Archive,
+    block_index: usize,
+    password: &'h Password,
+    thread_count: u32,
+}
+
+enum LazyBlockState<'h, 's, R> {
+    Pending(&'h mut SharedBoundedReader<&'s mut R>),
+    Decoding(Box<dyn Read + 'h>),
+    Finished,
+}
+
+impl<'h, 's: 'h, R: Read + Seek + 's> LazyBlockDecoder<'h, 's, R> {
+    fn reader(&mut self) -> io::Result<&mut Box<dyn Read + 'h>> {
+        if let LazyBlockState::Pending(_) = self.state {
+            let LazyBlockState::Pending(source) =
+                std::mem::replace(&mut self.state, LazyBlockState::Finished)
+            else {
+                unreachable!()
+            };
+            let (reader, _size) = ArchiveReader::<R>::build_decode_stack(
+                source,
+                self.archive,
+                self.block_index,
+                self.password,
+                self.thread_count,
+            )
+            .map_err(io::Error::other)?;
+            self.state = LazyBlockState::Decoding(reader);
+        }
+        match &mut self.state {
+            LazyBlockState::Decoding(reader) => Ok(reader),
+            _ => Err(io::Error::other("Block was already decoded")),
+        }
+    }
+}
+
+/// Reads the data of a single entry from the decoded data of a block, which is shared
+/// by all entries of the block. The data of removed entries in front of the entry is skipped.
+struct BlockEntryReader<'h, 's, R: Read + Seek> {
+    block_decoder: Rc<RefCell<LazyBlockDecoder<'h, 's, R>>>,
+    skip: u64,
+    remaining: u64,
+    /// Whether this is the last entry which is read from the block.
+    is_last: bool,
+}
+
+impl<'h, 's: 'h, R: Read + Seek + 's> Read for BlockEntryReader<'h, 's, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let mut block_decoder = self.block_decoder.borrow_mut();
+        let block_reader = block_decoder.reader()?;
+        if self.skip > 0 {
+            let skipped = io::copy(&mut block_reader.take(self.skip), &mut io::sink())?;
+            if skipped != self.skip {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.skip = 0;
+        }
+        let bound = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        let size = block_reader.read(&mut buf[..bound])?;
+        if size == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= size as u64;
+        if self.remaining == 0 && self.is_last {
+            // Free the decoder as soon as possible.
+            block_decoder.state = LazyBlockState::Finished;
+        }
+        Ok(size)
+    }
+}
+
+/// Whether the block at `block_index` is encrypted.
+#[cfg(feature = "aes256")]
+fn is_encrypted(archive: &Archive, block_index: usize) -> bool {
+    archive.blocks[block_index]
+        .coders
+        .iter()
+        .any(|coder| coder.encoder_method_id() == EncoderMethod::ID_AES256_SHA256)
+}
+
+/// Verifies the `password` of the block at `block_index` by decoding the block up to the end
+/// of its first entry with a CRC, which has to match the CRC of the decoded data.
+#[cfg(feature = "aes256")]
+fn verify_password<S: Read + Seek>(
+    source: S,
+    archive: &Archive,
+    block_index: usize,
+    password: &Password,
+    thread_count: u32,
+) -> Result<(), Error> {
+    let (mut decoder, _size) = ArchiveReader::<S>::build_unverified_decode_stack(
+        source,
+        archive,
+        block_index,
+        password,
+        thread_count,
+    )?;
+    let stream_files = archive
+        .block_file_range(block_index)
+        .map(|index| &archive.files[index])
+        .filter(|file| file.has_stream);
+    for file in stream_files {
+        let mut entry = (&mut decoder).take(file.size);
+        let size = if file.has_crc {
+            let mut entry = Crc32VerifyingReader::new(entry, file.size as usize, file.crc);
+            std::io::copy(&mut entry, &mut std::io::sink())
+        } else {
+            std::io::copy(&mut entry, &mut std::io::sink())
+        }
+        .map_err(|e| Error::bad_password(e, true))?;
+        if size != file.size {
+            return Err(Error::bad_password(
+                std::io::ErrorKind::UnexpectedEof.into(),
+                true,
+            ));
+        }
+        if file.has_crc {
+            break;
+        }
+    }
+    Ok(())
+}
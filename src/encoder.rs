@@ -26,11 +26,16 @@ use crate::encryption::Aes256Sha256Encoder;
 use crate::{
     Error,
     archive::{EncoderConfiguration, EncoderMethod},
+    codec::registry::CodecRegistry,
     encoder_options::{DeltaOptions, EncoderOptions, Lzma2Options, LzmaOptions},
     writer::CountingWriter,
 };
 
-pub(crate) enum Encoder<W: Write> {
+/// Maximum size of the properties of a custom method, which is the size of the property
+/// buffers of the writer.
+const MAX_CUSTOM_PROPERTIES_SIZE: usize = 256;
+
+pub(crate) enum Encoder<'w, W: Write> {
     Copy(CountingWriter<W>),
     Bcj(Option<BcjWriter<CountingWriter<W>>>),
     Delta(DeltaWriter<CountingWriter<W>>),
@@ -51,9 +56,10 @@ pub(crate) enum Encoder<W: Write> {
     Zstd(Option<zstd::Encoder<'static, CountingWriter<W>>>),
     #[cfg(feature = "aes256")]
     Aes(Aes256Sha256Encoder<CountingWriter<W>>),
+    Custom(Box<dyn Write + 'w>),
 }
 
-impl<W: Write> Write for Encoder<W> {
+impl<W: Write> Write for Encoder<'_, W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         // Some encoder need to finish the encoding process. Because of lifetime limitations on
         // dynamic dispatch, we need to implement an implicit contract, where empty writes with
@@ -155,6 +161,7 @@ impl<W: Write> Write for Encoder<W> {
             },
             #[cfg(feature = "aes256")]
             Encoder::Aes(w) => w.write(buf),
+            Encoder::Custom(w) => w.write(buf),
         }
     }
 
@@ -180,14 +187,16 @@ impl<W: Write> Write for Encoder<W> {
             Encoder::Zstd(w) => w.as_mut().unwrap().flush(),
             #[cfg(feature = "aes256")]
             Encoder::Aes(w) => w.flush(),
+            Encoder::Custom(w) => w.flush(),
         }
     }
 }
 
-pub(crate) fn add_encoder<W: Write>(
+pub(crate) fn add_encoder<'w, W: Write + 'w>(
     input: CountingWriter<W>,
     method_config: &EncoderConfiguration,
-) -> Result<Encoder<W>, Error> {
+    codecs: &CodecRegistry,
+) -> Result<Encoder<'w, W>, Error> {
     let method = method_config.method;
 
     match method.id() {
@@ -320,9 +329,24 @@ pub(crate) fn add_encoder<W: Write>(
             };
             Ok(Encoder::Aes(Aes256Sha256Encoder::new(input, options)?))
         }
-        _ => Err(Error::UnsupportedCompressionMethod(
-            method.name().to_string(),
-        )),
+        id => {
+            let Some(codec) = codecs.get(id) else {
+                return Err(Error::UnsupportedCompressionMethod(
+                    method.name().to_string(),
+                ));
+            };
+            let properties = match method_config.options.as_ref() {
+                Some(EncoderOptions::Custom(properties)) => properties.as_slice(),
+                _ => &[],
+            };
+            if properties.len() > MAX_CUSTOM_PROPERTIES_SIZE {
+                return Err(Error::other(format!(
+                    "Properties of method {} are larger than {MAX_CUSTOM_PROPERTIES_SIZE} bytes",
+                    method.name()
+                )));
+            }
+            Ok(Encoder::Custom(codec.encoder(Box::new(input), properties)?))
+        }
     }
 }
 
@@ -420,6 +444,13 @@ pub(crate) fn get_options_as_properties<'a>(
             options.write_properties(out);
             &out[..34]
         }
-        _ => &[],
+        _ => match options {
+            Some(EncoderOptions::Custom(properties)) => {
+                let len = properties.len().min(out.len());
+                out[..len].copy_from_slice(&properties[..len]);
+                &out[..len]
+            }
+            _ => &[],
+        },
     }
 }
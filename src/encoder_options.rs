@@ -373,6 +373,14 @@ impl AesEncoderOptions {
         }
     }
 
+    /// Returns a copy of the options with a new random IV, to encrypt another stream with
+    /// the same key.
+    pub(crate) fn with_new_iv(&self) -> Self {
+        let mut options = self.clone();
+        getrandom::fill(&mut options.iv).expect("Can't generate IV");
+        options
+    }
+
     pub(crate) fn properties(&self) -> [u8; 34] {
         let mut props = [0u8; 34];
         self.write_properties(&mut props);
@@ -422,6 +430,9 @@ pub enum EncoderOptions {
     #[cfg(feature = "aes256")]
     /// AES256 encryption options.
     Aes(AesEncoderOptions),
+    /// Opaque properties of a custom method, which are stored in its coder and passed to the
+    /// registered [`Codec`](crate::Codec) of the method. At most 256 bytes are supported.
+    Custom(Vec<u8>),
 }
 
 #[cfg(feature = "aes256")]
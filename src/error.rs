@@ -51,6 +51,62 @@ pub enum Error {
     MaybeBadPassword(std::io::Error),
     /// File not found.
     FileNotFound,
+    /// The header is larger than allowed by [`crate::ReaderOptions`].
+    HeaderTooLarge {
+        /// Size of the header in bytes.
+        size: u64,
+        /// Maximum allowed size in bytes.
+        max: u64,
+    },
+    /// The archive has more entries than allowed by [`crate::ReaderOptions`].
+    TooManyEntries {
+        /// Number of entries in the archive.
+        count: usize,
+        /// Maximum allowed number of entries.
+        max: usize,
+    },
+    /// The archive has more blocks or packed streams than allowed by [`crate::ReaderOptions`].
+    TooManyBlocks {
+        /// Number of blocks or packed streams in the archive.
+        count: usize,
+        /// Maximum allowed number.
+        max: usize,
+    },
+    /// A block has more coders than allowed by [`crate::ReaderOptions`].
+    TooManyCoders {
+        /// Number of coders in the block.
+        count: usize,
+        /// Maximum allowed number of coders.
+        max: usize,
+    },
+    /// The total decoded size of the archive is larger than allowed by [`crate::ReaderOptions`].
+    UnpackSizeTooLarge {
+        /// Total decoded size of all blocks in bytes.
+        size: u64,
+        /// Maximum allowed size in bytes.
+        max: u64,
+    },
+    /// An entry is larger than allowed by [`crate::ReaderOptions`].
+    EntryTooLarge {
+        /// Name of the entry.
+        name: String,
+        /// Size of the entry in bytes.
+        size: u64,
+        /// Maximum allowed size in bytes.
+        max: u64,
+    },
+    /// The compression ratio of a block is higher than allowed by [`crate::ReaderOptions`].
+    CompressionRatioTooHigh {
+        /// Index of the block.
+        block_index: usize,
+        /// Decoded size of the block divided by the size of its packed streams.
+        ratio: u64,
+        /// Maximum allowed ratio.
+        max: u64,
+    },
+    /// Errors of blocks which were decoded in parallel, with the index of the failed block,
+    /// or `None` for an error of an entry without data.
+    Blocks(Vec<(Option<usize>, Error)>),
 }
 
 impl From<std::io::Error> for Error {
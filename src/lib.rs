@@ -13,8 +13,11 @@
 //! | BROTLI (*)     | ✓             | ✓           |
 //! | BZIP2          | ✓             | ✓           |
 //! | DEFLATE (*)    | ✓             | ✓           |
+//! | DEFLATE64 (*)  | ✓             |             |
+//! | LIZARD (*)     | ✓             |             |
 //! | PPMD           | ✓             | ✓           |
 //! | LZ4 (*)        | ✓             | ✓           |
+//! | LZS (*)        | ✓             |             |
 //! | ZSTD (*)       | ✓             | ✓           |
 //!
 //! (*) Require optional cargo feature.
@@ -29,7 +32,7 @@
 //! | BCJ PPC       | ✓             | ✓           |
 //! | BCJ SPARC     | ✓             | ✓           |
 //! | BCJ IA64      | ✓             | ✓           |
-//! | BCJ2          | ✓             |             |
+//! | BCJ2          | ✓             | ✓           |
 //! | DELTA         | ✓             | ✓           |
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![warn(missing_docs)]
@@ -44,7 +47,10 @@ mod encoder;
 pub mod encoder_options;
 mod encryption;
 mod error;
+mod read_at;
 mod reader;
+#[cfg(all(feature = "spool", not(target_arch = "wasm32")))]
+mod spool;
 
 #[cfg(feature = "compress")]
 mod writer;
@@ -54,10 +60,13 @@ pub(crate) mod bitset;
 pub(crate) mod block;
 mod codec;
 pub(crate) mod decoder;
+#[cfg(feature = "compress")]
+mod editor;
 
 mod time;
 #[cfg(feature = "util")]
 mod util;
+mod volume;
 
 use std::{
     io::{Read, Write},
@@ -66,9 +75,18 @@ use std::{
 
 pub use archive::*;
 pub use block::*;
+pub use codec::registry::Codec;
+#[cfg(feature = "compress")]
+pub use editor::{ArchiveEditor, BlockGrouping};
 pub use encryption::Password;
 pub use error::Error;
-pub use reader::{ArchiveReader, BlockDecoder};
+pub use read_at::{ReadAt, ReadAtReader};
+pub use reader::{
+    ArchiveReader, BlockDecoder, BlockTestResult, CrcStatus, Entries, EntryReader, EntryStream,
+    EntryTestResult, PackStreamTestResult, ReaderOptions, TestReport,
+};
+#[cfg(all(feature = "spool", not(target_arch = "wasm32")))]
+pub use spool::{DEFAULT_SPOOL_MEMORY_LIMIT, SpooledReader};
 pub use time::NtTime;
 #[cfg(all(feature = "compress", feature = "util", not(target_arch = "wasm32")))]
 pub use util::compress::*;
@@ -76,6 +94,9 @@ pub use util::compress::*;
 pub use util::decompress::*;
 #[cfg(all(feature = "util", target_arch = "wasm32"))]
 pub use util::wasm::*;
+pub use volume::MultiVolumeReader;
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+pub use volume::MultiVolumeWriter;
 #[cfg(feature = "compress")]
 pub use writer::*;
 
@@ -0,0 +1,148 @@
+#[cfg(any(unix, windows))]
+use std::fs::File;
+use std::{
+    io,
+    io::{Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+/// A source which can be read at any position through a shared reference, like `pread`.
+///
+/// Other than [`Read`] + [`Seek`], reading doesn't change a shared position, so one source
+/// can be read by multiple threads at the same time. Use [`ReadAtReader`] to read the source
+/// everywhere a `Read + Seek` source is accepted, for example with [`crate::ArchiveReader`]
+/// and [`crate::BlockDecoder`].
+pub trait ReadAt {
+    /// Reads bytes starting at `offset` into `buf` and returns the number of bytes read.
+    ///
+    /// Returns `Ok(0)` if `offset` is at or after the end of the source.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// Returns the size of the source in bytes.
+    fn size(&self) -> io::Result<u64>;
+}
+
+#[cfg(unix)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+#[cfg(windows)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        // Moves the position of the file, which isn't used by `ReadAt`.
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(self.len());
+        let data = &self[start..];
+        let size = buf.len().min(data.len());
+        buf[..size].copy_from_slice(&data[..size]);
+        Ok(size)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_slice().read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+/// A `Read + Seek` view of a [`ReadAt`] source with its own position.
+///
+/// Multiple readers can share one source, for example with `ReadAtReader::new(&file)`
+/// or `ReadAtReader::new(Arc::clone(&file))`.
+#[derive(Debug, Clone)]
+pub struct ReadAtReader<T: ReadAt> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T: ReadAt> ReadAtReader<T> {
+    /// Creates a reader which starts at the beginning of `inner`.
+    pub fn new(inner: T) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    /// Returns a reference to the underlying source.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the underlying source.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ReadAt> Read for ReadAtReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read_at(buf, self.pos)?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<T: ReadAt> Seek for ReadAtReader<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(pos) => self.inner.size()?.checked_add_signed(pos),
+            SeekFrom::Current(pos) => self.pos.checked_add_signed(pos),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
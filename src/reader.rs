@@ -1,3 +1,12 @@
+mod cache;
+mod coder_graph;
+mod entries;
+mod entry_reader;
+mod integrity;
+mod options;
+#[cfg(not(target_arch = "wasm32"))]
+mod parallel;
+
 use std::{
     cell::RefCell,
     collections::HashMap,
@@ -6,16 +15,34 @@ use std::{
     io::{Read, Seek, SeekFrom},
     num::NonZeroUsize,
     rc::Rc,
+    sync::Arc,
 };
 
 use crc32fast::Hasher;
-use lzma_rust2::filter::bcj2::Bcj2Reader;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::MultiVolumeReader;
+#[cfg(all(feature = "spool", not(target_arch = "wasm32")))]
+use crate::SpooledReader;
 use crate::{
-    ByteReader, Password, archive::*, bitset::BitSet, block::*, decoder::add_decoder, error::Error,
+    ByteReader, Password, archive::*, bitset::BitSet, block::*, codec::registry::Codec,
+    error::Error,
+};
+
+pub use self::entries::{Entries, EntryStream};
+pub use self::entry_reader::EntryReader;
+pub use self::integrity::{
+    BlockTestResult, CrcStatus, EntryTestResult, PackStreamTestResult, TestReport,
+};
+pub use self::options::ReaderOptions;
+use self::{
+    cache::{BlockCache, CachedDecoder, decoder_memory},
+    coder_graph::CoderGraph,
 };
 
-const MAX_MEM_LIMIT_KB: usize = usize::MAX / 1024;
+/// Maximum number of coders in a block, the same limit 7-Zip uses. The coder graph of a
+/// block is decoded recursively, so the limit also bounds the depth of the recursion.
+const MAX_BLOCK_CODERS: usize = 64;
 
 pub struct BoundedReader<R: Read> {
     inner: R,
@@ -46,13 +73,13 @@ impl<R: Read> Read for BoundedReader<R> {
 /// A special reader that shares it's inner reader with other instances and
 /// needs to re-seek every read operation.
 #[derive(Debug)]
-pub(crate) struct SharedBoundedReader<'a, R> {
-    inner: Rc<RefCell<&'a mut R>>,
+pub(crate) struct SharedBoundedReader<R> {
+    inner: Rc<RefCell<R>>,
     cur: u64,
     bounds: (u64, u64),
 }
 
-impl<'a, R> Clone for SharedBoundedReader<'a, R> {
+impl<R> Clone for SharedBoundedReader<R> {
     fn clone(&self) -> Self {
         Self {
             inner: Rc::clone(&self.inner),
@@ -62,7 +89,7 @@ impl<'a, R> Clone for SharedBoundedReader<'a, R> {
     }
 }
 
-impl<'a, R: Read + Seek> Seek for SharedBoundedReader<'a, R> {
+impl<R: Read + Seek> Seek for SharedBoundedReader<R> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         let new_pos = match pos {
             SeekFrom::Start(pos) => self.bounds.0 as i64 + pos as i64,
@@ -77,7 +104,7 @@ impl<'a, R: Read + Seek> Seek for SharedBoundedReader<'a, R> {
     }
 }
 
-impl<'a, R: Read + Seek> Read for SharedBoundedReader<'a, R> {
+impl<R: Read + Seek> Read for SharedBoundedReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.cur >= self.bounds.1 {
             return Ok(0);
@@ -94,8 +121,8 @@ impl<'a, R: Read + Seek> Read for SharedBoundedReader<'a, R> {
     }
 }
 
-impl<'a, R: Read + Seek> SharedBoundedReader<'a, R> {
-    fn new(inner: Rc<RefCell<&'a mut R>>, bounds: (u64, u64)) -> Self {
+impl<R: Read + Seek> SharedBoundedReader<R> {
+    pub(crate) fn new(inner: Rc<RefCell<R>>, bounds: (u64, u64)) -> Self {
         Self {
             inner,
             cur: bounds.0,
@@ -104,7 +131,7 @@ impl<'a, R: Read + Seek> SharedBoundedReader<'a, R> {
     }
 }
 
-struct Crc32VerifyingReader<R> {
+pub(crate) struct Crc32VerifyingReader<R> {
     inner: R,
     crc_digest: Hasher,
     expected_value: u64,
@@ -112,7 +139,7 @@ struct Crc32VerifyingReader<R> {
 }
 
 impl<R: Read> Crc32VerifyingReader<R> {
-    fn new(inner: R, remaining: usize, expected_value: u64) -> Self {
+    pub(crate) fn new(inner: R, remaining: usize, expected_value: u64) -> Self {
         Self {
             inner,
             crc_digest: Hasher::new(),
@@ -189,8 +216,126 @@ impl Archive {
     /// }
     /// ```
     pub fn read<R: Read + Seek>(reader: &mut R, password: &Password) -> Result<Archive, Error> {
+        Self::read_with_options(reader, password, ReaderOptions::default())
+    }
+
+    /// Read 7z file archive info use the specified `reader`, enforcing the limits of
+    /// `options`.
+    ///
+    /// Use this to read untrusted archives, see [`ReaderOptions`] for the available limits.
+    /// The limits are kept with the archive and also apply when decoding it.
+    pub fn read_with_options<R: Read + Seek>(
+        reader: &mut R,
+        password: &Password,
+        options: ReaderOptions,
+    ) -> Result<Archive, Error> {
+        match options.signature_search_limit {
+            Some(search_limit) => {
+                let reader_len = reader.seek(SeekFrom::End(0))?;
+                match Self::find_signature_header(reader, reader_len, search_limit)? {
+                    Some(base_offset) => Self::read_at(reader, base_offset, password, &options),
+                    None => {
+                        reader.seek(SeekFrom::Start(0))?;
+                        let mut signature = [0; 6];
+                        reader.read_exact(&mut signature)?;
+                        Err(Error::BadSignature(signature))
+                    }
+                }
+            }
+            None => Self::read_at(reader, 0, password, &options),
+        }
+    }
+
+    /// Read 7z file archive info use the specified `reader`, searching for the signature
+    /// header within the first `search_limit` bytes.
+    ///
+    /// This allows opening self-extracting (SFX) archives and archives that are embedded
+    /// after other data. Every signature found is only accepted if the CRC of its start
+    /// header is valid. All offsets of the archive are relative to the discovered position,
+    /// which is available via [`Archive::base_offset`].
+    ///
+    /// # Parameters
+    /// - `reader`       - the reader of the 7z file archive
+    /// - `password`     - archive password encoded in utf16 little endian
+    /// - `search_limit` - the maximum offset at which the signature header may start
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    ///
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut reader = File::open("installer.exe").unwrap();
+    ///
+    /// let archive = Archive::read_with_signature_scan(
+    ///     &mut reader,
+    ///     &Password::empty(),
+    ///     DEFAULT_SIGNATURE_SEARCH_LIMIT,
+    /// )
+    /// .unwrap();
+    /// println!("7z archive starts at offset {}", archive.base_offset());
+    ///
+    /// let mut reader = ArchiveReader::from_archive(archive, reader, Password::empty());
+    /// ```
+    pub fn read_with_signature_scan<R: Read + Seek>(
+        reader: &mut R,
+        password: &Password,
+        search_limit: u64,
+    ) -> Result<Archive, Error> {
+        Self::read_with_options(
+            reader,
+            password,
+            ReaderOptions::default().with_signature_search_limit(search_limit),
+        )
+    }
+
+    /// Returns the offset of the signature header from the beginning of the source.
+    ///
+    /// This is `0` for regular archives and the size of the leading data for
+    /// self-extracting archives.
+    #[inline]
+    pub fn base_offset(&self) -> u64 {
+        self.base_offset
+    }
+
+    /// Returns the comment of the archive, if it has one.
+    #[inline]
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the absolute position of the first packed stream in the source.
+    #[inline]
+    pub(crate) fn packed_streams_start(&self) -> u64 {
+        self.base_offset + SIGNATURE_HEADER_SIZE + self.pack_pos
+    }
+
+    /// Returns the range of file indices that belong to the given block.
+    ///
+    /// Entries without data can be placed between the entries of a block, so the range
+    /// can contain more entries than the block has sub streams.
+    pub(crate) fn block_file_range(&self, block_index: usize) -> std::ops::Range<usize> {
+        let start = self.stream_map.block_first_file_index[block_index];
+        let mut remaining = self.blocks[block_index].num_unpack_sub_streams;
+        let mut end = start;
+        while remaining > 0 && end < self.files.len() {
+            if self.files[end].has_stream {
+                remaining -= 1;
+            }
+            end += 1;
+        }
+        start..end
+    }
+
+    fn read_at<R: Read + Seek>(
+        reader: &mut R,
+        base_offset: u64,
+        password: &Password,
+        options: &ReaderOptions,
+    ) -> Result<Archive, Error> {
         let reader_len = reader.seek(SeekFrom::End(0))?;
-        reader.seek(SeekFrom::Start(0))?;
+        reader.seek(SeekFrom::Start(base_offset))?;
 
         let mut signature = [0; 6];
         reader.read_exact(&mut signature)?;
@@ -221,12 +366,75 @@ impl Archive {
         };
         if header_valid {
             let start_header = Self::read_start_header(reader, start_header_crc)?;
-            Self::init_archive(reader, start_header, password, true, 1)
+            Self::init_archive(
+                reader,
+                base_offset,
+                start_header,
+                password,
+                options,
+                true,
+                1,
+            )
         } else {
-            Self::try_to_locale_end_header(reader, reader_len, password, 1)
+            Self::try_to_locale_end_header(reader, base_offset, reader_len, password, options, 1)
         }
     }
 
+    /// Searches for a signature header with a valid start header CRC, which starts
+    /// at most `search_limit` bytes into the source.
+    fn find_signature_header<R: Read + Seek>(
+        reader: &mut R,
+        reader_len: u64,
+        search_limit: u64,
+    ) -> Result<Option<u64>, Error> {
+        const CHUNK_SIZE: usize = 64 * 1024;
+        let header_size = SIGNATURE_HEADER_SIZE as usize;
+
+        let end = reader_len.min(search_limit.saturating_add(SIGNATURE_HEADER_SIZE));
+        let mut buf = vec![0; CHUNK_SIZE + header_size];
+        let mut chunk_start = 0;
+        while chunk_start + SIGNATURE_HEADER_SIZE <= end {
+            let len = (end - chunk_start).min(buf.len() as u64) as usize;
+            reader.seek(SeekFrom::Start(chunk_start))?;
+            reader.read_exact(&mut buf[..len])?;
+
+            for i in 0..=len - header_size {
+                let base_offset = chunk_start + i as u64;
+                if buf[i..].starts_with(SEVEN_Z_SIGNATURE)
+                    && Self::is_valid_signature_header(
+                        &buf[i..i + header_size],
+                        base_offset,
+                        reader_len,
+                    )
+                {
+                    return Ok(Some(base_offset));
+                }
+            }
+            // Candidates overlapping the chunk end are checked again with the next chunk.
+            chunk_start += (len - header_size + 1) as u64;
+        }
+        Ok(None)
+    }
+
+    fn is_valid_signature_header(header: &[u8], base_offset: u64, reader_len: u64) -> bool {
+        let version_major = header[6];
+        let start_header_crc = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let start_header = &header[12..32];
+        if version_major != 0
+            || start_header_crc == 0
+            || crc32fast::hash(start_header) != start_header_crc
+        {
+            return false;
+        }
+        let next_header_offset = u64::from_le_bytes(start_header[0..8].try_into().unwrap());
+        let next_header_size = u64::from_le_bytes(start_header[8..16].try_into().unwrap());
+        base_offset
+            .checked_add(SIGNATURE_HEADER_SIZE)
+            .and_then(|pos| pos.checked_add(next_header_offset))
+            .and_then(|pos| pos.checked_add(next_header_size))
+            .is_some_and(|end| end <= reader_len)
+    }
+
     fn read_start_header<R: Read>(
         reader: &mut R,
         start_header_crc: u32,
@@ -249,22 +457,31 @@ impl Archive {
         })
     }
 
-    fn read_header<R: Read + Seek>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_header<R: Read + Seek, RI: Read + Seek>(
+        header: &mut R,
+        reader: &mut RI,
+        archive: &mut Archive,
+        password: &Password,
+        thread_count: u32,
+    ) -> Result<(), Error> {
         let mut nid = header.read_u8()?;
         if nid == K_ARCHIVE_PROPERTIES {
-            Self::read_archive_properties(header)?;
+            archive.archive_properties = Self::read_archive_properties(header)?;
             nid = header.read_u8()?;
         }
 
+        let mut additional_streams = Vec::new();
         if nid == K_ADDITIONAL_STREAMS_INFO {
-            return Err(Error::other("Additional streams unsupported"));
+            additional_streams =
+                Self::read_additional_streams(header, reader, archive, password, thread_count)?;
+            nid = header.read_u8()?;
         }
         if nid == K_MAIN_STREAMS_INFO {
             Self::read_streams_info(header, archive)?;
             nid = header.read_u8()?;
         }
         if nid == K_FILES_INFO {
-            Self::read_files_info(header, archive)?;
+            Self::read_files_info(header, archive, &additional_streams)?;
             nid = header.read_u8()?;
         }
         if nid != K_END {
@@ -274,20 +491,75 @@ impl Archive {
         Ok(())
     }
 
-    fn read_archive_properties<R: Read + Seek>(header: &mut R) -> Result<(), Error> {
-        let mut nid = header.read_u8()?;
-        while nid != K_END {
-            let property_size = read_variable_usize(header, "propertySize")?;
-            header.seek(SeekFrom::Current(property_size as i64))?;
-            nid = header.read_u8()?;
+    fn read_archive_properties<R: Read>(header: &mut R) -> Result<Vec<ArchiveProperty>, Error> {
+        let mut properties = Vec::new();
+        let mut property_type = header.read_u8()?;
+        while property_type != K_END {
+            let property_size = read_variable_u64(header)?;
+            let mut data = Vec::new();
+            header.take(property_size).read_to_end(&mut data)?;
+            if data.len() as u64 != property_size {
+                return Err(Error::other("Archive property is truncated"));
+            }
+            properties.push(ArchiveProperty {
+                property_type,
+                data,
+            });
+            property_type = header.read_u8()?;
         }
-        Ok(())
+        Ok(properties)
+    }
+
+    /// Reads the additional streams info and decodes the additional streams, which hold
+    /// header data stored outside of the header, like file names or times.
+    fn read_additional_streams<R: Read, RI: Read + Seek>(
+        header: &mut R,
+        reader: &mut RI,
+        archive: &Archive,
+        password: &Password,
+        thread_count: u32,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let mut streams_archive = Archive {
+            base_offset: archive.base_offset,
+            options: archive.options,
+            ..Default::default()
+        };
+        Self::read_streams_info(header, &mut streams_archive)?;
+        Self::calculate_stream_map(&mut streams_archive)?;
+        let total_size = streams_archive.blocks.iter().fold(0u64, |total, block| {
+            total.saturating_add(block.get_unpack_size())
+        });
+        archive.options.check_header_size(total_size)?;
+
+        let mut streams = Vec::with_capacity(streams_archive.blocks.len());
+        for (block_index, block) in streams_archive.blocks.iter().enumerate() {
+            let unpack_size = block.get_unpack_size();
+            let (decoder, _) = ArchiveReader::<RI>::build_decode_stack(
+                &mut *reader,
+                &streams_archive,
+                block_index,
+                password,
+                thread_count,
+            )?;
+            let mut data = Vec::new();
+            decoder
+                .take(unpack_size)
+                .read_to_end(&mut data)
+                .map_err(|e| Error::bad_password(e, !password.is_empty()))?;
+            if data.len() as u64 != unpack_size {
+                return Err(Error::other("Additional stream is truncated"));
+            }
+            streams.push(data);
+        }
+        Ok(streams)
     }
 
     fn try_to_locale_end_header<R: Read + Seek>(
         reader: &mut R,
+        base_offset: u64,
         reader_len: u64,
         password: &Password,
+        options: &ReaderOptions,
         thread_count: u32,
     ) -> Result<Self, Error> {
         let search_limit = 1024 * 1024;
@@ -310,8 +582,15 @@ impl Archive {
                     next_header_size: reader_len - pos,
                     next_header_crc: 0,
                 };
-                let result =
-                    Self::init_archive(reader, start_header, password, false, thread_count)?;
+                let result = Self::init_archive(
+                    reader,
+                    base_offset,
+                    start_header,
+                    password,
+                    options,
+                    false,
+                    thread_count,
+                )?;
 
                 if !result.files.is_empty() {
                     return Ok(result);
@@ -325,11 +604,14 @@ impl Archive {
 
     fn init_archive<R: Read + Seek>(
         reader: &mut R,
+        base_offset: u64,
         start_header: StartHeader,
         password: &Password,
+        options: &ReaderOptions,
         verify_crc: bool,
         thread_count: u32,
     ) -> Result<Self, Error> {
+        options.check_header_size(start_header.next_header_size)?;
         if start_header.next_header_size > usize::MAX as u64 {
             return Err(Error::other(format!(
                 "Cannot handle next_header_size {}",
@@ -340,7 +622,7 @@ impl Archive {
         let next_header_size_int = start_header.next_header_size as usize;
 
         reader.seek(SeekFrom::Start(
-            SIGNATURE_HEADER_SIZE + start_header.next_header_offset,
+            base_offset + SIGNATURE_HEADER_SIZE + start_header.next_header_offset,
         ))?;
 
         let mut buf = vec![0; next_header_size_int];
@@ -349,9 +631,14 @@ impl Archive {
             return Err(Error::NextHeaderCrcMismatch);
         }
 
-        let mut archive = Archive::default();
+        let mut archive = Archive {
+            base_offset,
+            options: *options,
+            ..Default::default()
+        };
         let mut buf_reader = buf.as_slice();
         let mut nid = buf_reader.read_u8()?;
+        let mut header_encrypted = false;
         let mut header = if nid == K_ENCODED_HEADER {
             let (mut out_reader, buf_size) = Self::read_encoded_header(
                 &mut buf_reader,
@@ -360,12 +647,21 @@ impl Archive {
                 password,
                 thread_count,
             )?;
+            header_encrypted = archive.blocks[0]
+                .coders
+                .iter()
+                .any(|coder| coder.encoder_method_id() == EncoderMethod::ID_AES256_SHA256);
+            options.check_header_size(buf_size as u64)?;
             buf.clear();
             buf.resize(buf_size, 0);
             out_reader
                 .read_exact(&mut buf)
                 .map_err(|e| Error::bad_password(e, !password.is_empty()))?;
-            archive = Archive::default();
+            archive = Archive {
+                base_offset,
+                options: *options,
+                ..Default::default()
+            };
             buf_reader = buf.as_slice();
             nid = buf_reader.read_u8()?;
             buf_reader
@@ -374,11 +670,17 @@ impl Archive {
         };
         let mut header = std::io::Cursor::new(&mut header);
         if nid == K_HEADER {
-            Self::read_header(&mut header, &mut archive)?;
+            Self::read_header(&mut header, reader, &mut archive, password, thread_count)?;
         } else {
             return Err(Error::other("Broken or unsupported archive: no Header"));
         }
 
+        options.check_sizes(&archive)?;
+        archive.header_end = base_offset
+            + SIGNATURE_HEADER_SIZE
+            + start_header.next_header_offset
+            + start_header.next_header_size;
+        archive.header_encrypted = header_encrypted;
         archive.is_solid = archive
             .blocks
             .iter()
@@ -399,43 +701,14 @@ impl Archive {
             .blocks
             .first()
             .ok_or(Error::other("no blocks, can't read encoded header"))?;
-        let first_pack_stream_index = 0;
-        let block_offset = SIGNATURE_HEADER_SIZE + archive.pack_pos;
         if archive.pack_sizes.is_empty() {
             return Err(Error::other("no packed streams, can't read encoded header"));
         }
-
-        reader.seek(SeekFrom::Start(block_offset))?;
-        let coder_len = block.coders.len();
         let unpack_size = block.get_unpack_size() as usize;
-        let pack_size = archive.pack_sizes[first_pack_stream_index] as usize;
-        let input_reader = BoundedReader::new(reader, pack_size);
-        let mut decoder: Box<dyn Read> = Box::new(input_reader);
-        let mut decoder = if coder_len > 0 {
-            for (index, coder) in block.ordered_coder_iter() {
-                if coder.num_in_streams != 1 || coder.num_out_streams != 1 {
-                    return Err(Error::other(
-                        "Multi input/output stream coders are not yet supported",
-                    ));
-                }
-                let next = add_decoder(
-                    decoder,
-                    block.get_unpack_size_at_index(index) as usize,
-                    coder,
-                    password,
-                    MAX_MEM_LIMIT_KB,
-                    thread_count,
-                )?;
-                decoder = Box::new(next);
-            }
-            decoder
-        } else {
-            decoder
-        };
-        if block.has_crc {
-            decoder = Box::new(Crc32VerifyingReader::new(decoder, unpack_size, block.crc));
-        }
 
+        Self::calculate_stream_map(archive)?;
+        let (decoder, _) =
+            ArchiveReader::<RI>::build_decode_stack(reader, archive, 0, password, thread_count)?;
         Ok((decoder, unpack_size))
     }
 
@@ -463,8 +736,13 @@ impl Archive {
         Ok(())
     }
 
-    fn read_files_info<R: Read + Seek>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_files_info<R: Read + Seek>(
+        header: &mut R,
+        archive: &mut Archive,
+        additional_streams: &[Vec<u8>],
+    ) -> Result<(), Error> {
         let num_files = read_variable_usize(header, "num files")?;
+        archive.options.check_entries(num_files)?;
         let mut files: Vec<ArchiveEntry> = vec![Default::default(); num_files];
 
         let mut is_empty_stream: Option<BitSet> = None;
@@ -500,91 +778,83 @@ impl Archive {
                     };
                     is_anti = Some(read_bits(header, n)?);
                 }
-                K_NAME => {
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other("Not implemented:external != 0"));
+                K_NAME => match read_external(header, additional_streams)? {
+                    Some(mut data) => {
+                        let names_length = data.len();
+                        read_names(&mut data, names_length, &mut files)?;
                     }
-                    if (size - 1) & 1 != 0 {
-                        return Err(Error::other("file names length invalid"));
+                    None => {
+                        let size = assert_usize(size, "file names length")?;
+                        read_names(header, size.saturating_sub(1), &mut files)?;
                     }
-
-                    let size = assert_usize(size, "file names length")?;
-                    // let mut names = vec![0u8; size - 1];
-                    // header.read_exact(&mut names)?;
-                    let names_reader = NamesReader::new(header, size - 1);
-
-                    let mut next_file = 0;
-                    for s in names_reader {
-                        files[next_file].name = s?;
-                        next_file += 1;
-                    }
-
-                    if next_file != files.len() {
-                        return Err(Error::other("Error parsing file names"));
-                    }
-                }
+                },
                 K_C_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kCTime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut external = read_external(header, additional_streams)?;
+                    let mut data = property_data(header, &mut external);
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_creation_date = times_defined.contains(i);
                         if file.has_creation_date {
-                            file.creation_date = header.read_u64()?.into();
+                            file.creation_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_A_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kATime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut external = read_external(header, additional_streams)?;
+                    let mut data = property_data(header, &mut external);
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_access_date = times_defined.contains(i);
                         if file.has_access_date {
-                            file.access_date = header.read_u64()?.into();
+                            file.access_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_M_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kMTime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut external = read_external(header, additional_streams)?;
+                    let mut data = property_data(header, &mut external);
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_last_modified_date = times_defined.contains(i);
                         if file.has_last_modified_date {
-                            file.last_modified_date = header.read_u64()?.into();
+                            file.last_modified_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_WIN_ATTRIBUTES => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kWinAttributes Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut external = read_external(header, additional_streams)?;
+                    let mut data = property_data(header, &mut external);
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_windows_attributes = times_defined.contains(i);
                         if file.has_windows_attributes {
-                            file.windows_attributes = header.read_u32()?;
+                            file.windows_attributes = data.read_u32()?;
+                        }
+                    }
+                }
+                K_START_POS => {
+                    let positions_defined = read_all_or_bits(header, num_files)?;
+                    let mut external = read_external(header, additional_streams)?;
+                    let mut data = property_data(header, &mut external);
+                    for (i, file) in files.iter_mut().enumerate() {
+                        file.has_start_position = positions_defined.contains(i);
+                        if file.has_start_position {
+                            file.start_position = data.read_u64()?;
                         }
                     }
                 }
-                K_START_POS => return Err(Error::other("kStartPos is unsupported, please report")),
+                K_COMMENT => {
+                    archive.comment = Some(match read_external(header, additional_streams)? {
+                        Some(mut data) => {
+                            let comment_length = data.len();
+                            read_comment(&mut data, comment_length)?
+                        }
+                        None => {
+                            let size = assert_usize(size, "comment length")?;
+                            read_comment(header, size.saturating_sub(1))?
+                        }
+                    });
+                }
                 K_DUMMY => {
                     header.seek(SeekFrom::Current(size as i64))?;
                 }
@@ -706,6 +976,7 @@ impl Archive {
     fn read_pack_info<R: Read>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
         archive.pack_pos = read_variable_u64(header)?;
         let num_pack_streams = read_variable_usize(header, "num pack streams")?;
+        archive.options.check_blocks(num_pack_streams)?;
         let mut nid = header.read_u8()?;
         if nid == K_SIZE {
             archive.pack_sizes = vec![0u64; num_pack_streams];
@@ -738,6 +1009,7 @@ impl Archive {
             return Err(Error::other(format!("Expected kFolder, got {nid}")));
         }
         let num_blocks = read_variable_usize(header, "num blocks")?;
+        archive.options.check_blocks(num_blocks)?;
 
         archive.blocks.reserve_exact(num_blocks);
         let external = header.read_u8()?;
@@ -746,7 +1018,9 @@ impl Archive {
         }
 
         for _ in 0..num_blocks {
-            archive.blocks.push(Self::read_block(header)?);
+            archive
+                .blocks
+                .push(Self::read_block(header, &archive.options)?);
         }
 
         let nid = header.read_u8()?;
@@ -796,8 +1070,9 @@ impl Archive {
             for block in archive.blocks.iter_mut() {
                 let num_streams = read_variable_usize(header, "numStreams")?;
                 block.num_unpack_sub_streams = num_streams;
-                total_unpack_streams += num_streams;
+                total_unpack_streams = total_unpack_streams.saturating_add(num_streams);
             }
+            archive.options.check_entries(total_unpack_streams)?;
             nid = header.read_u8()?;
         }
 
@@ -884,10 +1159,17 @@ impl Archive {
         Ok(())
     }
 
-    fn read_block<R: Read>(header: &mut R) -> Result<Block, Error> {
+    fn read_block<R: Read>(header: &mut R, options: &ReaderOptions) -> Result<Block, Error> {
         let mut block = Block::default();
 
         let num_coders = read_variable_usize(header, "num coders")?;
+        if num_coders > MAX_BLOCK_CODERS {
+            return Err(Error::TooManyCoders {
+                count: num_coders,
+                max: MAX_BLOCK_CODERS,
+            });
+        }
+        options.check_coders(num_coders)?;
         let mut coders = Vec::with_capacity(num_coders);
         let mut total_in_streams = 0;
         let mut total_out_streams = 0;
@@ -1034,6 +1316,72 @@ fn read_bits<R: Read>(header: &mut R, size: usize) -> io::Result<BitSet> {
     Ok(bits)
 }
 
+/// Reads the `external` flag of a file property. Returns the additional stream holding the
+/// data of the property, or `None` if the data follows in the header.
+fn read_external<'s, R: Read>(
+    header: &mut R,
+    additional_streams: &'s [Vec<u8>],
+) -> Result<Option<&'s [u8]>, Error> {
+    if header.read_u8()? == 0 {
+        return Ok(None);
+    }
+    let data_index = read_variable_usize(header, "data index")?;
+    additional_streams
+        .get(data_index)
+        .map(|data| Some(data.as_slice()))
+        .ok_or_else(|| Error::other(format!("Additional stream {data_index} not found")))
+}
+
+/// Returns the reader of the data of a file property, see [`read_external`].
+fn property_data<'d, R: Read>(
+    header: &'d mut R,
+    external: &'d mut Option<&[u8]>,
+) -> &'d mut dyn Read {
+    match external {
+        Some(data) => data,
+        None => header,
+    }
+}
+
+fn read_names<R: Read>(
+    reader: &mut R,
+    names_length: usize,
+    files: &mut [ArchiveEntry],
+) -> Result<(), Error> {
+    if names_length & 1 != 0 {
+        return Err(Error::other("file names length invalid"));
+    }
+
+    let mut next_file = 0;
+    for name in NamesReader::new(reader, names_length) {
+        let file = files
+            .get_mut(next_file)
+            .ok_or_else(|| Error::other("Error parsing file names"))?;
+        file.name = name?;
+        next_file += 1;
+    }
+
+    if next_file != files.len() {
+        return Err(Error::other("Error parsing file names"));
+    }
+    Ok(())
+}
+
+/// Reads a comment stored as a UTF-16LE string, which is terminated by a null character.
+fn read_comment<R: Read>(reader: &mut R, comment_length: usize) -> Result<String, Error> {
+    let mut data = Vec::new();
+    reader.take(comment_length as u64).read_to_end(&mut data)?;
+    if data.len() != comment_length || comment_length & 1 != 0 {
+        return Err(Error::other("comment length invalid"));
+    }
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16(&units).map_err(|e| Error::other(format!("Invalid comment: {e}")))
+}
+
 struct NamesReader<'a, R: Read> {
     max_bytes: usize,
     read_bytes: usize,
@@ -1087,10 +1435,12 @@ struct IndexEntry {
 /// Reads a 7z archive file.
 pub struct ArchiveReader<R: Read + Seek> {
     source: R,
-    archive: Archive,
+    /// The archive, which is shared with the decoders kept by the cache.
+    archive: Arc<Archive>,
     password: Password,
     thread_count: u32,
     index: HashMap<String, IndexEntry>,
+    cache: BlockCache,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -1104,18 +1454,76 @@ impl ArchiveReader<File> {
     }
 }
 
+#[cfg(all(feature = "spool", not(target_arch = "wasm32")))]
+impl ArchiveReader<SpooledReader> {
+    /// Reads a 7z archive from a non-seekable `source` like a pipe or stdin and creates
+    /// a [`ArchiveReader`] to read it.
+    ///
+    /// The source is read completely before the archive is parsed, into memory up to
+    /// [`crate::DEFAULT_SPOOL_MEMORY_LIMIT`] bytes and into a temporary file otherwise.
+    /// Use [`SpooledReader::new`] with [`ArchiveReader::new`] to change the limit.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut reader = ArchiveReader::from_stream(std::io::stdin(), Password::empty()).unwrap();
+    /// reader
+    ///     .for_each_entries(|entry, _| {
+    ///         println!("{}", entry.name());
+    ///         Ok(true)
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn from_stream(source: impl Read, password: Password) -> Result<Self, Error> {
+        let source = SpooledReader::new(source, crate::DEFAULT_SPOOL_MEMORY_LIMIT)
+            .map_err(|e| Error::io_msg(e, "Spool archive"))?;
+        Self::new(source, password)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArchiveReader<MultiVolumeReader<File>> {
+    /// Opens a multi-volume 7z archive, starting with the volume at `first_path`
+    /// (for example `archive.7z.001`), and creates a [`ArchiveReader`] to read it.
+    ///
+    /// All following volumes (`archive.7z.002`, `archive.7z.003`, ...) are discovered
+    /// automatically. See [`MultiVolumeReader::open`] for details.
+    #[inline]
+    pub fn open_multi_volume(
+        first_path: impl AsRef<std::path::Path>,
+        password: Password,
+    ) -> Result<Self, Error> {
+        let source = MultiVolumeReader::open(first_path)?;
+        Self::new(source, password)
+    }
+}
+
 impl<R: Read + Seek> ArchiveReader<R> {
     /// Creates a [`ArchiveReader`] to read a 7z archive file from the given `source` reader.
     #[inline]
-    pub fn new(mut source: R, password: Password) -> Result<Self, Error> {
-        let archive = Archive::read(&mut source, &password)?;
+    pub fn new(source: R, password: Password) -> Result<Self, Error> {
+        Self::with_options(source, password, ReaderOptions::default())
+    }
+
+    /// Creates a [`ArchiveReader`] to read a 7z archive file from the given `source` reader,
+    /// enforcing the limits of `options` when reading and decoding the archive.
+    ///
+    /// See [`ReaderOptions`] for the available limits.
+    pub fn with_options(
+        mut source: R,
+        password: Password,
+        options: ReaderOptions,
+    ) -> Result<Self, Error> {
+        let archive = Archive::read_with_options(&mut source, &password, options)?;
 
         let mut reader = Self {
             source,
-            archive,
+            archive: Arc::new(archive),
             password,
             thread_count: 1,
             index: HashMap::default(),
+            cache: BlockCache::default(),
         };
 
         reader.fill_index();
@@ -1140,10 +1548,11 @@ impl<R: Read + Seek> ArchiveReader<R> {
     pub fn from_archive(archive: Archive, source: R, password: Password) -> Self {
         let mut reader = Self {
             source,
-            archive,
+            archive: Arc::new(archive),
             password,
             thread_count: 1,
             index: HashMap::default(),
+            cache: BlockCache::default(),
         };
 
         reader.fill_index();
@@ -1163,6 +1572,35 @@ impl<R: Read + Seek> ArchiveReader<R> {
         self.thread_count = thread_count.clamp(1, 256);
     }
 
+    /// Sets the memory budget in bytes of the cache of decoded data used by
+    /// [`ArchiveReader::read_file`]. The cache is disabled by default.
+    ///
+    /// Blocks which fit into the budget are decoded and cached completely, so reading many
+    /// files of a solid block decodes it only once. Of larger blocks, the data following the
+    /// requested file is cached up to the budget, and the decoder of the block is kept on
+    /// its own thread, positioned after the cached data. Reading the files in the order of
+    /// the archive therefore resumes the decoder instead of decoding the block again from
+    /// its start. Where no thread can be spawned, the block is decoded from its start on
+    /// every cache miss.
+    ///
+    /// The estimated memory of the kept decoders, which is mostly determined by the dictionary
+    /// size, counts against the budget as well, and at most four decoders are kept. The least
+    /// recently used data and decoders are evicted first.
+    pub fn set_block_cache_size(&mut self, budget: usize) {
+        self.cache.set_budget(budget);
+    }
+
+    /// Registers a custom `codec` to decode the coders of its method id, which this crate
+    /// doesn't support itself. It replaces a codec registered before for the same method id.
+    ///
+    /// The codecs are kept with the [`Archive`], so they are also used by the [`BlockDecoder`]s
+    /// of it.
+    pub fn register_codec(&mut self, codec: impl Codec + 'static) {
+        Arc::make_mut(&mut self.archive)
+            .codecs
+            .register(Arc::new(codec));
+    }
+
     fn fill_index(&mut self) {
         for (file_index, file) in self.archive.files.iter().enumerate() {
             let block_index = self.archive.stream_map.file_block_index[file_index];
@@ -1186,22 +1624,14 @@ impl<R: Read + Seek> ArchiveReader<R> {
         &self.archive
     }
 
-    fn build_decode_stack<'r>(
-        source: &'r mut R,
+    pub(crate) fn build_decode_stack<'r, S: Read + Seek + 'r>(
+        source: S,
         archive: &Archive,
         block_index: usize,
         password: &Password,
         thread_count: u32,
     ) -> Result<(Box<dyn Read + 'r>, usize), Error> {
         let block = &archive.blocks[block_index];
-        if block.total_input_streams > block.total_output_streams {
-            return Self::build_decode_stack2(source, archive, block_index, password, thread_count);
-        }
-        let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
-        let block_offset = SIGNATURE_HEADER_SIZE
-            + archive.pack_pos
-            + archive.stream_map.pack_stream_offsets[first_pack_stream_index];
-
         let (mut has_crc, mut crc) = (block.has_crc, block.crc);
 
         // Single stream blocks might have it's CRC stored in the single substream information.
@@ -1221,27 +1651,13 @@ impl<R: Read + Seek> ArchiveReader<R> {
             }
         }
 
-        source.seek(SeekFrom::Start(block_offset))?;
-        let pack_size = archive.pack_sizes[first_pack_stream_index] as usize;
-
-        let mut decoder: Box<dyn Read> = Box::new(BoundedReader::new(source, pack_size));
-        let block = &archive.blocks[block_index];
-        for (index, coder) in block.ordered_coder_iter() {
-            if coder.num_in_streams != 1 || coder.num_out_streams != 1 {
-                return Err(Error::unsupported(
-                    "Multi input/output stream coders are not supported",
-                ));
-            }
-            let next = add_decoder(
-                decoder,
-                block.get_unpack_size_at_index(index) as usize,
-                coder,
-                password,
-                MAX_MEM_LIMIT_KB,
-                thread_count,
-            )?;
-            decoder = Box::new(next);
-        }
+        let (mut decoder, pack_size) = Self::build_unverified_decode_stack(
+            source,
+            archive,
+            block_index,
+            password,
+            thread_count,
+        )?;
         if has_crc {
             decoder = Box::new(Crc32VerifyingReader::new(
                 decoder,
@@ -1249,179 +1665,65 @@ impl<R: Read + Seek> ArchiveReader<R> {
                 crc,
             ));
         }
-
         Ok((decoder, pack_size))
     }
 
-    fn build_decode_stack2<'r>(
-        source: &'r mut R,
+    /// Builds the decode stack of a block like [`ArchiveReader::build_decode_stack`], but
+    /// without verifying the checksum of the decoded data.
+    pub(crate) fn build_unverified_decode_stack<'r, S: Read + Seek + 'r>(
+        mut source: S,
         archive: &Archive,
         block_index: usize,
         password: &Password,
         thread_count: u32,
     ) -> Result<(Box<dyn Read + 'r>, usize), Error> {
-        const MAX_CODER_COUNT: usize = 32;
         let block = &archive.blocks[block_index];
-        if block.coders.len() > MAX_CODER_COUNT {
-            return Err(Error::unsupported(format!(
-                "Too many coders: {}",
-                block.coders.len()
-            )));
-        }
-
-        assert!(block.total_input_streams > block.total_output_streams);
-        let shared_source = Rc::new(RefCell::new(source));
         let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
-        let start_pos = SIGNATURE_HEADER_SIZE + archive.pack_pos;
-        let offsets = &archive.stream_map.pack_stream_offsets[first_pack_stream_index..];
-
-        let mut sources = Vec::with_capacity(block.packed_streams.len());
-
-        for (i, offset) in offsets[..block.packed_streams.len()].iter().enumerate() {
-            let pack_pos = start_pos + offset;
-            let pack_size = archive.pack_sizes[first_pack_stream_index + i];
-
-            let pack_reader = SharedBoundedReader::new(
-                Rc::clone(&shared_source),
-                (pack_pos, pack_pos + pack_size),
-            );
-
-            sources.push(pack_reader);
-        }
-
-        let mut coder_to_stream_map = [usize::MAX; MAX_CODER_COUNT];
-
-        let mut si = 0;
-        for (i, coder) in block.coders.iter().enumerate() {
-            coder_to_stream_map[i] = si;
-            si += coder.num_in_streams as usize;
-        }
-
-        let main_coder_index = {
-            let mut coder_used = [false; MAX_CODER_COUNT];
-            for bp in block.bind_pairs.iter() {
-                coder_used[bp.out_index as usize] = true;
-            }
-            let mut mci = 0;
-            for (i, used) in coder_used[..block.coders.len()].iter().enumerate() {
-                if !used {
-                    mci = i;
-                    break;
-                }
-            }
-            mci
+        let pack_stream_range =
+            first_pack_stream_index..first_pack_stream_index + block.packed_streams.len();
+        let (Some(pack_sizes), Some(offsets)) = (
+            archive.pack_sizes.get(pack_stream_range.clone()),
+            archive
+                .stream_map
+                .pack_stream_offsets
+                .get(pack_stream_range),
+        ) else {
+            return Err(Error::other("Missing packed streams of block"));
         };
+        let Some(&pack_size) = pack_sizes.first() else {
+            return Err(Error::other("Block has no packed streams"));
+        };
+        let start_pos = archive.packed_streams_start();
 
-        let id = block.coders[main_coder_index].encoder_method_id();
-        if id != EncoderMethod::ID_BCJ2 {
-            return Err(Error::unsupported(format!("Unsupported method: {id:?}")));
-        }
-
-        let num_in_streams = block.coders[main_coder_index].num_in_streams as usize;
-        let mut inputs: Vec<Box<dyn Read>> = Vec::with_capacity(num_in_streams);
-        let start_i = coder_to_stream_map[main_coder_index];
-        for i in start_i..num_in_streams + start_i {
-            inputs.push(Self::get_in_stream(
-                block,
-                &sources,
-                &coder_to_stream_map,
-                password,
-                i,
-                thread_count,
-            )?);
-        }
-        let mut decoder: Box<dyn Read> = Box::new(Bcj2Reader::new(inputs, block.get_unpack_size()));
-        if block.has_crc {
-            decoder = Box::new(Crc32VerifyingReader::new(
-                decoder,
-                block.get_unpack_size() as usize,
-                block.crc,
-            ));
-        }
-        Ok((
-            decoder,
-            archive.pack_sizes[first_pack_stream_index] as usize,
-        ))
-    }
-
-    fn get_in_stream<'r>(
-        block: &Block,
-        sources: &[SharedBoundedReader<'r, R>],
-        coder_to_stream_map: &[usize],
-        password: &Password,
-        in_stream_index: usize,
-        thread_count: u32,
-    ) -> Result<Box<dyn Read + 'r>, Error>
-    where
-        R: 'r,
-    {
-        let index = block
-            .packed_streams
-            .iter()
-            .position(|&i| i == in_stream_index as u64);
-        if let Some(index) = index {
-            return Ok(Box::new(sources[index].clone()));
-        }
-
-        let bp = block
-            .find_bind_pair_for_in_stream(in_stream_index as u64)
-            .ok_or_else(|| {
-                Error::other(format!(
-                    "Couldn't find bind pair for stream {in_stream_index}"
-                ))
-            })?;
-        let index = bp.out_index as usize;
+        let packed_streams: Vec<Box<dyn Read + 'r>> = if pack_sizes.len() == 1 {
+            source.seek(SeekFrom::Start(start_pos + offsets[0]))?;
+            vec![Box::new(BoundedReader::new(source, pack_size as usize))]
+        } else {
+            // The packed streams are read alternately, so they share the source.
+            let shared_source = Rc::new(RefCell::new(source));
+            offsets
+                .iter()
+                .zip(pack_sizes)
+                .map(|(offset, pack_size)| {
+                    let pack_pos = start_pos + offset;
+                    Box::new(SharedBoundedReader::new(
+                        Rc::clone(&shared_source),
+                        (pack_pos, pack_pos + pack_size),
+                    )) as Box<dyn Read + 'r>
+                })
+                .collect()
+        };
 
-        Self::get_in_stream2(
+        let decoder = CoderGraph::new(
             block,
-            sources,
-            coder_to_stream_map,
+            packed_streams,
             password,
-            index,
+            archive.options.max_memory_kb,
             thread_count,
+            &archive.codecs,
         )
-    }
-
-    fn get_in_stream2<'r>(
-        block: &Block,
-        sources: &[SharedBoundedReader<'r, R>],
-        coder_to_stream_map: &[usize],
-        password: &Password,
-        in_stream_index: usize,
-        thread_count: u32,
-    ) -> Result<Box<dyn Read + 'r>, Error>
-    where
-        R: 'r,
-    {
-        let coder = &block.coders[in_stream_index];
-        let start_index = coder_to_stream_map[in_stream_index];
-        if start_index == usize::MAX {
-            return Err(Error::other("in_stream_index out of range"));
-        }
-        let uncompressed_len = block.unpack_sizes[in_stream_index] as usize;
-        if coder.num_in_streams == 1 {
-            let input = Self::get_in_stream(
-                block,
-                sources,
-                coder_to_stream_map,
-                password,
-                start_index,
-                thread_count,
-            )?;
-
-            let decoder = add_decoder(
-                input,
-                uncompressed_len,
-                coder,
-                password,
-                MAX_MEM_LIMIT_KB,
-                thread_count,
-            )?;
-            return Ok(Box::new(decoder));
-        }
-        Err(Error::unsupported(
-            "Multi input stream coders are not yet supported",
-        ))
+        .into_decoder()?;
+        Ok((decoder, pack_size as usize))
     }
 
     /// Takes a closure to decode each files in the archive.
@@ -1458,6 +1760,48 @@ impl<R: Read + Seek> ArchiveReader<R> {
         Ok(())
     }
 
+    /// Returns an iterator over all entries of the archive and their data.
+    ///
+    /// Other than [`ArchiveReader::for_each_entries`], the caller pulls the entries one
+    /// by one with [`Entries::next_entry`], so errors can be propagated with `?` and the
+    /// iteration can be stopped at any time. See [`Entries`] for an example.
+    pub fn entries(&mut self) -> Entries<'_, R> {
+        Entries::new(
+            &mut self.source,
+            &self.archive,
+            &self.password,
+            self.thread_count,
+        )
+    }
+
+    /// Opens the entry with the given path inside the archive for reading and seeking
+    /// in its data, without reading all of it into memory.
+    ///
+    /// See [`EntryReader`] for the cost of seeking in compressed and solid blocks.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::io::{Read, Seek, SeekFrom};
+    ///
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+    /// let mut entry = reader.open_entry("data.bin").unwrap();
+    /// entry.seek(SeekFrom::End(-16)).unwrap();
+    /// let mut trailer = [0; 16];
+    /// entry.read_exact(&mut trailer).unwrap();
+    /// ```
+    pub fn open_entry(&mut self, name: &str) -> Result<EntryReader<'_, R>, Error> {
+        let index_entry = *self.index.get(name).ok_or(Error::FileNotFound)?;
+        Ok(EntryReader::new(
+            &mut self.source,
+            &self.archive,
+            &self.password,
+            self.thread_count,
+            index_entry.file_index,
+        ))
+    }
+
     /// Returns the data of a file with the given path inside the archive.
     ///
     /// # Notice
@@ -1475,6 +1819,10 @@ impl<R: Read + Seek> ArchiveReader<R> {
             .block_index
             .ok_or_else(|| Error::other("File has no associated block"))?;
 
+        if self.cache.budget() > 0 {
+            return self.read_file_cached(index_entry.file_index, block_index);
+        }
+
         match self.archive.is_solid {
             true => {
                 let mut result = None;
@@ -1504,7 +1852,7 @@ impl<R: Read + Seek> ArchiveReader<R> {
             false => {
                 let pack_index = self.archive.stream_map.block_first_pack_stream_index[block_index];
                 let pack_offset = self.archive.stream_map.pack_stream_offsets[pack_index];
-                let block_offset = SIGNATURE_HEADER_SIZE + self.archive.pack_pos + pack_offset;
+                let block_offset = self.archive.packed_streams_start() + pack_offset;
 
                 self.source.seek(SeekFrom::Start(block_offset))?;
 
@@ -1535,6 +1883,124 @@ impl<R: Read + Seek> ArchiveReader<R> {
         }
     }
 
+    fn read_file_cached(
+        &mut self,
+        file_index: usize,
+        block_index: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let archive = &self.archive;
+        let file = &archive.files[file_index];
+        let mut stream_files = archive
+            .block_file_range(block_index)
+            .map(|index| (index, &archive.files[index]))
+            .filter(|(_, file)| file.has_stream);
+        let offset: u64 = stream_files
+            .by_ref()
+            .take_while(|&(index, _)| index != file_index)
+            .map(|(_, file)| file.size)
+            .sum();
+
+        let (file_size, has_crc, crc) = (file.size, file.has_crc, file.crc);
+
+        let data = match self.cache.get(block_index, offset, file_size) {
+            Some(data) => data.to_vec(),
+            None => {
+                let budget = self.cache.budget() as u64;
+                let block = &archive.blocks[block_index];
+                let block_size = block.get_unpack_size();
+                let (start, size) = if block_size <= budget {
+                    (0, block_size)
+                } else {
+                    // Cache the following files which fit into the budget besides the
+                    // decoder, so the decoder is positioned at the start of a file afterward.
+                    let budget = budget.saturating_sub(decoder_memory(block) as u64);
+                    let mut size = file_size;
+                    for (_, next) in stream_files {
+                        if size + next.size > budget {
+                            break;
+                        }
+                        size += next.size;
+                    }
+                    (offset, size)
+                };
+
+                let (window, decoder) =
+                    self.decode_window(block_index, start, size).map_err(|e| {
+                        match e.downcast::<Error>() {
+                            Ok(e) => e,
+                            Err(e) => {
+                                let name = self.archive.files[file_index].name();
+                                Error::io_msg(e, format!("Decode entry:{name}"))
+                            }
+                        }
+                    })?;
+                let file_start = (offset - start) as usize;
+                let data = window[file_start..file_start + file_size as usize].to_vec();
+                self.cache.insert(block_index, start, window);
+                if let Some(decoder) = decoder {
+                    self.cache.insert_decoder(decoder);
+                }
+                data
+            }
+        };
+
+        if has_crc && crc32fast::hash(&data) as u64 != crc {
+            return Err(Error::ChecksumVerificationFailed);
+        }
+        Ok(data)
+    }
+
+    /// Decodes `size` bytes of the block at `block_index` starting at `start`, resuming the
+    /// cached decoder of the block if it's positioned at or before `start`.
+    ///
+    /// Returns the decoded data and the decoder, unless it reached the end of the block.
+    fn decode_window(
+        &mut self,
+        block_index: usize,
+        start: u64,
+        size: u64,
+    ) -> io::Result<(Vec<u8>, Option<CachedDecoder>)> {
+        let mut window = Vec::with_capacity(size.min(self.cache.budget() as u64) as usize);
+        let mut unfinished = None;
+        let decoder = match self.cache.take_decoder(block_index, start) {
+            Some(decoder) => Ok(decoder),
+            None => CachedDecoder::spawn(
+                &self.archive,
+                block_index,
+                &self.password,
+                self.thread_count,
+            ),
+        };
+        match decoder {
+            Ok(mut decoder) => {
+                let skip = start - decoder.position();
+                let mut reader = decoder.reader(&mut self.source);
+                io::copy(&mut (&mut reader).take(skip), &mut io::sink())?;
+                reader.take(size).read_to_end(&mut window)?;
+                if decoder.position() < self.archive.blocks[block_index].get_unpack_size() {
+                    unfinished = Some(decoder);
+                }
+            }
+            // Without threads, the block is decoded from its start every time.
+            Err(_) => {
+                let (mut block_reader, _size) = Self::build_decode_stack(
+                    &mut self.source,
+                    &self.archive,
+                    block_index,
+                    &self.password,
+                    self.thread_count,
+                )
+                .map_err(io::Error::other)?;
+                io::copy(&mut (&mut block_reader).take(start), &mut io::sink())?;
+                block_reader.take(size).read_to_end(&mut window)?;
+            }
+        }
+        if (window.len() as u64) < size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok((window, unfinished))
+    }
+
     /// Get the compression method(s) used for a specific file in the archive.
     pub fn file_compression_methods(
         &self,
@@ -1618,14 +2084,12 @@ impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
     ///
     /// The entries are returned in the order they appear in the block.
     pub fn entries(&self) -> &[ArchiveEntry] {
-        let start = self.archive.stream_map.block_first_file_index[self.block_index];
-        let file_count = self.archive.blocks[self.block_index].num_unpack_sub_streams;
-        &self.archive.files[start..(file_count + start)]
+        &self.archive.files[self.archive.block_file_range(self.block_index)]
     }
 
     /// Returns the number of entries contained in this block.
     pub fn entry_count(&self) -> usize {
-        self.archive.blocks[self.block_index].num_unpack_sub_streams
+        self.archive.block_file_range(self.block_index).len()
     }
 
     /// Takes a closure to decode each files in this block.
@@ -1645,18 +2109,14 @@ impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
             password,
             source,
         } = self;
-        let (mut block_reader, _size) = ArchiveReader::build_decode_stack(
+        let (mut block_reader, _size) = ArchiveReader::<R>::build_decode_stack(
             source,
             archive,
             block_index,
             password,
             thread_count,
         )?;
-        let start = archive.stream_map.block_first_file_index[block_index];
-        let file_count = archive.blocks[block_index].num_unpack_sub_streams;
-
-        for file_index in start..(file_count + start) {
-            let file = &archive.files[file_index];
+        for file in &archive.files[archive.block_file_range(block_index)] {
             if file.has_stream && file.size > 0 {
                 let mut decoder: Box<dyn Read> =
                     Box::new(BoundedReader::new(&mut block_reader, file.size as usize));
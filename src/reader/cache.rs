@@ -0,0 +1,363 @@
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    sync::{
+        Arc,
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread,
+};
+
+use super::ArchiveReader;
+use crate::{Archive, Block, Password, decoder};
+
+/// Size of the chunks of packed and decoded data exchanged with the thread of a
+/// [`CachedDecoder`].
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum number of decoders kept in a [`BlockCache`], each of which has its own thread.
+const MAX_DECODERS: usize = 4;
+
+/// A least recently used cache of decoded block data, limited by a memory budget.
+///
+/// For blocks larger than the budget, the decoder is kept as well, positioned after the
+/// data read last, so reading the following data resumes it instead of decoding the block
+/// again from its start. The memory of the decoders counts against the budget.
+#[derive(Default)]
+pub(super) struct BlockCache {
+    budget: usize,
+    used: usize,
+    /// The cached data and decoders, the most recently used last.
+    entries: Vec<CacheEntry>,
+}
+
+enum CacheEntry {
+    Window(CachedWindow),
+    Decoder(CachedDecoder),
+}
+
+impl CacheEntry {
+    /// Returns the memory used by the entry.
+    fn size(&self) -> usize {
+        match self {
+            CacheEntry::Window(window) => window.data.len(),
+            CacheEntry::Decoder(decoder) => decoder.memory,
+        }
+    }
+}
+
+/// Decoded data of a block, starting at `offset` in the decoded data of the block.
+struct CachedWindow {
+    block_index: usize,
+    offset: u64,
+    data: Vec<u8>,
+}
+
+impl CachedWindow {
+    fn contains(&self, block_index: usize, offset: u64, size: u64) -> bool {
+        self.block_index == block_index
+            && self.offset <= offset
+            && offset + size <= self.offset + self.data.len() as u64
+    }
+}
+
+/// Returns the estimated memory of a [`CachedDecoder`] of the `block`.
+pub(super) fn decoder_memory(block: &Block) -> usize {
+    // The decoded chunk and the packed data requested by the thread.
+    let buffers = 2 * CHUNK_SIZE;
+    block
+        .coders
+        .iter()
+        .map(decoder::memory_usage)
+        .fold(buffers, usize::saturating_add)
+}
+
+impl BlockCache {
+    pub(super) fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub(super) fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0);
+    }
+
+    /// Returns `size` bytes of the decoded data of the block starting at `offset`, if cached.
+    pub(super) fn get(&mut self, block_index: usize, offset: u64, size: u64) -> Option<&[u8]> {
+        let position = self.entries.iter().position(|entry| match entry {
+            CacheEntry::Window(window) => window.contains(block_index, offset, size),
+            CacheEntry::Decoder(_) => false,
+        })?;
+        let entry = self.entries.remove(position);
+        self.entries.push(entry);
+
+        let Some(CacheEntry::Window(window)) = self.entries.last() else {
+            unreachable!()
+        };
+        let start = (offset - window.offset) as usize;
+        Some(&window.data[start..start + size as usize])
+    }
+
+    /// Caches the decoded data of the block starting at `offset`, replacing the data cached
+    /// for the block before. Data larger than the budget is not cached.
+    pub(super) fn insert(&mut self, block_index: usize, offset: u64, data: Vec<u8>) {
+        if data.len() > self.budget {
+            return;
+        }
+        if let Some(position) = self.entries.iter().position(
+            |entry| matches!(entry, CacheEntry::Window(window) if window.block_index == block_index),
+        ) {
+            self.used -= self.entries.remove(position).size();
+        }
+        self.push(CacheEntry::Window(CachedWindow {
+            block_index,
+            offset,
+            data,
+        }));
+    }
+
+    /// Removes the decoder of the block from the cache and returns it, if it's positioned
+    /// at or before `offset`.
+    pub(super) fn take_decoder(
+        &mut self,
+        block_index: usize,
+        offset: u64,
+    ) -> Option<CachedDecoder> {
+        let position = self.entries.iter().position(
+            |entry| matches!(entry, CacheEntry::Decoder(decoder) if decoder.block_index == block_index),
+        )?;
+        self.used -= self.entries[position].size();
+        let CacheEntry::Decoder(decoder) = self.entries.remove(position) else {
+            unreachable!()
+        };
+        (decoder.position <= offset).then_some(decoder)
+    }
+
+    /// Keeps the `decoder` to resume it when the data following its position is read.
+    /// Decoders larger than the budget are not kept.
+    pub(super) fn insert_decoder(&mut self, decoder: CachedDecoder) {
+        if decoder.memory > self.budget {
+            return;
+        }
+        let decoders = self
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry, CacheEntry::Decoder(_)))
+            .count();
+        if decoders >= MAX_DECODERS {
+            let position = self
+                .entries
+                .iter()
+                .position(|entry| matches!(entry, CacheEntry::Decoder(_)))
+                .unwrap();
+            self.used -= self.entries.remove(position).size();
+        }
+        self.push(CacheEntry::Decoder(decoder));
+    }
+
+    /// Adds the `entry` as the most recently used one, evicting the least recently used
+    /// entries to fit it into the budget.
+    fn push(&mut self, entry: CacheEntry) {
+        let size = entry.size();
+        self.evict(size);
+        self.used += size;
+        self.entries.push(entry);
+    }
+
+    /// Removes the least recently used entries until `additional` bytes fit into the budget.
+    fn evict(&mut self, additional: usize) {
+        while !self.entries.is_empty() && self.used + additional > self.budget {
+            self.used -= self.entries.remove(0).size();
+        }
+    }
+}
+
+/// A message from the thread of a [`CachedDecoder`].
+enum Message {
+    /// Requests to read up to `size` bytes of packed data at `position` of the source.
+    Read { position: u64, size: usize },
+    /// The next chunk of decoded data.
+    Decoded(io::Result<Vec<u8>>),
+}
+
+/// A decoder of a block which can be kept between reads, because it doesn't borrow the
+/// source of the archive.
+///
+/// The decoder runs on its own thread and requests the packed data from the reader, which
+/// reads it from the source while reading the decoded data. Once no decoded data is read
+/// anymore, the thread waits for the next read. Dropping the decoder stops the thread.
+pub(super) struct CachedDecoder {
+    block_index: usize,
+    /// Estimated memory of the decoder, see [`decoder_memory`].
+    memory: usize,
+    /// Position of the decoder in the decoded data of the block.
+    position: u64,
+    chunk: Cursor<Vec<u8>>,
+    messages: Receiver<Message>,
+    responses: SyncSender<io::Result<Vec<u8>>>,
+}
+
+impl CachedDecoder {
+    /// Starts decoding the block at `block_index` on a new thread.
+    ///
+    /// Fails if no thread can be spawned, for example on platforms without threads.
+    pub(super) fn spawn(
+        archive: &Arc<Archive>,
+        block_index: usize,
+        password: &Password,
+        thread_count: u32,
+    ) -> io::Result<Self> {
+        let (message_sender, messages) = mpsc::sync_channel(0);
+        let (responses, response_receiver) = mpsc::sync_channel(0);
+        let source = RemoteSource {
+            position: 0,
+            buffer_start: 0,
+            buffer: Vec::new(),
+            messages: message_sender.clone(),
+            responses: response_receiver,
+        };
+        let memory = decoder_memory(&archive.blocks[block_index]);
+        let archive = Arc::clone(archive);
+        let password = password.clone();
+        thread::Builder::new().spawn(move || {
+            let mut decoder = match ArchiveReader::<RemoteSource>::build_decode_stack(
+                source,
+                &archive,
+                block_index,
+                &password,
+                thread_count,
+            ) {
+                Ok((decoder, _size)) => decoder,
+                Err(e) => {
+                    let _ = message_sender.send(Message::Decoded(Err(io::Error::other(e))));
+                    return;
+                }
+            };
+            loop {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let decoded = match decoder.read(&mut chunk) {
+                    Ok(0) => return,
+                    Ok(size) => {
+                        chunk.truncate(size);
+                        Ok(chunk)
+                    }
+                    Err(e) => Err(e),
+                };
+                let failed = decoded.is_err();
+                // Sending fails once the decoder was dropped.
+                if message_sender.send(Message::Decoded(decoded)).is_err() || failed {
+                    return;
+                }
+            }
+        })?;
+        Ok(Self {
+            block_index,
+            memory,
+            position: 0,
+            chunk: Cursor::new(Vec::new()),
+            messages,
+            responses,
+        })
+    }
+
+    /// Returns the position of the decoder in the decoded data of the block.
+    pub(super) fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns a reader of the decoded data, which reads the packed data from `source`.
+    pub(super) fn reader<'a, R: Read + Seek>(
+        &'a mut self,
+        source: &'a mut R,
+    ) -> CachedDecoderReader<'a, R> {
+        CachedDecoderReader {
+            decoder: self,
+            source,
+        }
+    }
+}
+
+/// Reads the decoded data of a [`CachedDecoder`].
+pub(super) struct CachedDecoderReader<'a, R> {
+    decoder: &'a mut CachedDecoder,
+    source: &'a mut R,
+}
+
+impl<R: Read + Seek> Read for CachedDecoderReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let decoder = &mut *self.decoder;
+        loop {
+            let size = decoder.chunk.read(buf)?;
+            if size > 0 || buf.is_empty() {
+                decoder.position += size as u64;
+                return Ok(size);
+            }
+            match decoder.messages.recv() {
+                Ok(Message::Read { position, size }) => {
+                    let mut data = Vec::with_capacity(size);
+                    let result = self
+                        .source
+                        .seek(SeekFrom::Start(position))
+                        .and_then(|_| (&mut *self.source).take(size as u64).read_to_end(&mut data))
+                        .map(|_| data);
+                    decoder
+                        .responses
+                        .send(result)
+                        .map_err(|_| io::Error::other("Block decoder stopped"))?;
+                }
+                Ok(Message::Decoded(chunk)) => decoder.chunk = Cursor::new(chunk?),
+                // The thread has finished decoding the block.
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+/// The source of the decoder on the thread of a [`CachedDecoder`], which requests the
+/// packed data from the reader.
+struct RemoteSource {
+    position: u64,
+    /// Position of `buffer` in the source.
+    buffer_start: u64,
+    buffer: Vec<u8>,
+    messages: SyncSender<Message>,
+    responses: Receiver<io::Result<Vec<u8>>>,
+}
+
+impl Read for RemoteSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position < self.buffer_start || self.position >= buffer_end {
+            let stopped = || io::Error::other("Block decoder stopped");
+            self.messages
+                .send(Message::Read {
+                    position: self.position,
+                    size: CHUNK_SIZE,
+                })
+                .map_err(|_| stopped())?;
+            self.buffer = self.responses.recv().map_err(|_| stopped())??;
+            self.buffer_start = self.position;
+        }
+        let start = (self.position - self.buffer_start) as usize;
+        let size = buf.len().min(self.buffer.len() - start);
+        buf[..size].copy_from_slice(&self.buffer[start..start + size]);
+        self.position += size as u64;
+        Ok(size)
+    }
+}
+
+impl Seek for RemoteSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Seeking from the end of the source",
+                ));
+            }
+        }
+        .ok_or_else(|| io::Error::other("SeekBeforeStart"))?;
+        Ok(self.position)
+    }
+}
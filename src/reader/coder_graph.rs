@@ -0,0 +1,126 @@
+use std::io::Read;
+
+use lzma_rust2::filter::bcj2::Bcj2Reader;
+
+use super::MAX_BLOCK_CODERS;
+use crate::{
+    Password, archive::EncoderMethod, block::Block, codec::registry::CodecRegistry,
+    decoder::add_decoder, error::Error,
+};
+
+/// Decoder of the coder graph of a block, which follows the bind pairs and packed streams of
+/// the block from its main output stream back to the packed streams.
+pub(super) struct CoderGraph<'a, 'r> {
+    block: &'a Block,
+    /// Readers of the packed streams of the block, in the order of `Block::packed_streams`.
+    /// Each is taken by the coder reading it.
+    packed_streams: Vec<Option<Box<dyn Read + 'r>>>,
+    /// Whether the output of each coder is already read by another coder.
+    used_coders: Vec<bool>,
+    password: &'a Password,
+    max_mem_limit_kb: usize,
+    thread_count: u32,
+    codecs: &'a CodecRegistry,
+}
+
+impl<'a, 'r> CoderGraph<'a, 'r> {
+    pub(super) fn new(
+        block: &'a Block,
+        packed_streams: Vec<Box<dyn Read + 'r>>,
+        password: &'a Password,
+        max_mem_limit_kb: usize,
+        thread_count: u32,
+        codecs: &'a CodecRegistry,
+    ) -> Self {
+        Self {
+            block,
+            packed_streams: packed_streams.into_iter().map(Some).collect(),
+            used_coders: vec![false; block.coders.len()],
+            password,
+            max_mem_limit_kb,
+            thread_count,
+            codecs,
+        }
+    }
+
+    /// Returns the decoder of the main output stream of the block.
+    pub(super) fn into_decoder(mut self) -> Result<Box<dyn Read + 'r>, Error> {
+        if self.block.coders.len() > MAX_BLOCK_CODERS {
+            return Err(Error::TooManyCoders {
+                count: self.block.coders.len(),
+                max: MAX_BLOCK_CODERS,
+            });
+        }
+        let out_index = self
+            .block
+            .main_out_stream()
+            .ok_or_else(|| Error::other("Block has no main output stream"))?;
+        self.out_stream(out_index)
+    }
+
+    /// Returns the decoder of the output stream with the given `index`.
+    fn out_stream(&mut self, index: u64) -> Result<Box<dyn Read + 'r>, Error> {
+        let block = self.block;
+        let coder_index = block
+            .coder_index_for_out_stream(index)
+            .ok_or_else(|| Error::other(format!("Output stream {index} out of range")))?;
+        if std::mem::replace(&mut self.used_coders[coder_index], true) {
+            return Err(Error::other(format!(
+                "Output of coder {coder_index} is read more than once"
+            )));
+        }
+        let coder = &block.coders[coder_index];
+        if coder.num_out_streams != 1 {
+            return Err(Error::unsupported(
+                "Coders with multiple output streams are not supported",
+            ));
+        }
+        let unpack_size = block.get_unpack_size_at_index(index as usize);
+
+        let first_in_stream = block.first_in_stream_index(coder_index);
+        let mut inputs = Vec::with_capacity(coder.num_in_streams as usize);
+        for in_index in first_in_stream..first_in_stream + coder.num_in_streams {
+            inputs.push(self.in_stream(in_index)?);
+        }
+
+        if coder.encoder_method_id() == EncoderMethod::ID_BCJ2 {
+            if inputs.len() != 4 {
+                return Err(Error::other("BCJ2 coder needs four input streams"));
+            }
+            return Ok(Box::new(Bcj2Reader::new(inputs, unpack_size)));
+        }
+        let [input] = <[_; 1]>::try_from(inputs).map_err(|_| {
+            Error::unsupported(format!(
+                "Multi input stream coders are not supported: {:?}",
+                coder.encoder_method_id()
+            ))
+        })?;
+        let decoder = add_decoder(
+            input,
+            unpack_size as usize,
+            coder,
+            self.password,
+            self.max_mem_limit_kb,
+            self.thread_count,
+            self.codecs,
+        )?;
+        Ok(Box::new(decoder))
+    }
+
+    /// Returns the reader of the input stream with the given `index`, which is either a packed
+    /// stream or the output stream of another coder.
+    fn in_stream(&mut self, index: u64) -> Result<Box<dyn Read + 'r>, Error> {
+        let block = self.block;
+        if let Some(position) = block.packed_streams.iter().position(|&i| i == index) {
+            return self
+                .packed_streams
+                .get_mut(position)
+                .and_then(Option::take)
+                .ok_or_else(|| Error::other(format!("Packed stream {position} is missing")));
+        }
+        let bind_pair = block
+            .find_bind_pair_for_in_stream(index)
+            .ok_or_else(|| Error::other(format!("Couldn't find bind pair for stream {index}")))?;
+        self.out_stream(bind_pair.out_index)
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    cell::RefCell,
+    io::{self, Read, Seek},
+    ops::Range,
+    rc::Rc,
+};
+
+use super::{ArchiveReader, BoundedReader, Crc32VerifyingReader, SharedBoundedReader};
+use crate::{Archive, ArchiveEntry, Error, Password};
+
+/// A pull-based iterator over all entries of an archive, created by [`ArchiveReader::entries`].
+///
+/// The entries are returned in the same order as by [`ArchiveReader::for_each_entries`]:
+/// the entries of each block in block order, followed by the entries without data.
+/// Data of an entry which isn't read before the next entry is requested is skipped.
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::*;
+///
+/// let mut reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+/// let mut entries = reader.entries();
+/// while let Some(result) = entries.next_entry() {
+///     let (entry, mut data) = result.unwrap();
+///     if entry.name().ends_with(".txt") {
+///         std::io::copy(&mut data, &mut std::io::stdout()).unwrap();
+///     }
+/// }
+/// ```
+pub struct Entries<'a, R: Read + Seek> {
+    source: Rc<RefCell<&'a mut R>>,
+    archive: &'a Archive,
+    password: &'a Password,
+    thread_count: u32,
+    /// Index of the next block to decode.
+    next_block_index: usize,
+    /// Remaining file indices of the current block.
+    block_files: Range<usize>,
+    decoder: Option<Box<dyn Read + 'a>>,
+    /// Bytes of the current entry which were not read yet.
+    remaining: u64,
+    /// Index of the next file to check for entries without a block.
+    next_empty_index: usize,
+}
+
+impl<'a, R: Read + Seek> Entries<'a, R> {
+    pub(super) fn new(
+        source: &'a mut R,
+        archive: &'a Archive,
+        password: &'a Password,
+        thread_count: u32,
+    ) -> Self {
+        Self {
+            source: Rc::new(RefCell::new(source)),
+            archive,
+            password,
+            thread_count,
+            next_block_index: 0,
+            block_files: 0..0,
+            decoder: None,
+            remaining: 0,
+            next_empty_index: 0,
+        }
+    }
+
+    /// Returns the next entry together with a reader for its data, or `None` if all entries
+    /// were returned.
+    ///
+    /// The checksum of the data is verified when the entry is read to its end. After an
+    /// error of the current block, the iteration continues with the next block.
+    pub fn next_entry(&mut self) -> Option<Result<(&'a ArchiveEntry, EntryStream<'_>), Error>> {
+        let archive = self.archive;
+        loop {
+            if let Some(file_index) = self.block_files.next() {
+                if let Err(e) = self.skip_remaining() {
+                    self.block_files = 0..0;
+                    return Some(Err(e));
+                }
+                let file = &archive.files[file_index];
+                if !file.has_stream || file.size == 0 {
+                    return Some(Ok((file, EntryStream::empty(&mut self.remaining))));
+                }
+
+                if self.decoder.is_none() {
+                    let source = SharedBoundedReader::new(Rc::clone(&self.source), (0, u64::MAX));
+                    match ArchiveReader::<R>::build_decode_stack(
+                        source,
+                        archive,
+                        self.next_block_index - 1,
+                        self.password,
+                        self.thread_count,
+                    ) {
+                        Ok((decoder, _size)) => self.decoder = Some(decoder),
+                        Err(e) => {
+                            self.block_files = 0..0;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+
+                self.remaining = file.size;
+                let decoder: &mut dyn Read = self.decoder.as_mut().unwrap();
+                let mut reader: Box<dyn Read> =
+                    Box::new(BoundedReader::new(decoder, file.size as usize));
+                if file.has_crc {
+                    reader = Box::new(Crc32VerifyingReader::new(
+                        reader,
+                        file.size as usize,
+                        file.crc,
+                    ));
+                }
+                return Some(Ok((
+                    file,
+                    EntryStream {
+                        reader: Some(reader),
+                        remaining: &mut self.remaining,
+                    },
+                )));
+            }
+
+            // The current block is finished, so its remaining data doesn't need to be skipped.
+            self.decoder = None;
+            self.remaining = 0;
+            if self.next_block_index < archive.blocks.len() {
+                self.block_files = archive.block_file_range(self.next_block_index);
+                self.next_block_index += 1;
+                continue;
+            }
+
+            while self.next_empty_index < archive.files.len() {
+                let file_index = self.next_empty_index;
+                self.next_empty_index += 1;
+                if archive.stream_map.file_block_index[file_index].is_none() {
+                    let file = &archive.files[file_index];
+                    return Some(Ok((file, EntryStream::empty(&mut self.remaining))));
+                }
+            }
+            return None;
+        }
+    }
+
+    /// Skips the data of the current entry which was not read.
+    fn skip_remaining(&mut self) -> Result<(), Error> {
+        if self.remaining == 0 {
+            return Ok(());
+        }
+        let Some(decoder) = self.decoder.as_mut() else {
+            return Ok(());
+        };
+        let skipped = io::copy(&mut decoder.take(self.remaining), &mut io::sink())
+            .map_err(|e| Error::from(e).maybe_bad_password(!self.password.is_empty()))?;
+        if skipped != self.remaining {
+            return Err(Error::io_msg(
+                io::ErrorKind::UnexpectedEof.into(),
+                "Skip entry data",
+            ));
+        }
+        self.remaining = 0;
+        Ok(())
+    }
+}
+
+/// Reader for the data of an entry returned by [`Entries::next_entry`].
+pub struct EntryStream<'e> {
+    reader: Option<Box<dyn Read + 'e>>,
+    /// Bytes of the entry which were not read yet, shared with [`Entries`].
+    remaining: &'e mut u64,
+}
+
+impl<'e> EntryStream<'e> {
+    fn empty(remaining: &'e mut u64) -> Self {
+        Self {
+            reader: None,
+            remaining,
+        }
+    }
+}
+
+impl Read for EntryStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(0);
+        };
+        let size = reader.read(buf)?;
+        *self.remaining -= size as u64;
+        Ok(size)
+    }
+}
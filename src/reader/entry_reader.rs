@@ -0,0 +1,197 @@
+use std::{
+    cell::RefCell,
+    io::{self, Read, Seek, SeekFrom},
+    rc::Rc,
+};
+
+use crc32fast::Hasher;
+
+use super::{ArchiveReader, SharedBoundedReader};
+use crate::{Archive, ArchiveEntry, EncoderMethod, Error, Password};
+
+/// A `Read + Seek` handle to the data of a single entry, created by
+/// [`ArchiveReader::open_entry`].
+///
+/// Seeking itself is cheap, the data is only decoded when it's read. Reading after seeking
+/// forward skips the decoded data in between, while reading after seeking backward decodes
+/// the block of the entry again from its start. In solid blocks the data of all entries in
+/// front of the entry is decoded and skipped as well. Blocks which are stored without
+/// compression are read directly at the requested position.
+///
+/// The checksum of the entry is verified each time the entry is read from its start to its
+/// end.
+pub struct EntryReader<'a, R: Read + Seek> {
+    source: Rc<RefCell<&'a mut R>>,
+    archive: &'a Archive,
+    password: &'a Password,
+    thread_count: u32,
+    entry: &'a ArchiveEntry,
+    block_index: Option<usize>,
+    /// Offset of the entry in the decoded data of its block.
+    block_offset: u64,
+    /// Position of the data in the source, if the block is stored without compression.
+    stored_offset: Option<u64>,
+    pos: u64,
+    decoder: Option<Box<dyn Read + 'a>>,
+    /// Position of `decoder` in the decoded data of the block.
+    decoder_pos: u64,
+    hasher: Hasher,
+    /// Number of bytes from the start of the entry which were added to `hasher`.
+    hashed: u64,
+}
+
+impl<'a, R: Read + Seek> EntryReader<'a, R> {
+    pub(super) fn new(
+        source: &'a mut R,
+        archive: &'a Archive,
+        password: &'a Password,
+        thread_count: u32,
+        file_index: usize,
+    ) -> Self {
+        let entry = &archive.files[file_index];
+        let block_index =
+            archive.stream_map.file_block_index[file_index].filter(|_| entry.has_stream);
+
+        let mut block_offset = 0;
+        let mut stored_offset = None;
+        if let Some(block_index) = block_index {
+            block_offset = archive
+                .block_file_range(block_index)
+                .take_while(|&index| index != file_index)
+                .map(|index| &archive.files[index])
+                .filter(|file| file.has_stream)
+                .map(|file| file.size)
+                .sum();
+
+            let block = &archive.blocks[block_index];
+            if block.coders.len() == 1
+                && block.packed_streams.len() == 1
+                && block.coders[0].encoder_method_id() == EncoderMethod::ID_COPY
+            {
+                let pack_index = archive.stream_map.block_first_pack_stream_index[block_index];
+                stored_offset = Some(
+                    archive.packed_streams_start()
+                        + archive.stream_map.pack_stream_offsets[pack_index],
+                );
+            }
+        }
+
+        Self {
+            source: Rc::new(RefCell::new(source)),
+            archive,
+            password,
+            thread_count,
+            entry,
+            block_index,
+            block_offset,
+            stored_offset,
+            pos: 0,
+            decoder: None,
+            decoder_pos: 0,
+            hasher: Hasher::new(),
+            hashed: 0,
+        }
+    }
+
+    /// Returns the entry which is read.
+    pub fn entry(&self) -> &'a ArchiveEntry {
+        self.entry
+    }
+
+    /// Returns the decoder of the block, positioned at `block_pos` in the decoded data.
+    fn decoder_at(&mut self, block_index: usize, block_pos: u64) -> io::Result<&mut dyn Read> {
+        if self.decoder.is_none() || self.decoder_pos > block_pos {
+            // Release the previous decoder before building a new one.
+            self.decoder = None;
+            let source = SharedBoundedReader::new(Rc::clone(&self.source), (0, u64::MAX));
+            let (decoder, _size) = ArchiveReader::<R>::build_unverified_decode_stack(
+                source,
+                self.archive,
+                block_index,
+                self.password,
+                self.thread_count,
+            )
+            .map_err(io::Error::other)?;
+            self.decoder = Some(decoder);
+            self.decoder_pos = 0;
+        }
+
+        let decoder = self.decoder.as_mut().unwrap();
+        if self.decoder_pos < block_pos {
+            let skip = block_pos - self.decoder_pos;
+            let skipped = io::copy(&mut decoder.take(skip), &mut io::sink())?;
+            self.decoder_pos += skipped;
+            if skipped != skip {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        Ok(decoder)
+    }
+}
+
+impl<R: Read + Seek> Read for EntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.entry.size.saturating_sub(self.pos);
+        let Some(block_index) = self.block_index else {
+            return Ok(0);
+        };
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let bound = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+        let buf = &mut buf[..bound];
+
+        let block_pos = self.block_offset + self.pos;
+        let size = match self.stored_offset {
+            Some(offset) => {
+                let mut source = self.source.borrow_mut();
+                source.seek(SeekFrom::Start(offset + block_pos))?;
+                source.read(buf)?
+            }
+            None => {
+                let size = self.decoder_at(block_index, block_pos)?.read(buf)?;
+                self.decoder_pos += size as u64;
+                size
+            }
+        };
+        if size == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        // Reading from the start again restarts the verification.
+        if self.pos == 0 {
+            self.hasher = Hasher::new();
+            self.hashed = 0;
+        }
+        if self.hashed == self.pos {
+            self.hasher.update(&buf[..size]);
+            self.hashed += size as u64;
+            if self.hashed == self.entry.size && self.entry.has_crc {
+                let crc = std::mem::replace(&mut self.hasher, Hasher::new()).finalize();
+                if crc as u64 != self.entry.crc {
+                    return Err(io::Error::other(Error::ChecksumVerificationFailed));
+                }
+            }
+        }
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<R: Read + Seek> Seek for EntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(pos) => self.entry.size.checked_add_signed(pos),
+            SeekFrom::Current(pos) => self.pos.checked_add_signed(pos),
+        };
+        let new_pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crc32fast::Hasher;
+
+use super::ArchiveReader;
+use crate::{Error, archive::SIGNATURE_HEADER_SIZE};
+
+/// Result of verifying data against its stored CRC32 checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcStatus {
+    /// The data matches the stored checksum.
+    Ok,
+    /// The data doesn't match the stored checksum.
+    Mismatch {
+        /// The checksum stored in the archive.
+        expected: u32,
+        /// The checksum of the data.
+        actual: u32,
+    },
+    /// The archive doesn't store a checksum for the data.
+    NotStored,
+    /// The data couldn't be read completely, so it wasn't verified.
+    NotChecked,
+}
+
+impl CrcStatus {
+    fn verify(expected: Option<u32>, actual: u32) -> Self {
+        match expected {
+            Some(expected) if expected == actual => Self::Ok,
+            Some(expected) => Self::Mismatch { expected, actual },
+            None => Self::NotStored,
+        }
+    }
+
+    /// Returns `true` if the verification didn't find a mismatch or an unreadable data.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok | Self::NotStored)
+    }
+}
+
+/// Test result of an entry with data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryTestResult {
+    /// Index of the entry in [`crate::Archive::files`].
+    pub file_index: usize,
+    /// Name of the entry.
+    pub name: String,
+    /// Result of verifying the decoded data of the entry.
+    pub crc: CrcStatus,
+}
+
+/// Test result of a block.
+#[derive(Debug)]
+pub struct BlockTestResult {
+    /// Index of the block in [`crate::Archive::blocks`].
+    pub block_index: usize,
+    /// The error which stopped decoding the block, if any.
+    ///
+    /// Entries of the block which follow the error are reported as [`CrcStatus::NotChecked`].
+    pub error: Option<Error>,
+}
+
+/// Test result of a packed stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackStreamTestResult {
+    /// Index of the packed stream in the archive.
+    pub pack_index: usize,
+    /// Result of verifying the raw data of the packed stream.
+    pub crc: CrcStatus,
+}
+
+/// Report of [`ArchiveReader::test`].
+#[derive(Debug)]
+pub struct TestReport {
+    /// Result of verifying the start header against the checksum in the signature header.
+    pub start_header_crc: CrcStatus,
+    /// Result of verifying the header against the checksum in the start header.
+    pub header_crc: CrcStatus,
+    /// Results of all packed streams.
+    pub pack_streams: Vec<PackStreamTestResult>,
+    /// Results of all blocks.
+    pub blocks: Vec<BlockTestResult>,
+    /// Results of all entries with data.
+    pub entries: Vec<EntryTestResult>,
+    /// Total number of bytes which were verified: the packed streams with a stored checksum
+    /// and the decoded data of all entries.
+    pub bytes_checked: u64,
+}
+
+impl TestReport {
+    /// Returns `true` if no error and no checksum mismatch was found.
+    pub fn is_ok(&self) -> bool {
+        self.start_header_crc.is_ok()
+            && self.header_crc.is_ok()
+            && self.pack_streams.iter().all(|stream| stream.crc.is_ok())
+            && self.blocks.iter().all(|block| block.error.is_none())
+            && self.entries.iter().all(|entry| entry.crc.is_ok())
+    }
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Tests the integrity of the archive, like `7z t`.
+    ///
+    /// Verifies the checksums of the headers and the packed streams and decodes all blocks
+    /// to verify the checksums of all entries. Other than [`ArchiveReader::for_each_entries`],
+    /// the test continues after a failure, so the returned [`TestReport`] contains the
+    /// status of every part of the archive.
+    ///
+    /// An error is only returned if the source itself can't be read.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+    /// let report = reader.test().unwrap();
+    /// for entry in report.entries.iter().filter(|entry| !entry.crc.is_ok()) {
+    ///     println!("{}: {:?}", entry.name, entry.crc);
+    /// }
+    /// ```
+    pub fn test(&mut self) -> Result<TestReport, Error> {
+        let mut bytes_checked = 0;
+        let (start_header_crc, header_crc) = self.test_headers()?;
+        let pack_streams = self.test_pack_streams(&mut bytes_checked)?;
+
+        let mut blocks = Vec::with_capacity(self.archive.blocks.len());
+        let mut entries = Vec::new();
+        for block_index in 0..self.archive.blocks.len() {
+            let error = self.test_block(block_index, &mut entries, &mut bytes_checked);
+            blocks.push(BlockTestResult { block_index, error });
+        }
+        entries.sort_by_key(|entry| entry.file_index);
+
+        Ok(TestReport {
+            start_header_crc,
+            header_crc,
+            pack_streams,
+            blocks,
+            entries,
+            bytes_checked,
+        })
+    }
+
+    fn test_headers(&mut self) -> Result<(CrcStatus, CrcStatus), Error> {
+        let mut signature_header = [0; SIGNATURE_HEADER_SIZE as usize];
+        self.source
+            .seek(SeekFrom::Start(self.archive.base_offset()))?;
+        self.source.read_exact(&mut signature_header)?;
+
+        let stored_crc = u32::from_le_bytes(signature_header[8..12].try_into().unwrap());
+        let start_header = &signature_header[12..32];
+        if stored_crc == 0 {
+            // The start header of an unfinished archive is empty, so the header was located
+            // by searching for it and its checksum is unknown.
+            return Ok((CrcStatus::NotStored, CrcStatus::NotStored));
+        }
+        let start_header_crc = CrcStatus::verify(Some(stored_crc), crc32fast::hash(start_header));
+        if !start_header_crc.is_ok() {
+            return Ok((start_header_crc, CrcStatus::NotChecked));
+        }
+
+        let next_header_offset = u64::from_le_bytes(start_header[0..8].try_into().unwrap());
+        let next_header_size = u64::from_le_bytes(start_header[8..16].try_into().unwrap());
+        let next_header_crc = u32::from_le_bytes(start_header[16..20].try_into().unwrap());
+        self.source.seek(SeekFrom::Start(
+            self.archive.base_offset() + SIGNATURE_HEADER_SIZE + next_header_offset,
+        ))?;
+        let mut hasher = Hasher::new();
+        let read = copy_with_crc(&mut (&mut self.source).take(next_header_size), &mut hasher)?;
+        let header_crc = if read == next_header_size {
+            CrcStatus::verify(Some(next_header_crc), hasher.finalize())
+        } else {
+            CrcStatus::NotChecked
+        };
+        Ok((start_header_crc, header_crc))
+    }
+
+    fn test_pack_streams(
+        &mut self,
+        bytes_checked: &mut u64,
+    ) -> Result<Vec<PackStreamTestResult>, Error> {
+        let archive = &self.archive;
+        let mut results = Vec::with_capacity(archive.pack_sizes.len());
+        for pack_index in 0..archive.pack_sizes.len() {
+            if !archive.pack_crcs_defined.contains(pack_index) {
+                results.push(PackStreamTestResult {
+                    pack_index,
+                    crc: CrcStatus::NotStored,
+                });
+                continue;
+            }
+            let size = archive.pack_sizes[pack_index];
+            self.source.seek(SeekFrom::Start(
+                archive.packed_streams_start() + archive.stream_map.pack_stream_offsets[pack_index],
+            ))?;
+            let mut hasher = Hasher::new();
+            let read = copy_with_crc(&mut (&mut self.source).take(size), &mut hasher)?;
+            *bytes_checked += read;
+            let crc = if read == size {
+                CrcStatus::verify(
+                    Some(archive.pack_crcs[pack_index] as u32),
+                    hasher.finalize(),
+                )
+            } else {
+                CrcStatus::NotChecked
+            };
+            results.push(PackStreamTestResult { pack_index, crc });
+        }
+        Ok(results)
+    }
+
+    /// Decodes the block at `block_index` and adds the results of its entries to `entries`.
+    /// Returns the error which stopped the decoding, if any.
+    fn test_block(
+        &mut self,
+        block_index: usize,
+        entries: &mut Vec<EntryTestResult>,
+        bytes_checked: &mut u64,
+    ) -> Option<Error> {
+        let archive = &self.archive;
+        let encrypted = !self.password.is_empty();
+        let mut stream_files = archive
+            .block_file_range(block_index)
+            .filter(|&file_index| archive.files[file_index].has_stream);
+
+        let mut error = None;
+        match Self::build_unverified_decode_stack(
+            &mut self.source,
+            archive,
+            block_index,
+            &self.password,
+            self.thread_count,
+        ) {
+            Ok((block_reader, _size)) => {
+                let mut block_reader = CrcReader::new(block_reader);
+                for file_index in stream_files.by_ref() {
+                    let file = &archive.files[file_index];
+                    let mut hasher = Hasher::new();
+                    let crc = match copy_with_crc(
+                        &mut (&mut block_reader).take(file.size),
+                        &mut hasher,
+                    ) {
+                        Ok(read) if read == file.size => CrcStatus::verify(
+                            file.has_crc.then_some(file.crc as u32),
+                            hasher.finalize(),
+                        ),
+                        Ok(_) => {
+                            error = Some(Error::io_msg(
+                                std::io::ErrorKind::UnexpectedEof.into(),
+                                format!("Decode entry:{}", file.name()),
+                            ));
+                            CrcStatus::NotChecked
+                        }
+                        Err(e) => {
+                            error = Some(Error::from(e).maybe_bad_password(encrypted));
+                            CrcStatus::NotChecked
+                        }
+                    };
+                    entries.push(EntryTestResult {
+                        file_index,
+                        name: file.name().to_string(),
+                        crc,
+                    });
+                    if error.is_some() {
+                        break;
+                    }
+                }
+                *bytes_checked += block_reader.read;
+
+                let block = &archive.blocks[block_index];
+                if error.is_none()
+                    && block.has_crc
+                    && block_reader.read == block.get_unpack_size()
+                    && block_reader.hasher.finalize() != block.crc as u32
+                {
+                    error = Some(Error::ChecksumVerificationFailed);
+                }
+            }
+            Err(e) => error = Some(e),
+        }
+
+        for file_index in stream_files {
+            entries.push(EntryTestResult {
+                file_index,
+                name: archive.files[file_index].name().to_string(),
+                crc: CrcStatus::NotChecked,
+            });
+        }
+        error
+    }
+}
+
+/// Computes the checksum of all data read through it.
+struct CrcReader<R> {
+    inner: R,
+    hasher: Hasher,
+    read: u64,
+}
+
+impl<R: Read> CrcReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Hasher::new(),
+            read: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.hasher.update(&buf[..size]);
+        self.read += size as u64;
+        Ok(size)
+    }
+}
+
+/// Reads `reader` to the end while computing the checksum of the data.
+fn copy_with_crc<R: Read>(reader: &mut R, hasher: &mut Hasher) -> std::io::Result<u64> {
+    let mut buf = [0; 8192];
+    let mut read = 0;
+    loop {
+        let size = match reader.read(&mut buf) {
+            Ok(0) => return Ok(read),
+            Ok(size) => size,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..size]);
+        read += size as u64;
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{Archive, Error};
+
+/// Limits for reading untrusted archives, used by [`Archive::read_with_options`] and
+/// [`crate::ArchiveReader::with_options`].
+///
+/// All limits are disabled by default. Each exceeded limit is reported by a dedicated
+/// [`Error`] variant before the memory for the limited data is allocated or the data is
+/// decoded.
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::*;
+///
+/// let options = ReaderOptions::default()
+///     .with_max_header_size(16 * 1024 * 1024)
+///     .with_max_entries(100_000)
+///     .with_max_total_unpack_size(10 * 1024 * 1024 * 1024)
+///     .with_max_compression_ratio(1000)
+///     .with_max_decoder_memory(256 * 1024 * 1024);
+/// let source = std::fs::File::open("upload.7z").unwrap();
+/// let reader = ArchiveReader::with_options(source, Password::empty(), options).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderOptions {
+    pub(crate) max_header_size: u64,
+    pub(crate) max_entries: usize,
+    pub(crate) max_blocks: usize,
+    pub(crate) max_coders: usize,
+    pub(crate) max_total_unpack_size: u64,
+    pub(crate) max_entry_size: u64,
+    pub(crate) max_memory_kb: usize,
+    pub(crate) max_compression_ratio: u64,
+    pub(crate) signature_search_limit: Option<u64>,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            max_header_size: u64::MAX,
+            max_entries: usize::MAX,
+            max_blocks: usize::MAX,
+            max_coders: usize::MAX,
+            max_total_unpack_size: u64::MAX,
+            max_entry_size: u64::MAX,
+            max_memory_kb: usize::MAX / 1024,
+            max_compression_ratio: u64::MAX,
+            signature_search_limit: None,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Sets the maximum size in bytes of the header, before and after decoding it.
+    pub fn with_max_header_size(mut self, max_header_size: u64) -> Self {
+        self.max_header_size = max_header_size;
+        self
+    }
+
+    /// Sets the maximum number of entries.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets the maximum number of blocks and of packed streams.
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+        self
+    }
+
+    /// Sets the maximum number of coders in a block.
+    pub fn with_max_coders(mut self, max_coders: usize) -> Self {
+        self.max_coders = max_coders;
+        self
+    }
+
+    /// Sets the maximum total size in bytes of the decoded data of all blocks.
+    pub fn with_max_total_unpack_size(mut self, max_total_unpack_size: u64) -> Self {
+        self.max_total_unpack_size = max_total_unpack_size;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a single entry.
+    pub fn with_max_entry_size(mut self, max_entry_size: u64) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// Sets the maximum memory in bytes a decoder may need, which is mostly determined by
+    /// the dictionary size of LZMA and LZMA2 and the memory size of PPMD.
+    pub fn with_max_decoder_memory(mut self, max_decoder_memory: usize) -> Self {
+        self.max_memory_kb = max_decoder_memory / 1024;
+        self
+    }
+
+    /// Sets the maximum ratio between the decoded size of a block and the size of its
+    /// packed streams, to detect decompression bombs.
+    pub fn with_max_compression_ratio(mut self, max_compression_ratio: u64) -> Self {
+        self.max_compression_ratio = max_compression_ratio;
+        self
+    }
+
+    /// Searches for the signature header within the first `search_limit` bytes of the
+    /// source, like [`Archive::read_with_signature_scan`].
+    pub fn with_signature_search_limit(mut self, search_limit: u64) -> Self {
+        self.signature_search_limit = Some(search_limit);
+        self
+    }
+
+    pub(crate) fn check_header_size(&self, size: u64) -> Result<(), Error> {
+        if size > self.max_header_size {
+            return Err(Error::HeaderTooLarge {
+                size,
+                max: self.max_header_size,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_entries(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_entries {
+            return Err(Error::TooManyEntries {
+                count,
+                max: self.max_entries,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_blocks(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_blocks {
+            return Err(Error::TooManyBlocks {
+                count,
+                max: self.max_blocks,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_coders(&self, count: usize) -> Result<(), Error> {
+        if count > self.max_coders {
+            return Err(Error::TooManyCoders {
+                count,
+                max: self.max_coders,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the sizes of the parsed archive against the limits.
+    pub(crate) fn check_sizes(&self, archive: &Archive) -> Result<(), Error> {
+        if let Some(file) = archive
+            .files
+            .iter()
+            .find(|file| file.size > self.max_entry_size)
+        {
+            return Err(Error::EntryTooLarge {
+                name: file.name().to_string(),
+                size: file.size,
+                max: self.max_entry_size,
+            });
+        }
+
+        let mut total_unpack_size = 0u64;
+        let mut next_pack_index = 0;
+        for (block_index, block) in archive.blocks.iter().enumerate() {
+            let unpack_size = block.get_unpack_size();
+            total_unpack_size = total_unpack_size.saturating_add(unpack_size);
+
+            let pack_count = block.packed_streams.len();
+            let pack_size: u64 = archive
+                .pack_sizes
+                .iter()
+                .skip(next_pack_index)
+                .take(pack_count)
+                .sum();
+            next_pack_index += pack_count;
+            let ratio = unpack_size / pack_size.max(1);
+            if ratio > self.max_compression_ratio {
+                return Err(Error::CompressionRatioTooHigh {
+                    block_index,
+                    ratio,
+                    max: self.max_compression_ratio,
+                });
+            }
+        }
+        if total_unpack_size > self.max_total_unpack_size {
+            return Err(Error::UnpackSizeTooLarge {
+                size: total_unpack_size,
+                max: self.max_total_unpack_size,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    io::{Read, Seek},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+use super::{ArchiveReader, BlockDecoder};
+use crate::{ArchiveEntry, Error, ReadAt, ReadAtReader};
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Takes a closure to decode each file in the archive, decoding multiple blocks in parallel.
+    ///
+    /// Each worker thread reads the archive through its own source returned by `open_source`,
+    /// for example a new [`std::fs::File`] handle of the archive. To share a single handle
+    /// between all threads, use [`ArchiveReader::par_for_each_entries_shared`] instead.
+    /// The blocks are scheduled
+    /// from the largest to the smallest, and the thread count set by
+    /// [`ArchiveReader::set_thread_count`] is split between the worker threads and the
+    /// internal threads of the decoders which support multi-threading (currently LZMA2).
+    ///
+    /// The entries of a block are passed to `each` in order, but the entries of different
+    /// blocks are passed concurrently and in no particular order. The entries without data
+    /// are passed last on the calling thread. If `each` returns `Ok(false)`, no further
+    /// entries are passed.
+    ///
+    /// A failed block doesn't stop the decoding of the other blocks. All errors are returned
+    /// together as [`Error::Blocks`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::{fs::File, path::Path};
+    ///
+    /// use sevenz_rust2::*;
+    ///
+    /// let path = "path/to/archive.7z";
+    /// let reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    /// let dest = Path::new("path/to/dest");
+    /// reader
+    ///     .par_for_each_entries(
+    ///         || Ok(File::open(path)?),
+    ///         |entry, data| {
+    ///             default_entry_extract_fn(entry, data, &dest.join(entry.name()))?;
+    ///             Ok(true)
+    ///         },
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn par_for_each_entries<S, O, F>(&self, open_source: O, each: F) -> Result<(), Error>
+    where
+        S: Read + Seek,
+        O: Fn() -> Result<S, Error> + Sync,
+        F: Fn(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error> + Sync,
+    {
+        let archive = &self.archive;
+        let password = &self.password;
+
+        let mut block_order: Vec<usize> = (0..archive.blocks.len()).collect();
+        block_order.sort_by_key(|&block_index| {
+            std::cmp::Reverse(archive.blocks[block_index].get_unpack_size())
+        });
+        let worker_count = (self.thread_count as usize).min(block_order.len());
+        let decoder_thread_count = (self.thread_count / worker_count.max(1) as u32).max(1);
+
+        let next = AtomicUsize::new(0);
+        let stopped = AtomicBool::new(false);
+        let errors = Mutex::new(Vec::new());
+        let each = |entry: &ArchiveEntry, data: &mut dyn Read| {
+            if stopped.load(Ordering::Relaxed) {
+                return Ok(false);
+            }
+            let proceed = each(entry, data)?;
+            if !proceed {
+                stopped.store(true, Ordering::Relaxed);
+            }
+            Ok(proceed)
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 0..worker_count {
+                let mut each = &each;
+                let (open_source, next, stopped, errors) = (&open_source, &next, &stopped, &errors);
+                let block_order = &block_order;
+                scope.spawn(move || {
+                    let mut source = None;
+                    while !stopped.load(Ordering::Relaxed) {
+                        let Some(&block_index) =
+                            block_order.get(next.fetch_add(1, Ordering::Relaxed))
+                        else {
+                            break;
+                        };
+                        let result = match source.as_mut() {
+                            Some(source) => Ok(source),
+                            None => open_source().map(|s| source.insert(s)),
+                        }
+                        .and_then(|source| {
+                            BlockDecoder::new(
+                                decoder_thread_count,
+                                block_index,
+                                archive,
+                                password,
+                                source,
+                            )
+                            .for_each_entries(&mut each)
+                        });
+                        if let Err(e) = result {
+                            errors.lock().unwrap().push((Some(block_index), e));
+                        }
+                    }
+                });
+            }
+        });
+
+        if !stopped.load(Ordering::Relaxed) {
+            for (file_index, file) in archive.files.iter().enumerate() {
+                if archive.stream_map.file_block_index[file_index].is_none() {
+                    let empty_reader: &mut dyn Read = &mut ([0u8; 0].as_slice());
+                    if let Err(e) = each(file, empty_reader) {
+                        errors.lock().unwrap().push((None, e));
+                        break;
+                    }
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut errors = errors.into_inner().unwrap();
+        if !errors.is_empty() {
+            errors.sort_by_key(|(block_index, _)| block_index.unwrap_or(usize::MAX));
+            return Err(Error::Blocks(errors));
+        }
+        Ok(())
+    }
+}
+
+impl<T: ReadAt + Sync> ArchiveReader<ReadAtReader<T>> {
+    /// Like [`ArchiveReader::par_for_each_entries`], but all worker threads read the archive
+    /// concurrently through the [`ReadAt`] source of this reader, so it isn't opened again.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::File;
+    ///
+    /// use sevenz_rust2::*;
+    ///
+    /// let file = File::open("path/to/archive.7z").unwrap();
+    /// let reader = ArchiveReader::new(ReadAtReader::new(file), Password::empty()).unwrap();
+    /// reader
+    ///     .par_for_each_entries_shared(|entry, data| {
+    ///         let size = std::io::copy(data, &mut std::io::sink())?;
+    ///         println!("{}: {size} bytes", entry.name());
+    ///         Ok(true)
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn par_for_each_entries_shared<F>(&self, each: F) -> Result<(), Error>
+    where
+        F: Fn(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error> + Sync,
+    {
+        let source = self.source.get_ref();
+        self.par_for_each_entries(|| Ok(ReadAtReader::new(source)), each)
+    }
+}
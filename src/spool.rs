@@ -0,0 +1,83 @@
+use std::{
+    fs::File,
+    io,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+};
+
+/// Default amount of data [`crate::ArchiveReader::from_stream`] keeps in memory before
+/// spooling to a temporary file.
+pub const DEFAULT_SPOOL_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A `Read + Seek` copy of a non-seekable source like a pipe or stdin.
+///
+/// 7z archives store their header at the end, so they can't be read from a non-seekable
+/// source directly. The source is read completely when the reader is created: up to a
+/// memory limit into memory, and otherwise into an anonymous temporary file, which only
+/// the current user can access and which is deleted when the reader is dropped.
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::*;
+///
+/// let source = SpooledReader::new(std::io::stdin(), 16 * 1024 * 1024).unwrap();
+/// let mut reader = ArchiveReader::new(source, Password::empty()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SpooledReader {
+    inner: Spool,
+}
+
+#[derive(Debug)]
+enum Spool {
+    Memory(Cursor<Vec<u8>>),
+    File(File),
+}
+
+impl SpooledReader {
+    /// Reads `source` to its end, keeping the data in memory if it's at most `max_memory`
+    /// bytes long and in a temporary file otherwise.
+    pub fn new<R: Read>(mut source: R, max_memory: usize) -> io::Result<Self> {
+        let mut data = Vec::new();
+        (&mut source)
+            .take(max_memory as u64 + 1)
+            .read_to_end(&mut data)?;
+        if data.len() <= max_memory {
+            return Ok(Self {
+                inner: Spool::Memory(Cursor::new(data)),
+            });
+        }
+
+        // The file is deleted by the OS once it's closed, also if spooling fails.
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&data)?;
+        drop(data);
+        io::copy(&mut source, &mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self {
+            inner: Spool::File(file),
+        })
+    }
+
+    /// Returns `true` if the data is kept in memory instead of a temporary file.
+    pub fn is_in_memory(&self) -> bool {
+        matches!(self.inner, Spool::Memory(_))
+    }
+}
+
+impl Read for SpooledReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Spool::Memory(cursor) => cursor.read(buf),
+            Spool::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for SpooledReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.inner {
+            Spool::Memory(cursor) => cursor.seek(pos),
+            Spool::File(file) => file.seek(pos),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+use std::{ffi::OsString, io::Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+use std::{
+    io,
+    io::{Read, Seek, SeekFrom},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::Error;
+
+/// A reader that concatenates the volumes of a split archive (`.7z.001`, `.7z.002`, ...)
+/// behind a single `Read + Seek` source.
+///
+/// The volumes are treated as one contiguous byte stream in the order they were given, so
+/// it can be used everywhere a single archive source is accepted, for example with
+/// [`crate::Archive::read`], [`crate::ArchiveReader::new`] or [`crate::BlockDecoder`].
+pub struct MultiVolumeReader<R: Read + Seek> {
+    volumes: Vec<R>,
+    /// Absolute start offset of each volume.
+    offsets: Vec<u64>,
+    total_len: u64,
+    pos: u64,
+    /// Index of the volume whose position is in sync with `pos`.
+    synced_volume: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MultiVolumeReader<File> {
+    /// Opens all volumes of a split archive, starting with the volume at `first_path`.
+    ///
+    /// The following volumes are discovered automatically by incrementing the numeric file
+    /// extension of `first_path` (`archive.7z.001`, `archive.7z.002`, ...) until a volume
+    /// does not exist. If `first_path` has no numeric extension, it is opened as the only volume.
+    pub fn open(first_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut volumes = Vec::new();
+        for path in volume_paths(first_path.as_ref()) {
+            let file = File::open(&path)
+                .map_err(|e| Error::file_open(e, path.to_string_lossy().to_string()))?;
+            volumes.push(file);
+        }
+        Ok(Self::new(volumes)?)
+    }
+}
+
+impl<R: Read + Seek> MultiVolumeReader<R> {
+    /// Creates a new [`MultiVolumeReader`] from the given volumes in their archive order.
+    pub fn new(mut volumes: Vec<R>) -> io::Result<Self> {
+        let mut offsets = Vec::with_capacity(volumes.len());
+        let mut total_len = 0;
+        for volume in volumes.iter_mut() {
+            offsets.push(total_len);
+            total_len += volume.seek(SeekFrom::End(0))?;
+        }
+        Ok(Self {
+            volumes,
+            offsets,
+            total_len,
+            pos: 0,
+            synced_volume: None,
+        })
+    }
+
+    /// Returns the number of volumes.
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+
+    /// Returns the combined size of all volumes.
+    pub fn total_len(&self) -> u64 {
+        self.total_len
+    }
+
+    /// Consumes the reader and returns the underlying volumes.
+    pub fn into_inner(self) -> Vec<R> {
+        self.volumes
+    }
+
+    fn volume_index(&self, pos: u64) -> usize {
+        // The position is always smaller than the total length here, so there is always
+        // a volume that contains it. Empty volumes are skipped by `partition_point`.
+        self.offsets.partition_point(|&offset| offset <= pos) - 1
+    }
+}
+
+impl<R: Read + Seek> Read for MultiVolumeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.total_len {
+            return Ok(0);
+        }
+        let index = self.volume_index(self.pos);
+        let volume_start = self.offsets[index];
+        let volume_end = self
+            .offsets
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.total_len);
+
+        let volume = &mut self.volumes[index];
+        if self.synced_volume != Some(index) {
+            volume.seek(SeekFrom::Start(self.pos - volume_start))?;
+            self.synced_volume = Some(index);
+        }
+
+        let bound = buf.len().min((volume_end - self.pos) as usize);
+        let size = volume.read(&mut buf[..bound])?;
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "volume is shorter than expected",
+            ));
+        }
+        self.pos += size as u64;
+        if self.pos >= volume_end {
+            self.synced_volume = None;
+        }
+        Ok(size)
+    }
+}
+
+impl<R: Read + Seek> Seek for MultiVolumeReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::End(pos) => self.total_len as i64 + pos,
+            SeekFrom::Current(pos) => self.pos as i64 + pos,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::other("SeekBeforeStart"));
+        }
+        if new_pos as u64 != self.pos {
+            self.pos = new_pos as u64;
+            self.synced_volume = None;
+        }
+        Ok(self.pos)
+    }
+}
+
+/// Returns the paths of all existing volumes that belong to the volume at `first_path`.
+#[cfg(not(target_arch = "wasm32"))]
+fn volume_paths(first_path: &Path) -> Vec<PathBuf> {
+    let number = first_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| !ext.is_empty() && ext.bytes().all(|b| b.is_ascii_digit()));
+
+    let Some(number) = number else {
+        return vec![first_path.to_path_buf()];
+    };
+
+    let width = number.len();
+    let mut next = number.parse::<u64>().unwrap_or(1);
+    let mut paths = vec![first_path.to_path_buf()];
+    loop {
+        next += 1;
+        let path = first_path.with_extension(format!("{next:0width$}"));
+        if !path.is_file() {
+            break;
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+/// A writer that splits its output into volumes (`.7z.001`, `.7z.002`, ...) of a fixed size,
+/// which 7-Zip and [`MultiVolumeReader`] can reassemble.
+///
+/// The writer can seek freely across all volumes, so the start header of an archive can
+/// still be patched into the first volume once all data has been written.
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+pub struct MultiVolumeWriter {
+    base_path: PathBuf,
+    volume_size: u64,
+    volumes: Vec<File>,
+    pos: u64,
+    len: u64,
+    /// Index of the volume whose position is in sync with `pos`.
+    synced_volume: Option<usize>,
+}
+
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+impl MultiVolumeWriter {
+    /// Creates a new [`MultiVolumeWriter`] which writes volumes of `volume_size` bytes.
+    ///
+    /// The volume file names are derived from `base_path` by appending the volume number,
+    /// for example `archive.7z` will create `archive.7z.001`, `archive.7z.002` and so on.
+    /// Only the last volume can be smaller than `volume_size`.
+    pub fn create(base_path: impl AsRef<Path>, volume_size: u64) -> Result<Self, Error> {
+        if volume_size == 0 {
+            return Err(Error::other("Volume size must not be zero"));
+        }
+        let mut writer = Self {
+            base_path: base_path.as_ref().to_path_buf(),
+            volume_size,
+            volumes: Vec::new(),
+            pos: 0,
+            len: 0,
+            synced_volume: None,
+        };
+        writer.ensure_volume(0)?;
+        Ok(writer)
+    }
+
+    /// Returns the number of volumes created so far.
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+
+    /// Returns the path of the volume with the given zero based `index`.
+    pub fn volume_path(&self, index: usize) -> PathBuf {
+        let mut name = OsString::from(self.base_path.as_os_str());
+        name.push(format!(".{:03}", index + 1));
+        PathBuf::from(name)
+    }
+
+    fn ensure_volume(&mut self, index: usize) -> Result<(), Error> {
+        while self.volumes.len() <= index {
+            let path = self.volume_path(self.volumes.len());
+            let file = File::create(&path)
+                .map_err(|e| Error::file_open(e, path.to_string_lossy().to_string()))?;
+            self.volumes.push(file);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+impl Write for MultiVolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let index = (self.pos / self.volume_size) as usize;
+        let offset = self.pos % self.volume_size;
+        self.ensure_volume(index).map_err(io::Error::other)?;
+
+        let volume = &mut self.volumes[index];
+        if self.synced_volume != Some(index) {
+            volume.seek(SeekFrom::Start(offset))?;
+            self.synced_volume = Some(index);
+        }
+
+        let bound = buf.len().min((self.volume_size - offset) as usize);
+        let size = volume.write(&buf[..bound])?;
+        self.pos += size as u64;
+        self.len = self.len.max(self.pos);
+        if self.pos % self.volume_size == 0 {
+            self.synced_volume = None;
+        }
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for volume in self.volumes.iter_mut() {
+            volume.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "compress", not(target_arch = "wasm32")))]
+impl Seek for MultiVolumeWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => pos as i64,
+            SeekFrom::End(pos) => self.len as i64 + pos,
+            SeekFrom::Current(pos) => self.pos as i64 + pos,
+        };
+        if new_pos < 0 {
+            return Err(io::Error::other("SeekBeforeStart"));
+        }
+        if new_pos as u64 != self.pos {
+            self.pos = new_pos as u64;
+            self.synced_volume = None;
+        }
+        Ok(self.pos)
+    }
+}
@@ -7,27 +7,46 @@ mod source_reader;
 mod unpack_info;
 
 use std::{
-    cell::Cell,
-    io::{Read, Seek, Write},
+    cell::{Cell, RefCell},
+    io::{Cursor, Read, Seek, Write},
+    ops::{Deref, DerefMut},
     rc::Rc,
     sync::Arc,
 };
 #[cfg(not(target_arch = "wasm32"))]
-use std::{fs::File, path::Path};
+use std::{
+    fs::{File, OpenOptions},
+    path::Path,
+};
 
 pub(crate) use counting_writer::CountingWriter;
 use crc32fast::Hasher;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::MultiVolumeWriter;
+
 #[cfg(all(feature = "util", not(target_arch = "wasm32")))]
 pub(crate) use self::lazy_file_reader::LazyFileReader;
 pub(crate) use self::seq_reader::SeqReader;
 pub use self::source_reader::SourceReader;
 use self::{pack_info::PackInfo, unpack_info::UnpackInfo};
 use crate::{
-    ArchiveEntry, AutoFinish, AutoFinisher, ByteWriter, Error,
+    ArchiveEntry, ArchiveProperty, AutoFinish, AutoFinisher, Block, ByteWriter, Coder, Error,
+    Password,
     archive::*,
     bitset::{BitSet, write_bit_set},
+    block::BindPair,
+    codec::{
+        bcj2::{Bcj2Streams, Bcj2Writer},
+        registry::{Codec, CodecRegistry},
+    },
     encoder,
+    encoder_options::{EncoderOptions, LzmaOptions},
+};
+#[cfg(feature = "aes256")]
+use crate::{
+    encoder_options::AesEncoderOptions,
+    encryption::{Aes256Sha256Decoder, Aes256Sha256Encoder},
 };
 
 macro_rules! write_times {
@@ -76,6 +95,10 @@ macro_rules! write_times {
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// The streams of a BCJ2 block besides the main stream, which are set once the block
+/// writer is finished.
+type SharedBcj2Streams = Rc<RefCell<Bcj2Streams>>;
+
 /// Writes a 7z archive file.
 pub struct ArchiveWriter<W: Write> {
     output: W,
@@ -84,6 +107,80 @@ pub struct ArchiveWriter<W: Write> {
     pack_info: PackInfo,
     unpack_info: UnpackInfo,
     encrypt_header: bool,
+    comment: Option<String>,
+    /// Archive properties of an existing archive, which are written back unchanged.
+    archive_properties: Vec<ArchiveProperty>,
+    /// Position of the signature header in the output.
+    base_offset: u64,
+    codecs: CodecRegistry,
+    /// AES options to encrypt the header with, instead of the AES options of the content methods.
+    #[cfg(feature = "aes256")]
+    header_encryption: Option<AesEncoderOptions>,
+}
+
+/// Appends entries to an existing 7z archive, see [`ArchiveWriter::open_for_append`].
+///
+/// New packed streams are written behind the old header, so the existing archive stays
+/// readable until the appender is finished. Only then they are moved to the end of the
+/// existing packed streams. The entries are added with the [`ArchiveWriter`] the appender
+/// dereferences to.
+pub struct ArchiveAppender<W: Write> {
+    writer: ArchiveWriter<W>,
+    /// Absolute position of the end of the existing packed streams.
+    pack_end: u64,
+    /// Absolute position of the end of the old header, where the new packed streams start.
+    header_end: u64,
+    /// The unencoded header of the existing archive.
+    header: Vec<u8>,
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A writer whose length can be changed, like a [`File`].
+///
+/// It's needed to update an archive in place, to remove the data left behind the new header.
+/// This trait is sealed and implemented for [`File`], `Vec<u8>`, [`Cursor`] and `&mut T`.
+pub trait SetLen: private::Sealed {
+    /// Truncates or extends the underlying data to `len` bytes.
+    fn set_len(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl private::Sealed for File {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SetLen for File {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        File::set_len(self, len)
+    }
+}
+
+impl private::Sealed for Vec<u8> {}
+
+impl SetLen for Vec<u8> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        let len = usize::try_from(len).map_err(std::io::Error::other)?;
+        self.resize(len, 0);
+        Ok(())
+    }
+}
+
+impl<T: SetLen + ?Sized> private::Sealed for &mut T {}
+
+impl<T: SetLen + ?Sized> SetLen for &mut T {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        (**self).set_len(len)
+    }
+}
+
+impl<T: SetLen> private::Sealed for Cursor<T> {}
+
+impl<T: SetLen> SetLen for Cursor<T> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.get_mut().set_len(len)
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -94,12 +191,65 @@ impl ArchiveWriter<File> {
             .map_err(|e| Error::file_open(e, path.as_ref().to_string_lossy().to_string()))?;
         Self::new(file)
     }
+
+    /// Opens an existing 7z archive at `path` to append new entries to it.
+    ///
+    /// The packed streams of the existing entries are kept in place and are not recompressed.
+    /// New entries are written behind the old header, which stays untouched until
+    /// [`ArchiveWriter::finish`] moves them to the end of the existing packed streams and
+    /// writes the new header. The `password` is only needed if the header of the existing
+    /// archive is encrypted, in which case the new header is encrypted with it as well.
+    ///
+    /// The archive stays readable if the writer is not finished, it then only contains
+    /// the existing entries.
+    pub fn open_for_append(
+        path: impl AsRef<Path>,
+        password: &Password,
+    ) -> Result<ArchiveAppender<File>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+            .map_err(|e| Error::file_open(e, path.as_ref().to_string_lossy().to_string()))?;
+        ArchiveAppender::new(file, password)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArchiveWriter<MultiVolumeWriter> {
+    /// Creates a multi-volume 7z archive, which is split into volumes of `volume_size` bytes.
+    ///
+    /// The volumes are named after `base_path` with the volume number appended,
+    /// for example `archive.7z.001`, `archive.7z.002` and so on.
+    pub fn create_multi_volume(base_path: impl AsRef<Path>, volume_size: u64) -> Result<Self> {
+        let writer = MultiVolumeWriter::create(base_path, volume_size)?;
+        Self::new(writer)
+    }
 }
 
 impl<W: Write + Seek> ArchiveWriter<W> {
     /// Prepares writer to write a 7z archive to.
-    pub fn new(mut writer: W) -> Result<Self> {
-        writer.seek(std::io::SeekFrom::Start(SIGNATURE_HEADER_SIZE))?;
+    pub fn new(writer: W) -> Result<Self> {
+        Self::with_base_offset(writer, 0)
+    }
+
+    /// Prepares writer to write a 7z archive after the data of `prefix`.
+    ///
+    /// The prefix, for example a self-extracting stub, is copied to the start of `writer`
+    /// and the archive is written directly after it. All offsets in the archive headers
+    /// are relative to the start of the archive, so the result can be read with
+    /// [`Archive::read_with_signature_scan`](crate::Archive::read_with_signature_scan).
+    pub fn with_prefix<P: Read>(mut writer: W, mut prefix: P) -> Result<Self> {
+        writer.seek(std::io::SeekFrom::Start(0))?;
+        let base_offset = std::io::copy(&mut prefix, &mut writer)
+            .map_err(|e| Error::io_msg(e, "Write archive prefix"))?;
+        Self::with_base_offset(writer, base_offset)
+    }
+
+    fn with_base_offset(mut writer: W, base_offset: u64) -> Result<Self> {
+        writer.seek(std::io::SeekFrom::Start(
+            base_offset + SIGNATURE_HEADER_SIZE,
+        ))?;
 
         Ok(Self {
             output: writer,
@@ -108,9 +258,215 @@ impl<W: Write + Seek> ArchiveWriter<W> {
             pack_info: Default::default(),
             unpack_info: Default::default(),
             encrypt_header: true,
+            comment: None,
+            archive_properties: Vec::new(),
+            base_offset,
+            codecs: CodecRegistry::default(),
+            #[cfg(feature = "aes256")]
+            header_encryption: None,
         })
     }
 
+    /// Prepares writer to add entries to the existing `archive` stored in `writer`.
+    ///
+    /// The entries and blocks of `archive` are kept as they are and new packed streams
+    /// are written directly after the last existing packed stream.
+    pub(crate) fn from_archive(writer: W, archive: &Archive) -> Result<Self> {
+        let mut this = Self::with_base_offset(writer, archive.base_offset())?;
+
+        this.pack_info.pos = archive.pack_pos;
+        for (i, size) in archive.pack_sizes.iter().copied().enumerate() {
+            let crc = if archive.pack_crcs_defined.contains(i) {
+                archive.pack_crcs[i] as u32
+            } else {
+                0
+            };
+            this.pack_info.add_stream(size, crc);
+        }
+
+        let mut sub_stream_index = 0;
+        for block in archive.blocks.iter() {
+            let (sub_stream_sizes, sub_stream_crcs) = match archive.sub_streams_info.as_ref() {
+                Some(info) => {
+                    let range = sub_stream_index..sub_stream_index + block.num_unpack_sub_streams;
+                    sub_stream_index = range.end;
+                    let crcs = range
+                        .clone()
+                        .map(|i| info.has_crc.contains(i).then_some(info.crcs[i] as u32))
+                        .collect();
+                    (info.unpack_sizes[range].to_vec(), crcs)
+                }
+                None => (
+                    vec![block.get_unpack_size()],
+                    vec![block.has_crc.then_some(block.crc as u32)],
+                ),
+            };
+            this.unpack_info
+                .add_existing(block.clone(), sub_stream_sizes, sub_stream_crcs);
+        }
+
+        this.files = archive.files.clone();
+        this.comment = archive.comment.clone();
+        this.archive_properties = archive.archive_properties.clone();
+
+        let pack_end = archive.packed_streams_start() + archive.pack_sizes.iter().sum::<u64>();
+        this.output.seek(std::io::SeekFrom::Start(pack_end))?;
+        Ok(this)
+    }
+
+    /// Copies the packed streams of the block at `block_index` of `archive` verbatim from
+    /// `source` and adds the `entries` stored in it, without decoding or encoding any data.
+    pub(crate) fn push_existing_block<S: Read + Seek>(
+        &mut self,
+        source: &mut S,
+        archive: &Archive,
+        block_index: usize,
+        entries: Vec<ArchiveEntry>,
+    ) -> Result<()> {
+        let block = &archive.blocks[block_index];
+        let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
+        for pack_index in
+            first_pack_stream_index..first_pack_stream_index + block.packed_streams.len()
+        {
+            self.copy_pack_stream(source, archive, pack_index)?;
+        }
+        self.add_existing_block(block.clone(), entries);
+        Ok(())
+    }
+
+    /// Copies the block at `block_index` of `archive` from `source` like
+    /// [`ArchiveWriter::push_existing_block`], but re-encrypts the packed streams which are
+    /// encrypted with AES using `new_password`, without decompressing their data.
+    ///
+    /// If `new_password` is empty, the encryption is removed from the block instead.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn push_rekeyed_block<S: Read + Seek>(
+        &mut self,
+        source: &mut S,
+        archive: &Archive,
+        block_index: usize,
+        entries: Vec<ArchiveEntry>,
+        password: &Password,
+        new_password: &Password,
+    ) -> Result<()> {
+        let mut block = archive.blocks[block_index].clone();
+        let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
+        let mut removed_coders = Vec::new();
+        for (i, &in_index) in archive.blocks[block_index]
+            .packed_streams
+            .iter()
+            .enumerate()
+        {
+            let pack_index = first_pack_stream_index + i;
+            let coder_index = block
+                .coder_index_for_in_stream(in_index)
+                .ok_or_else(|| Error::other(format!("No coder for packed stream {in_index}")))?;
+            let coder = &block.coders[coder_index];
+            if coder.encoder_method_id() != EncoderMethod::ID_AES256_SHA256 {
+                self.copy_pack_stream(source, archive, pack_index)?;
+                continue;
+            }
+
+            let offset =
+                archive.packed_streams_start() + archive.stream_map.pack_stream_offsets[pack_index];
+            source.seek(std::io::SeekFrom::Start(offset))?;
+            let unpack_size =
+                block.unpack_sizes[block.first_out_stream_index(coder_index) as usize];
+            let decoder = Aes256Sha256Decoder::new(
+                source.take(archive.pack_sizes[pack_index]),
+                &coder.properties,
+                password,
+            )?;
+            let mut decoder = decoder.take(unpack_size);
+
+            let mut compressed_len = 0;
+            let mut compressed = CompressWrapWriter::new(&mut self.output, &mut compressed_len);
+            let copied = if new_password.is_empty() {
+                removed_coders.push(coder_index);
+                std::io::copy(&mut decoder, &mut compressed)
+            } else {
+                let options = AesEncoderOptions::new(new_password.clone());
+                let mut encoder = Aes256Sha256Encoder::new(&mut compressed, &options)?;
+                block.coders[coder_index].properties = options.properties().to_vec();
+                // Writing an empty buffer finishes the encoder.
+                std::io::copy(&mut decoder, &mut encoder)
+                    .and_then(|copied| encoder.write(&[]).map(|_| copied))
+            }
+            .map_err(|e| Error::io_msg(e, "Re-encrypt packed stream"))?;
+            if copied != unpack_size {
+                return Err(Error::io_msg(
+                    std::io::ErrorKind::UnexpectedEof.into(),
+                    "Re-encrypt packed stream",
+                ));
+            }
+            let compressed_crc = compressed.crc_value();
+            self.pack_info
+                .add_stream(compressed_len as u64, compressed_crc);
+        }
+
+        // Removing a coder only changes the stream indices of the following coders.
+        for coder_index in removed_coders.into_iter().rev() {
+            block.remove_coder(coder_index);
+        }
+        self.add_existing_block(block, entries);
+        Ok(())
+    }
+
+    /// Copies the packed stream at `pack_index` of `archive` verbatim from `source`.
+    fn copy_pack_stream<S: Read + Seek>(
+        &mut self,
+        source: &mut S,
+        archive: &Archive,
+        pack_index: usize,
+    ) -> Result<()> {
+        let offset =
+            archive.packed_streams_start() + archive.stream_map.pack_stream_offsets[pack_index];
+        let size = archive.pack_sizes[pack_index];
+        source.seek(std::io::SeekFrom::Start(offset))?;
+        let copied = std::io::copy(&mut source.take(size), &mut self.output)
+            .map_err(|e| Error::io_msg(e, "Copy packed stream"))?;
+        if copied != size {
+            return Err(Error::io_msg(
+                std::io::ErrorKind::UnexpectedEof.into(),
+                "Copy packed stream",
+            ));
+        }
+        let crc = if archive.pack_crcs_defined.contains(pack_index) {
+            archive.pack_crcs[pack_index] as u32
+        } else {
+            0
+        };
+        self.pack_info.add_stream(size, crc);
+        Ok(())
+    }
+
+    /// Adds an existing `block` whose packed streams were already written, together with
+    /// the `entries` stored in it.
+    fn add_existing_block(&mut self, block: Block, entries: Vec<ArchiveEntry>) {
+        let sub_stream_sizes = entries.iter().map(|file| file.size).collect();
+        let sub_stream_crcs = entries
+            .iter()
+            .map(|file| file.has_crc.then_some(file.crc as u32))
+            .collect();
+        self.unpack_info
+            .add_existing(block, sub_stream_sizes, sub_stream_crcs);
+        self.files.extend(entries);
+    }
+
+    /// Returns the number of entries added so far.
+    pub(crate) fn entry_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Inserts an `entry` without data at `index` of the entries added so far.
+    pub(crate) fn insert_empty_entry(&mut self, index: usize, mut entry: ArchiveEntry) {
+        entry.has_stream = false;
+        entry.size = 0;
+        entry.compressed_size = 0;
+        entry.has_crc = false;
+        self.files.insert(index, entry);
+    }
+
     /// Returns a wrapper around `self` that will finish the stream on drop.
     pub fn auto_finish(self) -> AutoFinisher<Self> {
         AutoFinisher(Some(self))
@@ -125,11 +481,39 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         self
     }
 
+    /// Registers a custom `codec` to encode the content methods with its method id, which this
+    /// crate doesn't support itself. It replaces a codec registered before for the same
+    /// method id.
+    pub fn register_codec(&mut self, codec: impl Codec + 'static) -> &mut Self {
+        self.codecs.register(Arc::new(codec));
+        self
+    }
+
     /// Whether to enable the encryption of the -header. Default is `true`.
     pub fn set_encrypt_header(&mut self, enabled: bool) {
         self.encrypt_header = enabled;
     }
 
+    /// Sets the AES options to encrypt the header with, which take precedence over the AES
+    /// options of the content methods.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn set_header_encryption(&mut self, options: Option<AesEncoderOptions>) {
+        self.header_encryption = options;
+    }
+
+    /// Whether the content methods encrypt the entry data.
+    #[cfg(feature = "aes256")]
+    pub(crate) fn encrypts_content(&self) -> bool {
+        self.content_methods
+            .iter()
+            .any(|conf| conf.method.id() == EncoderMethod::AES256_SHA256.id())
+    }
+
+    /// Sets the comment of the archive, which is stored in the header.
+    pub fn set_comment(&mut self, comment: &str) {
+        self.comment = Some(comment.to_string());
+    }
+
     /// Non-solid compression - Adds an archive `entry` with data from `reader`.
     ///
     /// # Example
@@ -162,9 +546,10 @@ impl<W: Write + Seek> ArchiveWriter<W> {
                 let mut more_sizes: Vec<Rc<Cell<usize>>> =
                     Vec::with_capacity(self.content_methods.len() - 1);
 
-                let (crc, size) = {
-                    let mut w = Self::create_writer(
+                let (crc, size, bcj2_streams) = {
+                    let (mut w, bcj2_streams) = Self::create_block_writer(
                         &self.content_methods,
+                        &self.codecs,
                         &mut compressed,
                         &mut more_sizes,
                     )?;
@@ -194,7 +579,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
                     w.write(&[])
                         .map_err(|e| Error::io_msg(e, format!("Encode entry:{}", entry.name())))?;
 
-                    (w.crc_value(), write_len)
+                    (w.crc_value(), write_len, bcj2_streams)
                 };
                 let compressed_crc = compressed.crc_value();
                 entry.has_stream = true;
@@ -210,10 +595,15 @@ impl<W: Write + Seek> ArchiveWriter<W> {
                 sizes.extend(more_sizes.iter().map(|s| s.get() as u64));
                 sizes.push(size as u64);
 
-                self.unpack_info
-                    .add(self.content_methods.clone(), sizes, crc);
-
-                self.files.push(entry);
+                if let Some(streams) = bcj2_streams {
+                    let methods = Arc::clone(&self.content_methods);
+                    let block = self.write_bcj2_streams(&methods, &sizes, streams.take())?;
+                    self.add_existing_block(block, vec![entry]);
+                } else {
+                    self.unpack_info
+                        .add(self.content_methods.clone(), sizes, crc);
+                    self.files.push(entry);
+                }
                 return Ok(self.files.last().unwrap());
             }
         }
@@ -242,8 +632,13 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         let content_methods = &self.content_methods;
         let mut more_sizes: Vec<Rc<Cell<usize>>> = Vec::with_capacity(content_methods.len() - 1);
 
-        let (crc, size) = {
-            let mut w = Self::create_writer(content_methods, &mut compressed, &mut more_sizes)?;
+        let (crc, size, bcj2_streams) = {
+            let (mut w, bcj2_streams) = Self::create_block_writer(
+                content_methods,
+                &self.codecs,
+                &mut compressed,
+                &mut more_sizes,
+            )?;
             let mut write_len = 0;
             let mut w = CompressWrapWriter::new(&mut w, &mut write_len);
             let mut buf = [0u8; 4096];
@@ -293,7 +688,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
                 Error::io_msg(e, format!("Encode entry:{}", entries_names(&entries)))
             })?;
 
-            (w.crc_value(), write_len)
+            (w.crc_value(), write_len, bcj2_streams)
         };
         let compressed_crc = compressed.crc_value();
         let mut sub_stream_crcs = Vec::with_capacity(entries.len());
@@ -315,6 +710,13 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         sizes.extend(more_sizes.iter().map(|s| s.get() as u64));
         sizes.push(size as u64);
 
+        if let Some(streams) = bcj2_streams {
+            let methods = Arc::clone(&self.content_methods);
+            let block = self.write_bcj2_streams(&methods, &sizes, streams.take())?;
+            self.add_existing_block(block, entries);
+            return Ok(self);
+        }
+
         self.unpack_info.add_multiple(
             content_methods.clone(),
             sizes,
@@ -328,8 +730,184 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         Ok(self)
     }
 
+    /// Creates the writer for the data of a block like [`ArchiveWriter::create_writer`].
+    ///
+    /// If the last method is BCJ2, the main stream of the filter is encoded with the other
+    /// methods and the returned [`Bcj2Streams`] receive its other streams once the writer is
+    /// finished, which need to be written with [`ArchiveWriter::write_bcj2_streams`].
+    fn create_block_writer<'a, O: Write + 'a>(
+        methods: &[EncoderConfiguration],
+        codecs: &CodecRegistry,
+        out: O,
+        more_sized: &mut Vec<Rc<Cell<usize>>>,
+    ) -> Result<(Box<dyn Write + 'a>, Option<SharedBcj2Streams>)> {
+        match methods.split_last() {
+            Some((last, main_methods)) if last.method.id() == EncoderMethod::ID_BCJ2 => {
+                let main = Self::create_writer(main_methods, codecs, out, more_sized)?;
+                let counting = CountingWriter::new(main);
+                more_sized.push(counting.counting());
+                let encoder = Bcj2Writer::new(counting);
+                let streams = encoder.streams();
+                Ok((Box::new(encoder), Some(streams)))
+            }
+            _ => Ok((Self::create_writer(methods, codecs, out, more_sized)?, None)),
+        }
+    }
+
+    /// Writes the call, jump and range coder streams of a BCJ2 block after its main stream
+    /// and returns the coder graph of the block.
+    ///
+    /// The block has the layout 7-Zip uses: the BCJ2 coder has four input streams, the main
+    /// stream is encoded with the other `methods`, the call and jump streams with LZMA and
+    /// the range coder stream is stored. The coders of the main stream come first, so the
+    /// coders start like `methods`. If the main stream is encrypted, the other streams
+    /// are encrypted as well. `sizes` are the unpack sizes of the coders of the main stream,
+    /// followed by the size of the main stream and of the block.
+    fn write_bcj2_streams(
+        &mut self,
+        methods: &[EncoderConfiguration],
+        sizes: &[u64],
+        streams: Bcj2Streams,
+    ) -> Result<Block> {
+        let main_methods = &methods[..methods.len() - 1];
+        let bcj2_in_index = main_methods.len() as u64;
+        let mut block = Block::default();
+        Self::add_coder_chain(&mut block, main_methods, sizes, bcj2_in_index);
+        block
+            .coders
+            .push(Coder::new(EncoderMethod::ID_BCJ2, 4, Vec::new()));
+        block.unpack_sizes.push(sizes[sizes.len() - 1]);
+        block.total_input_streams += 4;
+        block.total_output_streams += 1;
+
+        // Each stream is encrypted with its own IV.
+        let call_methods = Self::bcj2_branch_stream_methods(main_methods);
+        let jump_methods = Self::bcj2_branch_stream_methods(main_methods);
+        let rc_methods: Vec<_> = Self::encryption_with_new_iv(main_methods)
+            .into_iter()
+            .collect();
+
+        for (in_index, (methods, data)) in [
+            (call_methods, streams.call),
+            (jump_methods, streams.jump),
+            (rc_methods, streams.rc),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let sizes = self.write_packed_stream(&methods, &data)?;
+            Self::add_coder_chain(
+                &mut block,
+                &methods,
+                &sizes,
+                bcj2_in_index + 1 + in_index as u64,
+            );
+        }
+        Ok(block)
+    }
+
+    /// Encodes `data` with `methods` as the next packed stream and returns the unpack sizes
+    /// of the coders.
+    fn write_packed_stream(
+        &mut self,
+        methods: &[EncoderConfiguration],
+        data: &[u8],
+    ) -> Result<Vec<u64>> {
+        let mut compressed_len = 0;
+        let mut compressed = CompressWrapWriter::new(&mut self.output, &mut compressed_len);
+        let mut more_sizes = Vec::with_capacity(methods.len());
+        {
+            let mut w =
+                Self::create_writer(methods, &self.codecs, &mut compressed, &mut more_sizes)?;
+            w.write_all(data)
+                .and_then(|_| w.flush())
+                .and_then(|_| w.write(&[]))
+                .map_err(|e| Error::io_msg(e, "Encode BCJ2 stream"))?;
+        }
+        let compressed_crc = compressed.crc_value();
+        self.pack_info
+            .add_stream(compressed_len as u64, compressed_crc);
+
+        let mut sizes: Vec<u64> = more_sizes.iter().map(|s| s.get() as u64).collect();
+        sizes.push(data.len() as u64);
+        Ok(sizes)
+    }
+
+    /// Adds the linear chain of coders for `methods` with the unpack `sizes` to `block`,
+    /// which decodes the packed stream written next into the input stream `target_in_index`.
+    fn add_coder_chain(
+        block: &mut Block,
+        methods: &[EncoderConfiguration],
+        sizes: &[u64],
+        target_in_index: u64,
+    ) {
+        if methods.is_empty() {
+            block.packed_streams.push(target_in_index);
+            return;
+        }
+        block.packed_streams.push(block.total_input_streams as u64);
+        for (i, (method, size)) in methods.iter().zip(sizes).enumerate() {
+            let mut temp = [0u8; 256];
+            let properties = encoder::get_options_as_properties(
+                method.method,
+                method.options.as_ref(),
+                &mut temp,
+            );
+            let out_index = block.coders.len() as u64;
+            let in_index = block.total_input_streams as u64;
+            block
+                .coders
+                .push(Coder::new(method.method.id(), 1, properties.to_vec()));
+            block.unpack_sizes.push(*size);
+            block.total_input_streams += 1;
+            block.total_output_streams += 1;
+            // Each coder outputs into the next coder of the chain, the last one into the target.
+            let next_in_index = if i + 1 < methods.len() {
+                in_index + 1
+            } else {
+                target_in_index
+            };
+            block.bind_pairs.push(BindPair {
+                in_index: next_in_index,
+                out_index,
+            });
+        }
+    }
+
+    /// Returns the methods for the call or jump stream of a BCJ2 block: LZMA with the options
+    /// 7-Zip uses for these streams, encrypted like the main stream.
+    fn bcj2_branch_stream_methods(
+        main_methods: &[EncoderConfiguration],
+    ) -> Vec<EncoderConfiguration> {
+        let mut options = lzma_rust2::LzmaOptions::with_preset(6);
+        options.dict_size = 1 << 20;
+        options.lc = 0;
+        options.lp = 2;
+        let lzma = EncoderConfiguration::new(EncoderMethod::LZMA)
+            .with_options(EncoderOptions::Lzma(LzmaOptions(options)));
+        Self::encryption_with_new_iv(main_methods)
+            .into_iter()
+            .chain([lzma])
+            .collect()
+    }
+
+    /// Returns the encryption of `methods`, if any, with a new IV for another stream.
+    #[cfg(feature = "aes256")]
+    fn encryption_with_new_iv(methods: &[EncoderConfiguration]) -> Option<EncoderConfiguration> {
+        methods.iter().find_map(|method| match &method.options {
+            Some(EncoderOptions::Aes(options)) => Some(options.with_new_iv().into()),
+            _ => None,
+        })
+    }
+
+    #[cfg(not(feature = "aes256"))]
+    fn encryption_with_new_iv(_methods: &[EncoderConfiguration]) -> Option<EncoderConfiguration> {
+        None
+    }
+
     fn create_writer<'a, O: Write + 'a>(
         methods: &[EncoderConfiguration],
+        codecs: &CodecRegistry,
         out: O,
         more_sized: &mut Vec<Rc<Cell<usize>>>,
     ) -> Result<Box<dyn Write + 'a>> {
@@ -339,10 +917,10 @@ impl<W: Write + Seek> ArchiveWriter<W> {
             if !first {
                 let counting = CountingWriter::new(encoder);
                 more_sized.push(counting.counting());
-                encoder = Box::new(encoder::add_encoder(counting, mc)?);
+                encoder = Box::new(encoder::add_encoder(counting, mc, codecs)?);
             } else {
                 let counting = CountingWriter::new(encoder);
-                encoder = Box::new(encoder::add_encoder(counting, mc)?);
+                encoder = Box::new(encoder::add_encoder(counting, mc, codecs)?);
             }
             first = false;
         }
@@ -352,10 +930,22 @@ impl<W: Write + Seek> ArchiveWriter<W> {
     /// Finishes the compression.
     pub fn finish(mut self) -> std::io::Result<W> {
         let mut header: Vec<u8> = Vec::with_capacity(64 * 1024);
-        self.write_encoded_header(&mut header)?;
-        let header_pos = self.output.stream_position()?;
-        self.output.write_all(&header)?;
-        let crc32 = crc32fast::hash(&header);
+        self.write_header(&mut header)?;
+        let position = self.output.stream_position()?;
+        self.write_header_at(&header, position)?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    /// Writes the encoded `header` at `position` and the start header pointing to it.
+    ///
+    /// Returns the position of the end of the header.
+    fn write_header_at(&mut self, header: &[u8], position: u64) -> std::io::Result<u64> {
+        let (data, header_offset) = self.encode_header(header, position)?;
+        let header = &data[header_offset..];
+        self.output.seek(std::io::SeekFrom::Start(position))?;
+        self.output.write_all(&data)?;
+        let crc32 = crc32fast::hash(header);
         let mut hh = [0u8; SIGNATURE_HEADER_SIZE as usize];
         {
             let mut hhw = hh.as_mut_slice();
@@ -368,21 +958,23 @@ impl<W: Write + Seek> ArchiveWriter<W> {
             hhw.write_u32(0)?;
 
             // start header
-            hhw.write_u64(header_pos - SIGNATURE_HEADER_SIZE)?;
+            let header_pos = position + header_offset as u64;
+            hhw.write_u64(header_pos - self.base_offset - SIGNATURE_HEADER_SIZE)?;
             hhw.write_u64(0xFFFFFFFF & header.len() as u64)?;
             hhw.write_u32(crc32)?;
         }
         let crc32 = crc32fast::hash(&hh[12..]);
         hh[8..12].copy_from_slice(&crc32.to_le_bytes());
 
-        self.output.seek(std::io::SeekFrom::Start(0))?;
+        self.output
+            .seek(std::io::SeekFrom::Start(self.base_offset))?;
         self.output.write_all(&hh)?;
-        self.output.flush()?;
-        Ok(self.output)
+        Ok(position + data.len() as u64)
     }
 
     fn write_header<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
         header.write_u8(K_HEADER)?;
+        self.write_archive_properties(header)?;
         header.write_u8(K_MAIN_STREAMS_INFO)?;
         self.write_streams_info(header)?;
         self.write_files_info(header)?;
@@ -390,29 +982,51 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         Ok(())
     }
 
-    fn write_encoded_header<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
-        let mut raw_header = Vec::with_capacity(64 * 1024);
-        self.write_header(&mut raw_header)?;
+    fn write_archive_properties<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
+        if self.archive_properties.is_empty() {
+            return Ok(());
+        }
+        header.write_u8(K_ARCHIVE_PROPERTIES)?;
+        for property in self.archive_properties.iter() {
+            header.write_u8(property.property_type)?;
+            write_u64(header, property.data.len() as u64)?;
+            header.write_all(&property.data)?;
+        }
+        header.write_u8(K_END)?;
+        Ok(())
+    }
+
+    /// Encodes the `raw_header` to be written at `position`.
+    ///
+    /// Returns the packed stream of the encoded header followed by the header itself,
+    /// and the offset of the header.
+    fn encode_header(&self, raw_header: &[u8], position: u64) -> std::io::Result<(Vec<u8>, usize)> {
         let mut pack_info = PackInfo::default();
 
-        let position = self.output.stream_position()?;
-        let pos = position - SIGNATURE_HEADER_SIZE;
+        let pos = position - self.base_offset - SIGNATURE_HEADER_SIZE;
         pack_info.pos = pos;
 
         let mut more_sizes = vec![];
         let size = raw_header.len() as u64;
-        let crc32 = crc32fast::hash(&raw_header);
+        let crc32 = crc32fast::hash(raw_header);
         let mut methods = vec![];
 
         if self.encrypt_header {
-            for conf in self.content_methods.iter() {
-                if conf.method.id() == EncoderMethod::AES256_SHA256.id() {
-                    methods.push(conf.clone());
-                    break;
+            #[cfg(feature = "aes256")]
+            if let Some(options) = &self.header_encryption {
+                methods.push(options.clone().into());
+            }
+            if methods.is_empty() {
+                for conf in self.content_methods.iter() {
+                    if conf.method.id() == EncoderMethod::AES256_SHA256.id() {
+                        methods.push(conf.clone());
+                        break;
+                    }
                 }
             }
         }
 
+        let encrypted = !methods.is_empty();
         methods.push(EncoderConfiguration::new(EncoderMethod::LZMA));
 
         let methods = Arc::new(methods);
@@ -422,21 +1036,21 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         let mut compress_size = 0;
         let mut compressed = CompressWrapWriter::new(&mut encoded_data, &mut compress_size);
         {
-            let mut encoder = Self::create_writer(&methods, &mut compressed, &mut more_sizes)
-                .map_err(std::io::Error::other)?;
-            encoder.write_all(&raw_header)?;
+            let mut encoder =
+                Self::create_writer(&methods, &self.codecs, &mut compressed, &mut more_sizes)
+                    .map_err(std::io::Error::other)?;
+            encoder.write_all(raw_header)?;
             encoder.flush()?;
             let _ = encoder.write(&[])?;
         }
 
         let compress_crc = compressed.crc_value();
         let compress_size = *compressed.bytes_written;
-        if compress_size as u64 + 20 >= size {
+        if !encrypted && compress_size as u64 + 20 >= size {
             // compression made it worse. Write raw data
-            header.write_all(&raw_header)?;
-            return Ok(());
+            return Ok((raw_header.to_vec(), 0));
         }
-        self.output.write_all(&encoded_data[..compress_size])?;
+        encoded_data.truncate(compress_size);
 
         pack_info.add_stream(compress_size as u64, compress_crc);
 
@@ -446,6 +1060,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         sizes.push(size);
         unpack_info.add(methods, sizes, crc32);
 
+        let header = &mut encoded_data;
         header.write_u8(K_ENCODED_HEADER)?;
 
         pack_info.write_to(header)?;
@@ -454,7 +1069,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
 
         header.write_u8(K_END)?;
 
-        Ok(())
+        Ok((encoded_data, compress_size))
     }
 
     fn write_streams_info<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
@@ -475,9 +1090,11 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         self.write_file_empty_files(header)?;
         self.write_file_anti_items(header)?;
         self.write_file_names(header)?;
+        self.write_comment(header)?;
         self.write_file_ctimes(header)?;
         self.write_file_atimes(header)?;
         self.write_file_mtimes(header)?;
+        self.write_file_start_positions(header)?;
         self.write_file_windows_attrs(header)?;
         header.write_u8(K_END)?;
         Ok(())
@@ -539,8 +1156,8 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         for entry in self.files.iter() {
             if !entry.has_stream {
                 let is_anti = entry.is_anti_item();
-                has_anti |= !is_anti;
-                if !is_anti {
+                has_anti |= is_anti;
+                if is_anti {
                     bitset.insert(counter);
                 }
                 counter += 1;
@@ -574,6 +1191,22 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         Ok(())
     }
 
+    fn write_comment<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
+        let Some(comment) = self.comment.as_ref() else {
+            return Ok(());
+        };
+        header.write_u8(K_COMMENT)?;
+        let mut temp: Vec<u8> = Vec::with_capacity(2 * comment.len() + 3);
+        temp.write_u8(0)?;
+        for c in comment.encode_utf16() {
+            temp.write_all(&c.to_le_bytes())?;
+        }
+        temp.write_all(&[0u8; 2])?;
+        write_u64(header, temp.len() as u64)?;
+        header.write_all(temp.as_slice())?;
+        Ok(())
+    }
+
     write_times!(
         write_file_ctimes,
         K_C_TIME,
@@ -587,6 +1220,12 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         has_last_modified_date,
         last_modified_date
     );
+    write_times!(
+        write_file_start_positions,
+        K_START_POS,
+        has_start_position,
+        start_position
+    );
     write_times!(
         write_file_windows_attrs,
         K_WIN_ATTRIBUTES,
@@ -602,6 +1241,103 @@ impl<W: Write + Seek> AutoFinish for ArchiveWriter<W> {
     }
 }
 
+impl<W: Read + Write + Seek + SetLen> ArchiveAppender<W> {
+    /// Opens the existing 7z archive stored in `writer` to append new entries to it, see
+    /// [`ArchiveWriter::open_for_append`].
+    pub fn new(mut writer: W, password: &Password) -> Result<Self> {
+        let archive = Archive::read(&mut writer, password)?;
+        #[cfg_attr(not(feature = "aes256"), allow(unused_mut))]
+        let mut this = Self::from_archive(writer, &archive)?;
+        #[cfg(feature = "aes256")]
+        if archive.header_encrypted {
+            this.writer
+                .set_header_encryption(Some(AesEncoderOptions::new(password.clone())));
+        }
+        Ok(this)
+    }
+
+    /// Prepares to update the existing `archive` stored in `writer` in place.
+    ///
+    /// When the appender is finished, the new header replaces the old header and the data
+    /// left behind it is removed.
+    pub(crate) fn from_archive(writer: W, archive: &Archive) -> Result<Self> {
+        let mut writer = ArchiveWriter::from_archive(writer, archive)?;
+        let pack_end = writer.output.stream_position()?;
+        let mut header = Vec::with_capacity(64 * 1024);
+        writer.write_header(&mut header)?;
+        writer
+            .output
+            .seek(std::io::SeekFrom::Start(archive.header_end))?;
+        Ok(Self {
+            writer,
+            pack_end,
+            header_end: archive.header_end,
+            header,
+        })
+    }
+
+    /// Moves the new packed streams to the end of the existing packed streams and writes
+    /// the new header behind them.
+    ///
+    /// The start header always points to a complete header: a copy of the header is first
+    /// written behind all data that is moved or overwritten, so the archive stays readable
+    /// if the update is interrupted.
+    pub fn finish(self) -> std::io::Result<W> {
+        let Self {
+            mut writer,
+            pack_end,
+            header_end,
+            header: old_header,
+        } = self;
+        let mut header: Vec<u8> = Vec::with_capacity(64 * 1024);
+        writer.write_header(&mut header)?;
+
+        let data_end = writer.output.stream_position()?;
+        let data_size = data_end - header_end;
+        let header_pos = pack_end + data_size;
+        let header_len = writer.encode_header(&header, header_pos)?.0.len() as u64;
+
+        // Until the new packed streams are moved, only the old header is valid.
+        let copy = if data_size > 0 { &old_header } else { &header };
+        writer.write_header_at(copy, data_end.max(header_pos + header_len))?;
+        writer.output.flush()?;
+
+        let mut buf = vec![0; data_size.min(1024 * 1024) as usize];
+        let mut moved = 0;
+        while moved < data_size {
+            let len = (data_size - moved).min(buf.len() as u64) as usize;
+            writer
+                .output
+                .seek(std::io::SeekFrom::Start(header_end + moved))?;
+            writer.output.read_exact(&mut buf[..len])?;
+            writer
+                .output
+                .seek(std::io::SeekFrom::Start(pack_end + moved))?;
+            writer.output.write_all(&buf[..len])?;
+            moved += len as u64;
+        }
+
+        let end = writer.write_header_at(&header, header_pos)?;
+        writer.output.flush()?;
+        writer.output.set_len(end)?;
+        Ok(writer.output)
+    }
+}
+
+impl<W: Write> Deref for ArchiveAppender<W> {
+    type Target = ArchiveWriter<W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.writer
+    }
+}
+
+impl<W: Write> DerefMut for ArchiveAppender<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.writer
+    }
+}
+
 pub(crate) fn write_u64<W: Write>(header: &mut W, mut value: u64) -> std::io::Result<()> {
     let mut first = 0;
     let mut mask = 0x80;
@@ -1,7 +1,7 @@
 use std::{io::Write, sync::Arc};
 
 use super::*;
-use crate::EncoderConfiguration;
+use crate::{Block, EncoderConfiguration};
 #[derive(Debug, Clone, Default)]
 pub(crate) struct UnpackInfo {
     pub(crate) blocks: Vec<BlockInfo>,
@@ -37,8 +37,26 @@ impl UnpackInfo {
             sizes,
             crc,
             num_sub_unpack_streams,
-            sub_stream_crcs,
+            sub_stream_crcs: sub_stream_crcs.into_iter().map(Some).collect(),
+            sub_stream_sizes,
+            ..Default::default()
+        })
+    }
+
+    /// Adds a block of an existing archive, whose packed streams are kept as they are.
+    pub(crate) fn add_existing(
+        &mut self,
+        block: Block,
+        sub_stream_sizes: Vec<u64>,
+        sub_stream_crcs: Vec<Option<u32>>,
+    ) {
+        self.blocks.push(BlockInfo {
+            sizes: block.unpack_sizes.clone(),
+            num_sub_unpack_streams: sub_stream_sizes.len() as u64,
             sub_stream_sizes,
+            sub_stream_crcs,
+            existing_block: Some(block),
+            ..Default::default()
         })
     }
 
@@ -106,7 +124,7 @@ impl UnpackInfo {
                 // Single substream - write CRC here and not in the folder section.
                 match f.sub_stream_crcs.first() {
                     None => {
-                        crcs_to_write.push(f.crc);
+                        crcs_to_write.push(Some(f.crc));
                     }
                     Some(crc) => {
                         crcs_to_write.push(*crc);
@@ -117,8 +135,20 @@ impl UnpackInfo {
 
         if !crcs_to_write.is_empty() {
             header.write_u8(K_CRC)?;
-            header.write_u8(1)?; // all CRCs defined.
-            for crc in crcs_to_write {
+            if crcs_to_write.iter().all(Option::is_some) {
+                header.write_u8(1)?; // all CRCs defined.
+            } else {
+                // Blocks of existing archives might have streams without CRC.
+                header.write_u8(0)?;
+                let mut crc_define_bits = BitSet::with_capacity(crcs_to_write.len());
+                for (i, crc) in crcs_to_write.iter().enumerate() {
+                    if crc.is_some() {
+                        crc_define_bits.insert(i);
+                    }
+                }
+                write_bit_set(&mut *header, &crc_define_bits)?;
+            }
+            for crc in crcs_to_write.into_iter().flatten() {
                 header.write_u32(crc)?;
             }
         }
@@ -135,7 +165,10 @@ pub(crate) struct BlockInfo {
     pub(crate) crc: u32,
     pub(crate) num_sub_unpack_streams: u64,
     pub(crate) sub_stream_sizes: Vec<u64>,
-    pub(crate) sub_stream_crcs: Vec<u32>,
+    pub(crate) sub_stream_crcs: Vec<Option<u32>>,
+    /// Coder graph of a block copied from an existing archive or of a BCJ2 block. If set,
+    /// it is written instead of `methods`.
+    pub(crate) existing_block: Option<Block>,
 }
 
 impl BlockInfo {
@@ -144,6 +177,9 @@ impl BlockInfo {
         header: &mut W,
         cache: &mut Vec<u8>,
     ) -> std::io::Result<()> {
+        if let Some(block) = &self.existing_block {
+            return Self::write_existing_block(block, header);
+        }
         cache.clear();
         let mut num_coders = 0;
         for mc in self.methods.iter() {
@@ -159,6 +195,41 @@ impl BlockInfo {
         Ok(())
     }
 
+    fn write_existing_block<W: Write>(block: &Block, header: &mut W) -> std::io::Result<()> {
+        write_u64(header, block.coders.len() as u64)?;
+        for coder in block.coders.iter() {
+            let id = coder.encoder_method_id();
+            let is_simple = coder.num_in_streams == 1 && coder.num_out_streams == 1;
+            let mut codec_flags = id.len() as u8;
+            if !is_simple {
+                codec_flags |= 0x10;
+            }
+            if !coder.properties.is_empty() {
+                codec_flags |= 0x20;
+            }
+            header.write_u8(codec_flags)?;
+            header.write_all(id)?;
+            if !is_simple {
+                write_u64(header, coder.num_in_streams)?;
+                write_u64(header, coder.num_out_streams)?;
+            }
+            if !coder.properties.is_empty() {
+                write_u64(header, coder.properties.len() as u64)?;
+                header.write_all(&coder.properties)?;
+            }
+        }
+        for bind_pair in block.bind_pairs.iter() {
+            write_u64(header, bind_pair.in_index)?;
+            write_u64(header, bind_pair.out_index)?;
+        }
+        if block.packed_streams.len() > 1 {
+            for packed_stream in block.packed_streams.iter() {
+                write_u64(header, *packed_stream)?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_single_codec<H: Write>(
         &self,
         mc: &EncoderConfiguration,
@@ -174,7 +245,7 @@ impl BlockInfo {
         out.write_u8(codec_flags)?;
         out.write_all(id)?;
         if !props.is_empty() {
-            out.write_u8(props.len() as u8)?;
+            write_u64(out, props.len() as u64)?;
             out.write_all(props)?;
         }
         Ok(())
@@ -0,0 +1,45 @@
+//! Helpers to build 7z archives byte by byte, for archive layouts the writer doesn't produce.
+#![allow(dead_code)]
+
+/// Writes `value` as a 7z variable-length number.
+pub fn write_number(out: &mut Vec<u8>, value: u64) {
+    let mut first = 0u8;
+    let mut mask = 0x80u8;
+    let mut extra_bytes = 0;
+    while extra_bytes < 8 {
+        if value < 1 << (7 * (extra_bytes + 1)) {
+            first |= (value >> (8 * extra_bytes)) as u8;
+            break;
+        }
+        first |= mask;
+        mask >>= 1;
+        extra_bytes += 1;
+    }
+    out.push(first);
+    out.extend((0..extra_bytes).map(|i| (value >> (8 * i)) as u8));
+}
+
+/// Encodes the `names` of the entries as they are stored in the kName property.
+pub fn encode_names(names: &[&str]) -> Vec<u8> {
+    names
+        .iter()
+        .flat_map(|name| name.encode_utf16().chain([0]))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect()
+}
+
+/// Builds an archive from its `packed` streams followed by the raw `header`, which may also
+/// be an encoded header.
+pub fn build_archive(packed: &[u8], header: &[u8]) -> Vec<u8> {
+    let mut start_header = Vec::new();
+    start_header.extend_from_slice(&(packed.len() as u64).to_le_bytes());
+    start_header.extend_from_slice(&(header.len() as u64).to_le_bytes());
+    start_header.extend_from_slice(&crc32fast::hash(header).to_le_bytes());
+
+    let mut data = vec![b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, 0, 4];
+    data.extend_from_slice(&crc32fast::hash(&start_header).to_le_bytes());
+    data.extend_from_slice(&start_header);
+    data.extend_from_slice(packed);
+    data.extend_from_slice(header);
+    data
+}
@@ -12,6 +12,11 @@ use sevenz_rust2::*;
 #[cfg(all(feature = "compress", feature = "util"))]
 use tempfile::*;
 
+#[cfg(all(feature = "compress", feature = "util"))]
+use crate::common::{build_archive, encode_names};
+
+mod common;
+
 #[cfg(all(feature = "compress", feature = "util"))]
 #[test]
 fn compress_empty_file() {
@@ -171,6 +176,59 @@ fn compress_one_file_with_random_content_encrypted() {
     }
 }
 
+#[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
+#[test]
+fn compress_small_archive_with_encrypted_header() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new("rust".into()).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("secret.txt"),
+            Some(b"data".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    // The header is too small to be compressed, but is still encrypted.
+    assert!(matches!(
+        Archive::read(&mut Cursor::new(&data), &Password::empty()),
+        Err(Error::PasswordRequired)
+    ));
+    let mut reader = ArchiveReader::new(Cursor::new(data), "rust".into()).unwrap();
+    assert_eq!(reader.read_file("secret.txt").unwrap(), b"data");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_anti_items() {
+    let mut anti_item = ArchiveEntry::new_file("deleted.txt");
+    anti_item.is_anti_item = true;
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer.push_archive_entry::<&[u8]>(anti_item, None).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data), &Password::empty()).unwrap();
+    let anti_items: Vec<_> = archive
+        .files
+        .iter()
+        .map(|file| (file.name(), file.is_anti_item()))
+        .collect();
+    assert_eq!(
+        anti_items,
+        [("dir", false), ("deleted.txt", true), ("empty.txt", false)]
+    );
+}
+
 #[cfg(all(feature = "compress", feature = "util"))]
 fn test_compression_method(methods: &[EncoderConfiguration]) {
     let mut content = Vec::new();
@@ -246,6 +304,34 @@ fn compress_with_copy_algorithm() {
     test_compression_method(&[EncoderMethod::COPY.into()]);
 }
 
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_undefined_pack_stream_crc() {
+    // A packed stream with the CRC 0 is written as a stream without a CRC.
+    let zero_crc = b"crc zero\xD0\xCF\x53\xE8";
+    assert_eq!(crc32fast::hash(zero_crc), 0);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("zero.bin"),
+            Some(zero_crc.as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("other.bin"),
+            Some(b"other".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("zero.bin").unwrap(), zero_crc);
+    assert_eq!(reader.read_file("other.bin").unwrap(), b"other");
+}
+
 #[cfg(all(feature = "compress", feature = "util"))]
 #[test]
 fn compress_with_delta_lzma_algorithm() {
@@ -277,6 +363,15 @@ fn compress_with_bcj_x86_lzma2_algorithm() {
     ]);
 }
 
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_bcj2_lzma2_algorithm() {
+    test_compression_method(&[
+        EncoderMethod::LZMA2.into(),
+        EncoderMethod::BCJ2_FILTER.into(),
+    ]);
+}
+
 #[cfg(all(feature = "compress", feature = "util"))]
 #[test]
 fn compress_with_bcj_arm_lzma2_algorithm() {
@@ -403,3 +498,377 @@ fn compress_with_lz4_skippable_algorithm() {
 fn compress_with_zstd_algorithm() {
     test_compression_method(&[EncoderMethod::ZSTD.into()]);
 }
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_prefix() {
+    let prefix = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    let mut writer =
+        ArchiveWriter::with_prefix(Cursor::new(Vec::new()), prefix.as_slice()).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+    assert_eq!(&data[..prefix.len()], prefix.as_slice());
+
+    let mut source = Cursor::new(data);
+    let archive = Archive::read_with_signature_scan(
+        &mut source,
+        &Password::empty(),
+        DEFAULT_SIGNATURE_SEARCH_LIMIT,
+    )
+    .unwrap();
+    assert_eq!(archive.base_offset(), prefix.len() as u64);
+
+    let mut reader = ArchiveReader::from_archive(archive, source, Password::empty());
+    assert_eq!(reader.read_file("apache2.txt").unwrap(), content);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+fn read_all_entries(path: &std::path::Path) -> Vec<(String, Vec<u8>)> {
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    let mut entries = Vec::new();
+    reader
+        .for_each_entries(|entry, reader| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            entries.push((entry.name().to_string(), data));
+            Ok(true)
+        })
+        .unwrap();
+    entries.sort();
+    entries
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn append_to_archive() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("append.7z");
+
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("day1.log"),
+            Some(b"day 1".as_slice()),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut writer = ArchiveWriter::open_for_append(&dest, &Password::empty()).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("archive"), None)
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("day2.log"),
+                ArchiveEntry::new_file("day3.log"),
+            ],
+            vec![
+                SourceReader::new(b"day 2".as_slice()),
+                SourceReader::new(b"day 3".as_slice()),
+            ],
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    let entries = read_all_entries(&dest);
+    let expected = [
+        ("archive", ""),
+        ("day1.log", "day 1"),
+        ("day2.log", "day 2"),
+        ("day3.log", "day 3"),
+    ];
+    assert_eq!(entries.len(), expected.len());
+    for ((name, data), (expected_name, expected_data)) in entries.iter().zip(expected) {
+        assert_eq!(name, expected_name);
+        assert_eq!(data, expected_data.as_bytes());
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn append_to_bcj2_archive() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("bcj2.7z");
+    std::fs::copy("tests/resources/7za433_7zip_lzma2_bcj2.7z", &dest).unwrap();
+    let original_entries = read_all_entries(&dest);
+
+    let mut writer = ArchiveWriter::open_for_append(&dest, &Password::empty()).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("new.txt"), Some(b"new".as_slice()))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut entries = read_all_entries(&dest);
+    assert_eq!(
+        entries.pop().unwrap(),
+        ("new.txt".to_string(), b"new".to_vec())
+    );
+    assert_eq!(entries, original_entries);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn append_without_finish_keeps_archive() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("unfinished.7z");
+
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("day1.log"),
+            Some(b"day 1".as_slice()),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut writer = ArchiveWriter::open_for_append(&dest, &Password::empty()).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("day2.log"),
+            Some(b"day 2".repeat(1000).as_slice()),
+        )
+        .unwrap();
+    drop(writer);
+
+    assert_eq!(
+        read_all_entries(&dest),
+        vec![("day1.log".to_string(), b"day 1".to_vec())]
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
+#[test]
+fn append_keeps_header_encryption() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("encrypted.7z");
+    let password = Password::from("secret");
+
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new(password.clone()).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("day1.log"),
+            Some(b"day 1".as_slice()),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut writer = ArchiveWriter::open_for_append(&dest, &password).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("day2.log"),
+            Some(b"day 2".as_slice()),
+        )
+        .unwrap();
+    writer.finish().unwrap();
+
+    assert!(Archive::open(&dest).is_err());
+    let mut reader = ArchiveReader::open(&dest, password).unwrap();
+    assert_eq!(reader.read_file("day1.log").unwrap(), b"day 1");
+    assert_eq!(reader.read_file("day2.log").unwrap(), b"day 2");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn append_keeps_archive_properties() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("properties.7z");
+    let names = encode_names(&["a.txt"]);
+    let mut header = vec![
+        0x01, // kHeader
+        0x02, 0x05, 0x03, 1, 2, 3, 0x00, // kArchiveProperties
+        0x05, 0x01, // kFilesInfo
+        0x0E, 0x01, 0x80, // kEmptyStream
+        0x0F, 0x01, 0x80, // kEmptyFile
+        0x11,
+    ];
+    header.push(names.len() as u8 + 1);
+    header.push(0x00);
+    header.extend_from_slice(&names);
+    header.extend_from_slice(&[0x00, 0x00]);
+    std::fs::write(&dest, build_archive(&[], &header)).unwrap();
+
+    let mut writer = ArchiveWriter::open_for_append(&dest, &Password::empty()).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("b.txt"), Some(b"b".as_slice()))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let archive = Archive::open(&dest).unwrap();
+    assert_eq!(
+        archive.archive_properties,
+        [ArchiveProperty {
+            property_type: 0x05,
+            data: vec![1, 2, 3],
+        }]
+    );
+    assert_eq!(
+        read_all_entries(&dest),
+        [
+            ("a.txt".to_string(), Vec::new()),
+            ("b.txt".to_string(), b"b".to_vec())
+        ]
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn round_trip_start_positions() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let mut first = ArchiveEntry::new_file("part1.bin");
+    first.has_start_position = true;
+    first.start_position = 0;
+    let mut second = ArchiveEntry::new_file("part2.bin");
+    second.has_start_position = true;
+    second.start_position = 0x1_0000_0000;
+    writer
+        .push_archive_entry(first, Some(b"first part".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry(second, Some(b"second part".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("other.bin"), None)
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data), &Password::empty()).unwrap();
+    let positions: Vec<_> = archive
+        .files
+        .iter()
+        .map(|file| (file.has_start_position, file.start_position))
+        .collect();
+    assert_eq!(positions, [(true, 0), (true, 0x1_0000_0000), (false, 0)]);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn round_trip_comment() {
+    let comment = "build: 1.2.3\ncommit: 0123abc\nnotes: ü 🦀";
+    for encrypt_header in [false, true] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_encrypt_header(encrypt_header);
+        writer.set_comment(comment);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("manifest.txt"),
+                Some(b"manifest".as_slice()),
+            )
+            .unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+        assert_eq!(reader.archive().comment(), Some(comment));
+        assert_eq!(reader.read_file("manifest.txt").unwrap(), b"manifest");
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+    let archive = Archive::read(&mut Cursor::new(data), &Password::empty()).unwrap();
+    assert_eq!(archive.comment(), None);
+}
+
+/// Returns x86-like data with many branch instructions, converted and unconverted ones.
+#[cfg(all(feature = "compress", feature = "util"))]
+fn branch_heavy_data(len: usize) -> Vec<u8> {
+    use rand::Rng;
+    let mut rng = rand::rng();
+    let mut data = Vec::with_capacity(len + 6);
+    while data.len() < len {
+        match rng.random_range(0..6) {
+            0 => data.push(0xE8),
+            1 => data.push(0xE9),
+            2 => data.extend_from_slice(&[0x0F, rng.random_range(0x80..=0x8F)]),
+            3 => data.extend_from_slice(&rng.random_range(-5000i32..5000).to_le_bytes()),
+            4 => data.extend_from_slice(&rng.random::<u32>().to_le_bytes()),
+            _ => data.push(rng.random()),
+        }
+    }
+    data.truncate(len);
+    data
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_solid_with_bcj2_filter() {
+    let mut contents: Vec<Vec<u8>> = [0, 1, 4, 5, 6, 100, 200_000]
+        .into_iter()
+        .map(branch_heavy_data)
+        .collect();
+    contents.push(vec![0x90, 0x0F, 0x85]);
+    contents.push(vec![0xE8; 7]);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        EncoderMethod::LZMA2.into(),
+        EncoderMethod::BCJ2_FILTER.into(),
+    ]);
+    let entries = (0..contents.len())
+        .map(|i| ArchiveEntry::new_file(&format!("{i}.bin")))
+        .collect();
+    let readers = contents
+        .iter()
+        .map(|content| SourceReader::new(content.as_slice()))
+        .collect();
+    writer.push_archive_entries(entries, readers).unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+    let block = &reader.archive().blocks[0];
+    let ids: Vec<_> = block
+        .coders
+        .iter()
+        .map(|coder| coder.encoder_method_id())
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            EncoderMethod::ID_LZMA2,
+            EncoderMethod::ID_BCJ2,
+            EncoderMethod::ID_LZMA,
+            EncoderMethod::ID_LZMA,
+        ]
+    );
+    for (i, content) in contents.iter().enumerate() {
+        assert_eq!(&reader.read_file(&format!("{i}.bin")).unwrap(), content);
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
+#[test]
+fn compress_encrypted_with_bcj2_filter() {
+    let content = branch_heavy_data(50_000);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new("secret".into()).into(),
+        EncoderMethod::LZMA2.into(),
+        EncoderMethod::BCJ2_FILTER.into(),
+    ]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("app.exe"), Some(content.as_slice()))
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data), "secret".into()).unwrap();
+    let aes_coders = reader.archive().blocks[0]
+        .coders
+        .iter()
+        .filter(|coder| coder.encoder_method_id() == EncoderMethod::ID_AES256_SHA256)
+        .count();
+    assert_eq!(aes_coders, 4);
+    assert_eq!(reader.read_file("app.exe").unwrap(), content);
+}
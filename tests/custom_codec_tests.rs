@@ -0,0 +1,136 @@
+#[cfg(feature = "compress")]
+use std::io::{self, Cursor, Read, Write};
+
+#[cfg(feature = "compress")]
+use sevenz_rust2::{encoder_options::EncoderOptions, *};
+
+#[cfg(feature = "compress")]
+const XOR_METHOD: EncoderMethod = EncoderMethod::new("XOR", &[0x7F, 0x58, 0x4F, 0x52]);
+
+/// A filter which XORs the data with the key stored in its properties.
+#[cfg(feature = "compress")]
+struct XorCodec;
+
+#[cfg(feature = "compress")]
+struct XorFilter<T> {
+    inner: T,
+    key: Vec<u8>,
+    pos: usize,
+}
+
+#[cfg(feature = "compress")]
+impl<T> XorFilter<T> {
+    fn new(inner: T, properties: &[u8]) -> Result<Self, Error> {
+        if properties.is_empty() {
+            return Err(Error::Other("XOR key is missing".into()));
+        }
+        Ok(Self {
+            inner,
+            key: properties.to_vec(),
+            pos: 0,
+        })
+    }
+
+    fn apply(&mut self, buf: &mut [u8]) {
+        for byte in buf {
+            *byte ^= self.key[self.pos % self.key.len()];
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<R: Read> Read for XorFilter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.apply(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Write for XorFilter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.write(buf);
+        }
+        let mut data = buf.to_vec();
+        self.apply(&mut data);
+        self.inner.write_all(&data)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "compress")]
+impl Codec for XorCodec {
+    fn method_id(&self) -> &[u8] {
+        XOR_METHOD.id()
+    }
+
+    fn decoder<'r>(
+        &self,
+        input: Box<dyn Read + 'r>,
+        properties: &[u8],
+        _unpack_size: u64,
+    ) -> Result<Box<dyn Read + 'r>, Error> {
+        Ok(Box::new(XorFilter::new(input, properties)?))
+    }
+
+    fn encoder<'w>(
+        &self,
+        output: Box<dyn Write + 'w>,
+        properties: &[u8],
+    ) -> Result<Box<dyn Write + 'w>, Error> {
+        Ok(Box::new(XorFilter::new(output, properties)?))
+    }
+}
+
+#[cfg(feature = "compress")]
+fn write_xor_archive(content: &[u8]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.register_codec(XorCodec).set_content_methods(vec![
+        EncoderConfiguration::new(XOR_METHOD)
+            .with_options(EncoderOptions::Custom(vec![0x5A, 0xA5])),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(content))
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_custom_codec_roundtrip() {
+    let content = b"columnar floats ".repeat(1000);
+    let data = write_xor_archive(&content);
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    reader.register_codec(XorCodec);
+    let coder = &reader.archive().blocks[0].coders[0];
+    assert_eq!(coder.encoder_method_id(), XOR_METHOD.id());
+    assert_eq!(reader.read_file("data.bin").unwrap(), content);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_custom_codec_not_registered() {
+    let data = write_xor_archive(b"data");
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!(matches!(
+        reader.read_file("data.bin"),
+        Err(Error::UnsupportedCompressionMethod(_))
+    ));
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![XOR_METHOD.into()]);
+    assert!(matches!(
+        writer.push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(&b"data"[..])),
+        Err(Error::UnsupportedCompressionMethod(_))
+    ));
+}
@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::{fs::File, io::Cursor};
 #[cfg(feature = "util")]
 use std::{
     fs::{read, read_to_string},
@@ -7,10 +7,18 @@ use std::{
 
 #[cfg(feature = "util")]
 use sevenz_rust2::decompress_file;
-use sevenz_rust2::{Archive, ArchiveReader, BlockDecoder, Password};
+use sevenz_rust2::{
+    Archive, ArchiveReader, BlockDecoder, DEFAULT_SIGNATURE_SEARCH_LIMIT, Error, Password,
+};
 #[cfg(feature = "util")]
 use tempfile::tempdir;
 
+#[cfg(any(feature = "deflate64", feature = "lizard", feature = "lzs"))]
+use crate::common::write_number;
+use crate::common::{build_archive, encode_names};
+
+mod common;
+
 #[cfg(feature = "util")]
 #[test]
 fn decompress_single_empty_file_unencoded_header() {
@@ -277,6 +285,63 @@ fn test_entry_compressed_size() {
     }
 }
 
+/// Builds an archive with a solid block stored with COPY, which contains the files `a.txt`
+/// and `b.txt` with a directory placed between them.
+fn archive_with_directory_inside_block() -> Vec<u8> {
+    let (a, b) = (b"first".as_slice(), b"second".as_slice());
+    let names = encode_names(&["a.txt", "dir", "b.txt"]);
+
+    let mut header = vec![0x01, 0x04, 0x06, 0x00, 0x01, 0x09];
+    header.push((a.len() + b.len()) as u8);
+    header.extend_from_slice(&[0x00, 0x07, 0x0B, 0x01, 0x00, 0x01, 0x01, 0x00, 0x0C]);
+    header.push((a.len() + b.len()) as u8);
+    header.extend_from_slice(&[0x00, 0x08, 0x0D, 0x02, 0x09, a.len() as u8, 0x0A, 0x01]);
+    header.extend_from_slice(&crc32fast::hash(a).to_le_bytes());
+    header.extend_from_slice(&crc32fast::hash(b).to_le_bytes());
+    // Only the directory has no stream.
+    header.extend_from_slice(&[0x00, 0x00, 0x05, 0x03, 0x0E, 0x01, 0x40, 0x11]);
+    header.push(names.len() as u8 + 1);
+    header.push(0x00);
+    header.extend_from_slice(&names);
+    header.extend_from_slice(&[0x00, 0x00]);
+
+    build_archive(&[a, b].concat(), &header)
+}
+
+#[test]
+fn test_block_decoder_with_directory_inside_block() {
+    let data = archive_with_directory_inside_block();
+    let mut source = Cursor::new(data.as_slice());
+    let archive = Archive::read(&mut source, &Password::empty()).unwrap();
+    let password = Password::empty();
+    let block_decoder = BlockDecoder::new(1, 0, &archive, &password, &mut source);
+    let names: Vec<_> = block_decoder
+        .entries()
+        .iter()
+        .map(|entry| entry.name())
+        .collect();
+    assert_eq!(names, ["a.txt", "dir", "b.txt"]);
+    assert_eq!(block_decoder.entry_count(), 3);
+
+    let mut entries = Vec::new();
+    block_decoder
+        .for_each_entries(&mut |entry, reader| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            entries.push((entry.name().to_string(), data));
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(
+        entries,
+        [
+            ("a.txt".to_string(), b"first".to_vec()),
+            ("dir".to_string(), Vec::new()),
+            ("b.txt".to_string(), b"second".to_vec()),
+        ]
+    );
+}
+
 #[test]
 fn test_get_file_by_path() {
     // non_solid.7z and solid.7z are expected to have the same content.
@@ -302,3 +367,1203 @@ fn test_get_file_by_path() {
         assert_eq!(&data0, &data1);
     }
 }
+
+fn with_sfx_stub(archive_path: &str) -> (Vec<u8>, u64) {
+    let mut data = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let base_offset = data.len() as u64;
+    data.extend(std::fs::read(archive_path).unwrap());
+    (data, base_offset)
+}
+
+#[test]
+fn test_read_sfx_archive() {
+    let (data, base_offset) = with_sfx_stub("tests/resources/non_solid.7z");
+
+    let result = Archive::read(&mut Cursor::new(&data), &Password::empty());
+    assert!(matches!(result, Err(Error::BadSignature(_))));
+
+    let mut source = Cursor::new(data);
+    let archive = Archive::read_with_signature_scan(
+        &mut source,
+        &Password::empty(),
+        DEFAULT_SIGNATURE_SEARCH_LIMIT,
+    )
+    .unwrap();
+    assert_eq!(archive.base_offset(), base_offset);
+
+    let mut sfx_reader = ArchiveReader::from_archive(archive, source, Password::empty());
+    let mut reader =
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap();
+    assert_eq!(reader.archive().base_offset(), 0);
+
+    let paths: Vec<String> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| !file.is_directory)
+        .map(|file| file.name.clone())
+        .collect();
+    for path in paths.iter() {
+        let data0 = reader.read_file(path.as_str()).unwrap();
+        let data1 = sfx_reader.read_file(path.as_str()).unwrap();
+        assert_eq!(&data0, &data1);
+    }
+}
+
+#[test]
+fn test_read_sfx_archive_bcj2() {
+    let (data, base_offset) = with_sfx_stub("tests/resources/7za433_7zip_lzma2_bcj2.7z");
+    let mut source = Cursor::new(data);
+    let password = Password::empty();
+    let archive =
+        Archive::read_with_signature_scan(&mut source, &password, DEFAULT_SIGNATURE_SEARCH_LIMIT)
+            .unwrap();
+    assert_eq!(archive.base_offset(), base_offset);
+    for i in 0..archive.blocks.len() {
+        let fd = BlockDecoder::new(1, i, &archive, &password, &mut source);
+        fd.for_each_entries(&mut |_entry, reader| {
+            std::io::copy(reader, &mut std::io::sink())?;
+            Ok(true)
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_read_sfx_archive_outside_search_limit() {
+    let (data, base_offset) = with_sfx_stub("tests/resources/non_solid.7z");
+    let result = Archive::read_with_signature_scan(
+        &mut Cursor::new(data),
+        &Password::empty(),
+        base_offset - 1,
+    );
+    assert!(matches!(result, Err(Error::BadSignature(_))));
+}
+
+fn assert_seekable_entries<R: std::io::Read + std::io::Seek>(mut reader: ArchiveReader<R>) {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let names: Vec<String> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| file.has_stream && file.size > 2)
+        .map(|file| file.name().to_string())
+        .collect();
+    assert!(!names.is_empty());
+
+    for name in names.iter().rev() {
+        let expected = reader.read_file(name).unwrap();
+        let mut entry = reader.open_entry(name).unwrap();
+        assert_eq!(entry.entry().name(), name);
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        assert_eq!(data, expected);
+
+        let middle = expected.len() / 2;
+        assert_eq!(
+            entry.seek(SeekFrom::Start(middle as u64)).unwrap(),
+            middle as u64
+        );
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        assert_eq!(data, expected[middle..]);
+
+        entry.seek(SeekFrom::End(-2)).unwrap();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        assert_eq!(data, expected[expected.len() - 2..]);
+
+        entry.seek(SeekFrom::Start(1)).unwrap();
+        entry.seek(SeekFrom::Current(-1)).unwrap();
+        let mut data = [0; 2];
+        entry.read_exact(&mut data).unwrap();
+        assert_eq!(data, expected[..2]);
+
+        assert!(entry.seek(SeekFrom::Current(-3)).is_err());
+    }
+}
+
+#[test]
+fn test_open_entry_in_solid_block() {
+    assert_seekable_entries(
+        ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap(),
+    );
+}
+
+#[test]
+fn test_open_entry_in_non_solid_blocks() {
+    assert_seekable_entries(
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap(),
+    );
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_open_entry_in_stored_block() {
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod, SourceReader};
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("first.txt"),
+                ArchiveEntry::new_file("second.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first entry".as_slice()),
+                SourceReader::new(b"second entry".as_slice()),
+            ],
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+    assert_seekable_entries(ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap());
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_open_entry_verifies_crc_after_seeking() {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod};
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("data.txt"),
+            Some(b"stored entry data".as_slice()),
+        )
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+    // Corrupt the last byte of the stored data behind the signature header.
+    data[32 + 16] ^= 0xFF;
+
+    let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+    let mut entry = reader.open_entry("data.txt").unwrap();
+    let mut start = [0; 4];
+    entry.read_exact(&mut start).unwrap();
+    entry.seek(SeekFrom::Start(2)).unwrap();
+    entry.read_to_end(&mut Vec::new()).unwrap();
+
+    entry.seek(SeekFrom::Start(0)).unwrap();
+    let err = entry.read_to_end(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        err.into_inner().unwrap().downcast::<Error>().map(|e| *e),
+        Ok(Error::ChecksumVerificationFailed)
+    ));
+}
+
+#[test]
+fn test_open_missing_entry() {
+    let mut reader = ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap();
+    assert!(matches!(
+        reader.open_entry("missing.txt"),
+        Err(Error::FileNotFound)
+    ));
+}
+
+fn assert_pulled_entries<R: std::io::Read + std::io::Seek>(mut reader: ArchiveReader<R>) {
+    use std::io::Read;
+
+    let mut expected = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            expected.push((entry.name().to_string(), content));
+            Ok(true)
+        })
+        .unwrap();
+
+    let mut entries = reader.entries();
+    let mut index = 0;
+    while let Some(result) = entries.next_entry() {
+        let (entry, mut data) = result.unwrap();
+        let (name, content) = &expected[index];
+        assert_eq!(entry.name(), name);
+        match index % 3 {
+            // Read the whole entry.
+            0 => {
+                let mut actual = Vec::new();
+                data.read_to_end(&mut actual).unwrap();
+                assert_eq!(&actual, content);
+            }
+            // Read only the start of the entry.
+            1 => {
+                let mut actual = vec![0; content.len().min(3)];
+                data.read_exact(&mut actual).unwrap();
+                assert_eq!(actual, content[..actual.len()]);
+            }
+            // Skip the entry.
+            _ => {}
+        }
+        index += 1;
+    }
+    assert_eq!(index, expected.len());
+}
+
+#[test]
+fn test_entries_in_solid_block() {
+    assert_pulled_entries(
+        ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap(),
+    );
+}
+
+#[test]
+fn test_entries_in_non_solid_blocks() {
+    assert_pulled_entries(
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap(),
+    );
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_entries_checksum_mismatch() {
+    use std::io::Read;
+
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod, SourceReader};
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("first.txt"),
+                ArchiveEntry::new_file("second.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first entry".as_slice()),
+                SourceReader::new(b"second entry".as_slice()),
+            ],
+        )
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+    // Corrupt the last byte of the second entry, which is stored uncompressed.
+    data[32 + 22] ^= 0xFF;
+
+    let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+    let mut entries = reader.entries();
+    {
+        let (_, mut first) = entries.next_entry().unwrap().unwrap();
+        let mut content = Vec::new();
+        first.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"first entry");
+    }
+    {
+        let (_, mut second) = entries.next_entry().unwrap().unwrap();
+        let mut content = Vec::new();
+        assert!(second.read_to_end(&mut content).is_err());
+    }
+    assert!(entries.next_entry().is_none());
+}
+
+#[test]
+fn test_par_for_each_entries() {
+    use std::{collections::HashMap, sync::Mutex};
+
+    let path = "tests/resources/non_solid.7z";
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    assert!(reader.archive().blocks.len() > 1);
+
+    let mut expected = HashMap::new();
+    reader
+        .for_each_entries(|entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            expected.insert(entry.name().to_string(), content);
+            Ok(true)
+        })
+        .unwrap();
+
+    for thread_count in [1, 2, 8] {
+        reader.set_thread_count(thread_count);
+        let actual = Mutex::new(HashMap::new());
+        reader
+            .par_for_each_entries(
+                || Ok(File::open(path)?),
+                |entry, data| {
+                    let mut content = Vec::new();
+                    data.read_to_end(&mut content)?;
+                    actual
+                        .lock()
+                        .unwrap()
+                        .insert(entry.name().to_string(), content);
+                    Ok(true)
+                },
+            )
+            .unwrap();
+        assert_eq!(actual.into_inner().unwrap(), expected);
+    }
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_par_for_each_entries_aggregates_errors() {
+    use std::sync::Mutex;
+
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod};
+
+    let contents: [&[u8]; 3] = [b"first entry", b"second entry", b"third entry"];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    for (i, content) in contents.iter().enumerate() {
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file(&format!("file{i}.txt")),
+                Some(*content),
+            )
+            .unwrap();
+    }
+    let mut data = writer.finish().unwrap().into_inner();
+    // Corrupt the first and the third entry, which are stored uncompressed.
+    data[32] ^= 0xFF;
+    data[32 + contents[0].len() + contents[1].len()] ^= 0xFF;
+
+    let reader = ArchiveReader::new(Cursor::new(data.clone()), Password::empty()).unwrap();
+    let decoded = Mutex::new(Vec::new());
+    let result = reader.par_for_each_entries(
+        || Ok(Cursor::new(data.as_slice())),
+        |entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            decoded.lock().unwrap().push(entry.name().to_string());
+            Ok(true)
+        },
+    );
+
+    let Err(Error::Blocks(errors)) = result else {
+        panic!("expected block errors, got {result:?}");
+    };
+    let failed_blocks: Vec<_> = errors.iter().map(|(block_index, _)| *block_index).collect();
+    assert_eq!(failed_blocks, [Some(0), Some(2)]);
+    assert_eq!(decoded.into_inner().unwrap(), ["file1.txt"]);
+}
+
+#[test]
+fn test_par_for_each_entries_shared() {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use sevenz_rust2::ReadAtReader;
+
+    let path = "tests/resources/non_solid.7z";
+    let mut expected = HashMap::new();
+    ArchiveReader::open(path, Password::empty())
+        .unwrap()
+        .for_each_entries(|entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            expected.insert(entry.name().to_string(), content);
+            Ok(true)
+        })
+        .unwrap();
+
+    let file = File::open(path).unwrap();
+    let reader = ArchiveReader::new(ReadAtReader::new(file), Password::empty()).unwrap();
+    let actual = Mutex::new(HashMap::new());
+    reader
+        .par_for_each_entries_shared(|entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            actual
+                .lock()
+                .unwrap()
+                .insert(entry.name().to_string(), content);
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(actual.into_inner().unwrap(), expected);
+}
+
+#[test]
+fn test_block_decoders_share_read_at_source() {
+    use std::sync::Arc;
+
+    use sevenz_rust2::ReadAtReader;
+
+    let data = Arc::new(std::fs::read("tests/resources/non_solid.7z").unwrap());
+    let archive = Archive::read(&mut ReadAtReader::new(&*data), &Password::empty()).unwrap();
+    let password = Password::empty();
+
+    let decoded: Vec<Vec<(String, Vec<u8>)>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..archive.blocks.len())
+            .map(|block_index| {
+                let (archive, password) = (&archive, &password);
+                let mut source = ReadAtReader::new(Arc::clone(&data));
+                scope.spawn(move || {
+                    let mut entries = Vec::new();
+                    BlockDecoder::new(1, block_index, archive, password, &mut source)
+                        .for_each_entries(&mut |entry, reader| {
+                            let mut content = Vec::new();
+                            reader.read_to_end(&mut content)?;
+                            entries.push((entry.name().to_string(), content));
+                            Ok(true)
+                        })
+                        .unwrap();
+                    entries
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    for (name, content) in decoded.into_iter().flatten() {
+        assert_eq!(reader.read_file(&name).unwrap(), content);
+    }
+}
+
+#[test]
+fn test_read_file_with_block_cache() {
+    let path = "tests/resources/solid.7z";
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    let names: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| file.has_stream)
+        .map(|file| file.name().to_string())
+        .collect();
+    let expected: Vec<_> = names
+        .iter()
+        .map(|name| reader.read_file(name).unwrap())
+        .collect();
+    let block_size = reader.archive().blocks[0].get_unpack_size() as usize;
+
+    // The budget can't hold any file, only a part of the block or the whole block.
+    for budget in [1, block_size / 2, block_size] {
+        reader.set_block_cache_size(budget);
+        for (name, expected) in names.iter().zip(&expected) {
+            assert_eq!(&reader.read_file(name).unwrap(), expected);
+        }
+        for (name, expected) in names.iter().zip(&expected).rev() {
+            assert_eq!(&reader.read_file(name).unwrap(), expected);
+        }
+    }
+}
+
+#[cfg(feature = "spool")]
+#[test]
+fn test_read_from_non_seekable_stream() {
+    use sevenz_rust2::SpooledReader;
+
+    /// A source which only implements `Read`, like a pipe.
+    struct Pipe<R>(R);
+
+    impl<R: std::io::Read> std::io::Read for Pipe<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    let data = std::fs::read("tests/resources/solid.7z").unwrap();
+    let mut expected = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+
+    let mut readers = [
+        ArchiveReader::from_stream(Pipe(data.as_slice()), Password::empty()).unwrap(),
+        ArchiveReader::new(
+            SpooledReader::new(Pipe(data.as_slice()), 16).unwrap(),
+            Password::empty(),
+        )
+        .unwrap(),
+    ];
+    for reader in &mut readers {
+        let names: Vec<_> = reader
+            .archive()
+            .files
+            .iter()
+            .map(|file| file.name().to_string())
+            .collect();
+        for name in names {
+            assert_eq!(
+                reader.read_file(&name).unwrap(),
+                expected.read_file(&name).unwrap()
+            );
+        }
+    }
+
+    assert!(
+        SpooledReader::new(Pipe(data.as_slice()), data.len())
+            .unwrap()
+            .is_in_memory()
+    );
+    assert!(
+        !SpooledReader::new(Pipe(data.as_slice()), 16)
+            .unwrap()
+            .is_in_memory()
+    );
+}
+
+/// Builds an archive with an archive property and one empty file, whose name and
+/// modification time are stored in additional streams.
+fn archive_with_additional_streams(mtime: u64) -> Vec<u8> {
+    let names = encode_names(&["a.txt"]);
+    let mut packed = names.clone();
+    packed.extend_from_slice(&mtime.to_le_bytes());
+
+    let header = [
+        0x01, // kHeader
+        0x02,
+        0x05,
+        0x03,
+        1,
+        2,
+        3,
+        0x00, // kArchiveProperties
+        0x03, // kAdditionalStreamsInfo
+        0x06,
+        0x00,
+        0x02,
+        0x09,
+        names.len() as u8,
+        0x08,
+        0x00, // kPackInfo
+        0x07,
+        0x0B,
+        0x02,
+        0x00, // kUnpackInfo with two Copy blocks
+        0x01,
+        0x01,
+        0x00, //
+        0x01,
+        0x01,
+        0x00, //
+        0x0C,
+        names.len() as u8,
+        0x08,
+        0x00, //
+        0x00, //
+        0x05,
+        0x01, // kFilesInfo
+        0x0E,
+        0x01,
+        0x80, // kEmptyStream
+        0x0F,
+        0x01,
+        0x80, // kEmptyFile
+        0x11,
+        0x02,
+        0x01,
+        0x00, // kName in additional stream 0
+        0x14,
+        0x03,
+        0x01,
+        0x01,
+        0x01, // kMTime in additional stream 1
+        0x00, //
+        0x00,
+    ];
+
+    build_archive(&packed, &header)
+}
+
+#[test]
+fn test_read_header_with_additional_streams() {
+    use sevenz_rust2::ArchiveProperty;
+
+    let mtime = 133_000_000_000_000_000;
+    let data = archive_with_additional_streams(mtime);
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+
+    assert_eq!(
+        archive.archive_properties,
+        [ArchiveProperty {
+            property_type: 0x05,
+            data: vec![1, 2, 3],
+        }]
+    );
+    assert_eq!(archive.files.len(), 1);
+    let file = &archive.files[0];
+    assert_eq!(file.name(), "a.txt");
+    assert!(!file.is_directory());
+    assert!(!file.has_stream());
+    assert!(file.has_last_modified_date);
+    assert_eq!(u64::from(file.last_modified_date), mtime);
+}
+
+/// Builds an archive with one empty file, whose header is encoded in a block with a Copy
+/// coder bound to the main input of a BCJ2 coder.
+fn archive_with_bcj2_encoded_header() -> Vec<u8> {
+    let names = encode_names(&["a.txt"]);
+    let mut header = vec![
+        0x01, // kHeader
+        0x05,
+        0x01, // kFilesInfo
+        0x0E,
+        0x01,
+        0x80, // kEmptyStream
+        0x0F,
+        0x01,
+        0x80, // kEmptyFile
+        0x11,
+        names.len() as u8 + 1,
+        0x00, // kName
+    ];
+    header.extend_from_slice(&names);
+    header.extend_from_slice(&[0x00, 0x00]);
+    // Without branch instructions, the BCJ2 main stream is the data itself and the range
+    // coder stream only holds the flushed initial state.
+    assert!(!header.iter().any(|&b| b == 0xE8 || b == 0xE9));
+    let rc = [0u8; 5];
+
+    let mut encoded_header = vec![
+        0x17, // kEncodedHeader
+        0x06,
+        0x00,
+        0x04,
+        0x09,
+        header.len() as u8,
+        0x00,
+        0x00,
+        rc.len() as u8,
+        0x00, // kPackInfo with the main, call, jump and range coder streams
+        0x07,
+        0x0B,
+        0x01,
+        0x00, // kUnpackInfo
+        0x02,
+        0x01,
+        0x00, // Copy coder
+        0x14,
+        0x03,
+        0x03,
+        0x01,
+        0x1B,
+        0x04,
+        0x01, // BCJ2 coder with four input streams
+        0x01,
+        0x00, // Bind pair from the Copy coder to the main stream of BCJ2
+        0x00,
+        0x02,
+        0x03,
+        0x04, // Packed streams
+        0x0C,
+        header.len() as u8,
+        header.len() as u8,
+        0x0A,
+        0x01,
+    ];
+    encoded_header.extend_from_slice(&crc32fast::hash(&header).to_le_bytes());
+    encoded_header.extend_from_slice(&[0x00, 0x00]);
+
+    let mut packed = header;
+    packed.extend_from_slice(&rc);
+
+    build_archive(&packed, &encoded_header)
+}
+
+#[test]
+fn test_read_bcj2_encoded_header() {
+    let data = archive_with_bcj2_encoded_header();
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+
+    assert_eq!(archive.files.len(), 1);
+    assert_eq!(archive.files[0].name(), "a.txt");
+    assert!(!archive.files[0].has_stream());
+}
+
+/// Writes the bits of a Deflate stream, starting with the least significant bit.
+#[cfg(feature = "deflate64")]
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+#[cfg(feature = "deflate64")]
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.data.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored with the most significant bit first.
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Writes a symbol of the fixed literal/length code.
+    fn write_fixed_literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.data.push(self.bits as u8);
+        }
+        self.data
+    }
+}
+
+/// Builds a Deflate64 stream with a stored block and a fixed Huffman block, which has a match
+/// with the length code 285 and the distance code 30 of Deflate64, and the decoded data.
+#[cfg(feature = "deflate64")]
+fn deflate64_stream() -> (Vec<u8>, Vec<u8>) {
+    let mut expected = Vec::new();
+    let mut writer = BitWriter::default();
+
+    let stored = b"stored block";
+    writer.write_bits(0b000, 3);
+    writer.write_bits(0, 5);
+    writer.write_bits(stored.len() as u32, 16);
+    writer.write_bits(!(stored.len() as u32) & 0xFFFF, 16);
+    for &byte in stored {
+        writer.write_bits(byte as u32, 8);
+    }
+    expected.extend_from_slice(stored);
+
+    writer.write_bits(0b011, 3);
+    for i in 0..40_000u32 {
+        let byte = (i.wrapping_mul(7) ^ (i >> 8)) as u8;
+        writer.write_fixed_literal(byte as u32);
+        expected.push(byte);
+    }
+    // A match with length 3 + 997 and distance 32769 + 7231.
+    let (length, distance) = (1000, 40_000);
+    writer.write_fixed_literal(285);
+    writer.write_bits(997, 16);
+    writer.write_code(30, 5);
+    writer.write_bits(7231, 14);
+    for _ in 0..length {
+        expected.push(expected[expected.len() - distance]);
+    }
+    writer.write_fixed_literal(b'!' as u32);
+    expected.push(b'!');
+    writer.write_fixed_literal(256);
+
+    (writer.finish(), expected)
+}
+
+/// Builds an archive with a single file, which is stored in a block with a single coder.
+#[cfg(any(feature = "deflate64", feature = "lizard", feature = "lzs"))]
+fn single_coder_archive(method_id: &[u8], packed: &[u8], expected: &[u8]) -> Vec<u8> {
+    let names = encode_names(&["data.bin"]);
+
+    let mut header = vec![0x01, 0x04, 0x06, 0x00, 0x01, 0x09];
+    write_number(&mut header, packed.len() as u64);
+    header.extend_from_slice(&[0x00, 0x07, 0x0B, 0x01, 0x00, 0x01]);
+    header.push(method_id.len() as u8);
+    header.extend_from_slice(method_id);
+    header.push(0x0C);
+    write_number(&mut header, expected.len() as u64);
+    header.extend_from_slice(&[0x0A, 0x01]);
+    header.extend_from_slice(&crc32fast::hash(expected).to_le_bytes());
+    header.extend_from_slice(&[0x00, 0x08, 0x00, 0x00, 0x05, 0x01, 0x11]);
+    write_number(&mut header, names.len() as u64 + 1);
+    header.push(0x00);
+    header.extend_from_slice(&names);
+    header.extend_from_slice(&[0x00, 0x00]);
+
+    build_archive(packed, &header)
+}
+
+#[cfg(feature = "deflate64")]
+#[test]
+fn test_deflate64() {
+    let (packed, expected) = deflate64_stream();
+    let data = single_coder_archive(&[0x04, 0x01, 0x09], &packed, &expected);
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.bin").unwrap(), expected);
+}
+
+/// Writes a stream of a Lizard sub-block with its 24-bit length.
+#[cfg(feature = "lizard")]
+fn write_lizard_stream(out: &mut Vec<u8>, stream: &[u8]) {
+    out.extend_from_slice(&(stream.len() as u32).to_le_bytes()[..3]);
+    out.extend_from_slice(stream);
+}
+
+#[cfg(any(feature = "lizard", feature = "lzs"))]
+fn copy_match(expected: &mut Vec<u8>, offset: usize, len: usize) {
+    for _ in 0..len {
+        expected.push(expected[expected.len() - offset]);
+    }
+}
+
+/// Compresses the literals with Huff0, using a table with direct weights for the symbols
+/// `a` (code `1`) and `b`, `c`, `d`, `r` (codes `000` to `011`).
+#[cfg(feature = "lizard")]
+fn huf_compress(literals: &[u8]) -> Vec<u8> {
+    // The weights of the symbols up to `q`, the weight of `r` is implied.
+    let mut weights = vec![0u8; b'r' as usize];
+    weights[b'a' as usize] = 3;
+    weights[b'b' as usize..=b'd' as usize].fill(1);
+    let mut out = vec![127 + weights.len() as u8];
+    out.extend(weights.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+
+    let segment_len = literals.len().div_ceil(4);
+    let streams: Vec<Vec<u8>> = literals
+        .chunks(segment_len)
+        .map(|segment| {
+            // The bitstream is read backwards, so the last symbol is written first.
+            let mut bits = Vec::new();
+            for &symbol in segment.iter().rev() {
+                let (code, len) = match symbol {
+                    b'a' => (1, 1),
+                    _ => ((symbol - b'b').min(3) as u32, 3),
+                };
+                bits.extend((0..len).map(|i| (code >> i) & 1 == 1));
+            }
+            bits.push(true);
+            bits.chunks(8)
+                .map(|byte| {
+                    byte.iter()
+                        .rev()
+                        .fold(0u8, |acc, &bit| acc << 1 | bit as u8)
+                })
+                .collect()
+        })
+        .collect();
+    assert_eq!(streams.len(), 4);
+    for stream in &streams[..3] {
+        out.extend_from_slice(&(stream.len() as u16).to_le_bytes());
+    }
+    for stream in &streams {
+        out.extend_from_slice(stream);
+    }
+    out
+}
+
+/// Builds the Lizard frame of 7-Zip ZS, preceded by the skippable frame of zstdmt, with linked
+/// blocks of the LIZv1 and LZ4 codewords, Huffman coded literals and an uncompressed block, and
+/// the decoded data.
+#[cfg(feature = "lizard")]
+fn lizard_stream() -> (Vec<u8>, Vec<u8>) {
+    let mut expected = Vec::new();
+    let mut frame = vec![0x06, 0x22, 0x4D, 0x18, 0x44, 0x10, 0x00];
+
+    let mut block = vec![20];
+    let (mut lengths, mut offsets16, mut offsets24, mut tokens) = (vec![], vec![], vec![], vec![]);
+    // 5 literals and a match of length 6 with a 16-bit offset.
+    tokens.push(6 << 3 | 5);
+    offsets16.extend_from_slice(&5u16.to_le_bytes());
+    expected.extend_from_slice(b"hello");
+    copy_match(&mut expected, 5, 6);
+    // 7 + 3 literals and a match of length 15 + 100000 with the last offset.
+    tokens.push(0x80 | 15 << 3 | 7);
+    lengths.extend_from_slice(&[3, 255, 0xA0, 0x86, 0x01]);
+    expected.extend_from_slice(b" world! xy");
+    copy_match(&mut expected, 5, 100_015);
+    // A match of length 4 + 16 with a 24-bit offset.
+    tokens.push(4);
+    offsets24.extend_from_slice(&70_000u32.to_le_bytes()[..3]);
+    copy_match(&mut expected, 70_000, 20);
+    // A match of length 300 + 47 with a 24-bit offset.
+    tokens.push(31);
+    lengths.extend_from_slice(&[254, 0x2C, 0x01]);
+    offsets24.extend_from_slice(&12_345u32.to_le_bytes()[..3]);
+    copy_match(&mut expected, 12_345, 347);
+    // 2 literals without a match.
+    tokens.push(0x80 | 2);
+    expected.extend_from_slice(b"..");
+    let literals = b"hello world! xy..end";
+    expected.extend_from_slice(b"end");
+
+    block.push(0);
+    write_lizard_stream(&mut block, &lengths);
+    write_lizard_stream(&mut block, &offsets16);
+    write_lizard_stream(&mut block, &offsets24);
+    write_lizard_stream(&mut block, &tokens);
+    write_lizard_stream(&mut block, literals);
+
+    // A sub-block of Huffman coded last literals.
+    let literals = b"abracadabra".repeat(40);
+    let compressed = huf_compress(&literals);
+    block.push(0x01);
+    for _ in 0..4 {
+        write_lizard_stream(&mut block, &[]);
+    }
+    block.extend_from_slice(&(literals.len() as u32).to_le_bytes()[..3]);
+    write_lizard_stream(&mut block, &compressed);
+    expected.extend_from_slice(&literals);
+
+    // An uncompressed sub-block.
+    block.push(0x80);
+    write_lizard_stream(&mut block, b"stored");
+    expected.extend_from_slice(b"stored");
+
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+
+    // An uncompressed block.
+    frame.extend_from_slice(&(0x80000000u32 | 3).to_le_bytes());
+    frame.extend_from_slice(b"raw");
+    expected.extend_from_slice(b"raw");
+
+    // A block of the LZ4 codewords with a match of length 15 + 20 + 4 into the previous blocks.
+    let mut block = vec![10, 0];
+    write_lizard_stream(&mut block, &[20]);
+    write_lizard_stream(&mut block, &1000u16.to_le_bytes());
+    write_lizard_stream(&mut block, &[]);
+    write_lizard_stream(&mut block, &[1 << 4 | 15]);
+    write_lizard_stream(&mut block, b"X!");
+    expected.push(b'X');
+    copy_match(&mut expected, 1000, 39);
+    expected.push(b'!');
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+
+    // The end mark and the content checksum, which isn't verified.
+    frame.extend_from_slice(&0u32.to_le_bytes());
+    frame.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut packed = vec![0x50, 0x2A, 0x4D, 0x18, 0x04, 0x00, 0x00, 0x00];
+    packed.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    packed.extend_from_slice(&frame);
+    (packed, expected)
+}
+
+#[cfg(feature = "lizard")]
+#[test]
+fn test_lizard() {
+    let (packed, expected) = lizard_stream();
+    let data = single_coder_archive(&[0x04, 0xF7, 0x11, 0x06], &packed, &expected);
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.bin").unwrap(), expected);
+}
+
+/// Writes 32 linked uncompressed blocks of 1 MiB, twice the 16 MiB of data matches of Lizard
+/// and LZS can refer to, so the data no longer referred to is removed before the next block.
+#[cfg(any(feature = "lizard", feature = "lzs"))]
+fn write_uncompressed_blocks(frame: &mut Vec<u8>, expected: &mut Vec<u8>) {
+    for i in 0..32u32 {
+        let block: Vec<u8> = (0..1u32 << 20)
+            .map(|j| (j.wrapping_mul(2_654_435_761) >> 24) as u8 ^ i as u8)
+            .collect();
+        frame.extend_from_slice(&(0x80000000u32 | block.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&block);
+        expected.extend_from_slice(&block);
+    }
+}
+
+#[cfg(feature = "lizard")]
+#[test]
+fn test_lizard_with_long_history() {
+    let mut expected = Vec::new();
+    let mut frame = vec![0x06, 0x22, 0x4D, 0x18, 0x44, 0x30, 0x00];
+    write_uncompressed_blocks(&mut frame, &mut expected);
+
+    // A block of the LIZv1 codewords with a match of length 4 + 16 with the largest 24-bit
+    // offset and the last literals.
+    let mut block = vec![20, 0];
+    write_lizard_stream(&mut block, &[]);
+    write_lizard_stream(&mut block, &[]);
+    write_lizard_stream(&mut block, &0xFFFFFFu32.to_le_bytes()[..3]);
+    write_lizard_stream(&mut block, &[4]);
+    write_lizard_stream(&mut block, b"end");
+    copy_match(&mut expected, 0xFFFFFF, 20);
+    expected.extend_from_slice(b"end");
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+    frame.extend_from_slice(&0u32.to_le_bytes());
+    frame.extend_from_slice(&0u32.to_le_bytes());
+
+    let data = single_coder_archive(&[0x04, 0xF7, 0x11, 0x06], &frame, &expected);
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!(reader.read_file("data.bin").unwrap() == expected);
+}
+
+/// Builds the LZ5 frame of the LZS method of 7-Zip ZS, preceded by the skippable frame of
+/// zstdmt, with a block of all four codewords, an uncompressed block and a linked block, and the
+/// decoded data.
+#[cfg(feature = "lzs")]
+fn lzs_stream() -> (Vec<u8>, Vec<u8>) {
+    let mut expected = Vec::new();
+    let mut frame = vec![0x05, 0x22, 0x4D, 0x18, 0x44, 0x10, 0x00];
+
+    let mut block = Vec::new();
+    // 5 literals and a match of length 3 + 3 with a 16-bit offset.
+    block.push(5 << 3 | 3);
+    block.extend_from_slice(b"hello");
+    block.extend_from_slice(&5u16.to_le_bytes());
+    expected.extend_from_slice(b"hello");
+    copy_match(&mut expected, 5, 6);
+    // 3 + 7 literals and a match of length 7 + 100005 + 3 with a 10-bit offset.
+    block.extend_from_slice(&[0x80 | 3 << 3 | 7, 7]);
+    block.extend_from_slice(b" world! xy");
+    block.push(10);
+    block.extend(std::iter::repeat_n(255, 392));
+    block.push(45);
+    expected.extend_from_slice(b" world! xy");
+    copy_match(&mut expected, 10, 100_015);
+    // A match of length 4 + 3 with a 24-bit offset.
+    block.push(0x40 | 4);
+    block.extend_from_slice(&70_000u32.to_le_bytes()[..3]);
+    copy_match(&mut expected, 70_000, 7);
+    // 2 literals and a match of length 1 + 3 with the last offset.
+    block.push(0x60 | 2 << 3 | 1);
+    block.extend_from_slice(b"ab");
+    expected.extend_from_slice(b"ab");
+    copy_match(&mut expected, 70_000, 4);
+    // The last literals.
+    block.push(3 << 3);
+    block.extend_from_slice(b"end");
+    expected.extend_from_slice(b"end");
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+
+    // An uncompressed block.
+    frame.extend_from_slice(&(0x80000000u32 | 3).to_le_bytes());
+    frame.extend_from_slice(b"raw");
+    expected.extend_from_slice(b"raw");
+
+    // A block with a match of length 2 + 3 into the previous blocks.
+    let mut block = vec![1 << 3 | 2, b'X'];
+    block.extend_from_slice(&1000u16.to_le_bytes());
+    block.extend_from_slice(&[1 << 3, b'!']);
+    expected.push(b'X');
+    copy_match(&mut expected, 1000, 5);
+    expected.push(b'!');
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+
+    // The end mark and the content checksum, which isn't verified.
+    frame.extend_from_slice(&0u32.to_le_bytes());
+    frame.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut packed = vec![0x50, 0x2A, 0x4D, 0x18, 0x04, 0x00, 0x00, 0x00];
+    packed.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    packed.extend_from_slice(&frame);
+    (packed, expected)
+}
+
+#[cfg(feature = "lzs")]
+#[test]
+fn test_lzs() {
+    let (packed, expected) = lzs_stream();
+    let data = single_coder_archive(&[0x04, 0xF7, 0x11, 0x05], &packed, &expected);
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.bin").unwrap(), expected);
+}
+
+#[cfg(feature = "lzs")]
+#[test]
+fn test_lzs_with_long_history() {
+    let mut expected = Vec::new();
+    let mut frame = vec![0x05, 0x22, 0x4D, 0x18, 0x44, 0x30, 0x00];
+    write_uncompressed_blocks(&mut frame, &mut expected);
+
+    // A match of length 4 + 3 with the largest 24-bit offset and the last literals.
+    let mut block = vec![0x40 | 4];
+    block.extend_from_slice(&0xFFFFFFu32.to_le_bytes()[..3]);
+    block.push(3 << 3);
+    block.extend_from_slice(b"end");
+    copy_match(&mut expected, 0xFFFFFF, 7);
+    expected.extend_from_slice(b"end");
+    frame.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&block);
+    frame.extend_from_slice(&0u32.to_le_bytes());
+    frame.extend_from_slice(&0u32.to_le_bytes());
+
+    let data = single_coder_archive(&[0x04, 0xF7, 0x11, 0x05], &frame, &expected);
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!(reader.read_file("data.bin").unwrap() == expected);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_read_file_with_block_cache_resumes_decoder() {
+    use std::{
+        io::{Read, Seek, SeekFrom},
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter, SourceReader, encoder_options::Lzma2Options};
+
+    /// A source which counts the bytes read from it.
+    struct CountingReader<R> {
+        inner: R,
+        count: Arc<AtomicU64>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let size = self.inner.read(buf)?;
+            self.count.fetch_add(size as u64, Ordering::Relaxed);
+            Ok(size)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    // Pseudorandom data, so the block is about as large as the archive.
+    let mut state = 0x2545_f491_u32;
+    let contents: Vec<Vec<u8>> = (0..200)
+        .map(|_| {
+            (0..4096)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+        })
+        .collect();
+    let names: Vec<_> = (0..contents.len()).map(|i| format!("{i}.bin")).collect();
+
+    // A small dictionary, so the memory of the decoder fits into the budget of the cache.
+    let mut options = Lzma2Options::from_level(6);
+    options.set_dictionary_size(64 * 1024);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![options.into()]);
+    writer
+        .push_archive_entries(
+            names
+                .iter()
+                .map(|name| ArchiveEntry::new_file(name))
+                .collect(),
+            contents
+                .iter()
+                .map(|content| SourceReader::new(content.as_slice()))
+                .collect(),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let count = Arc::new(AtomicU64::new(0));
+    let source = CountingReader {
+        inner: Cursor::new(data.as_slice()),
+        count: Arc::clone(&count),
+    };
+    let mut reader = ArchiveReader::new(source, Password::empty()).unwrap();
+    assert_eq!(reader.archive().blocks.len(), 1);
+    reader.set_block_cache_size(512 * 1024);
+
+    count.store(0, Ordering::Relaxed);
+    for (name, content) in names.iter().zip(&contents) {
+        assert_eq!(&reader.read_file(name).unwrap(), content);
+    }
+    // Decoding the block again for every file would read it about a hundred times.
+    assert!(count.load(Ordering::Relaxed) < 2 * data.len() as u64);
+
+    // The decoder isn't kept if it doesn't fit into the budget.
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    reader.set_block_cache_size(16 * 1024);
+    for (name, content) in names.iter().zip(&contents).step_by(20) {
+        assert_eq!(&reader.read_file(name).unwrap(), content);
+    }
+}
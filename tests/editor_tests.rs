@@ -0,0 +1,500 @@
+#[cfg(feature = "compress")]
+use std::io::{Cursor, Read};
+
+#[cfg(feature = "compress")]
+use sevenz_rust2::*;
+
+#[cfg(feature = "compress")]
+fn read_all_entries<R: Read + std::io::Seek>(source: R) -> Vec<(String, Vec<u8>)> {
+    read_all_entries_with_password(source, Password::empty())
+}
+
+#[cfg(feature = "compress")]
+fn read_all_entries_with_password<R: Read + std::io::Seek>(
+    source: R,
+    password: Password,
+) -> Vec<(String, Vec<u8>)> {
+    let mut reader = ArchiveReader::new(source, password).unwrap();
+    let mut entries = Vec::new();
+    reader
+        .for_each_entries(|entry, reader| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            entries.push((entry.name().to_string(), data));
+            Ok(true)
+        })
+        .unwrap();
+    entries.sort();
+    entries
+}
+
+#[cfg(feature = "compress")]
+fn remove_entries(source: &[u8], predicate: impl FnMut(&ArchiveEntry) -> bool) -> Vec<u8> {
+    let mut editor = ArchiveEditor::new(Cursor::new(source), Password::empty()).unwrap();
+    editor.remove(predicate);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    editor.write_to(&mut writer).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn remove_entries_from_solid_archive() {
+    let source = std::fs::read("tests/resources/solid.7z").unwrap();
+    let mut expected = read_all_entries(Cursor::new(&source));
+    let removed_name = expected
+        .iter()
+        .find(|(_, data)| !data.is_empty())
+        .map(|(name, _)| name.clone())
+        .unwrap();
+    expected.retain(|(name, _)| *name != removed_name);
+
+    let result = remove_entries(&source, |entry| entry.name() == removed_name);
+    assert_eq!(read_all_entries(Cursor::new(result)), expected);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn remove_entries_copies_unaffected_blocks() {
+    let source = std::fs::read("tests/resources/non_solid.7z").unwrap();
+    let mut expected = read_all_entries(Cursor::new(&source));
+    let removed_name = expected
+        .iter()
+        .find(|(_, data)| !data.is_empty())
+        .map(|(name, _)| name.clone())
+        .unwrap();
+    expected.retain(|(name, _)| *name != removed_name);
+
+    let result = remove_entries(&source, |entry| entry.name() == removed_name);
+    assert!(result.len() < source.len());
+
+    // The packed streams of the kept entries must be copied without re-encoding.
+    let source_archive = Archive::read(&mut Cursor::new(&source), &Password::empty()).unwrap();
+    let result_archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    for file in result_archive.files.iter().filter(|file| file.has_stream) {
+        let source_file = source_archive
+            .files
+            .iter()
+            .find(|source_file| source_file.name() == file.name())
+            .unwrap();
+        assert_eq!(file.compressed_size, source_file.compressed_size);
+    }
+
+    assert_eq!(read_all_entries(Cursor::new(result)), expected);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn remove_entries_from_mixed_block() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("logs"), None)
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("logs/a.log"),
+                ArchiveEntry::new_file("logs/secret.txt"),
+                ArchiveEntry::new_file("logs/b.log"),
+            ],
+            vec![
+                SourceReader::new(b"first log".as_slice()),
+                SourceReader::new(b"a secret".as_slice()),
+                SourceReader::new(b"second log".as_slice()),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("readme.txt"),
+            Some(b"readme".as_slice()),
+        )
+        .unwrap();
+    let source = writer.finish().unwrap().into_inner();
+
+    let result = remove_entries(&source, |entry| entry.name() == "logs/secret.txt");
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    assert_eq!(archive.files.len(), 4);
+    assert_eq!(archive.blocks.len(), 2);
+    assert_eq!(
+        read_all_entries(Cursor::new(result)),
+        vec![
+            ("logs".to_string(), b"".to_vec()),
+            ("logs/a.log".to_string(), b"first log".to_vec()),
+            ("logs/b.log".to_string(), b"second log".to_vec()),
+            ("readme.txt".to_string(), b"readme".to_vec()),
+        ]
+    );
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn update_metadata_in_place() {
+    let source = std::fs::read("tests/resources/solid.7z").unwrap();
+    let expected: Vec<(String, Vec<u8>)> = read_all_entries(Cursor::new(&source))
+        .into_iter()
+        .map(|(name, data)| (format!("renamed/{name}"), data))
+        .collect();
+    let mut editor = ArchiveEditor::new(Cursor::new(source.clone()), Password::empty()).unwrap();
+    let modified = NtTime::new(133_000_000_000_000_000);
+    for entry in editor.entries_mut() {
+        entry.name = format!("renamed/{}", entry.name);
+        entry.has_last_modified_date = true;
+        entry.last_modified_date = modified;
+        entry.size = 0;
+    }
+    let result = editor.update_in_place().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    for entry in archive.files.iter() {
+        assert!(entry.name().starts_with("renamed/"));
+        assert_eq!(entry.last_modified_date, modified);
+    }
+    assert_eq!(read_all_entries(Cursor::new(result.clone())), expected);
+
+    // The new header replaces the old one, so repeated updates don't grow the archive.
+    let mut updated = result.clone();
+    for _ in 0..3 {
+        let mut editor = ArchiveEditor::new(Cursor::new(updated), Password::empty()).unwrap();
+        editor.entries_mut()[0].last_modified_date = modified;
+        updated = editor.update_in_place().unwrap().into_inner();
+        assert_eq!(updated.len(), result.len());
+    }
+    assert_eq!(read_all_entries(Cursor::new(updated)), expected);
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn update_metadata_in_place_encrypted_header() {
+    let password = Password::from("secret");
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        encoder_options::AesEncoderOptions::new(password.clone()).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(b"content".as_slice()))
+        .unwrap();
+    let source = writer.finish().unwrap();
+
+    let mut editor = ArchiveEditor::new(source, password.clone()).unwrap();
+    editor.entries_mut()[0].name = "b.txt".to_string();
+    let mut result = editor.update_in_place().unwrap();
+
+    assert!(Archive::read(&mut result, &Password::empty()).is_err());
+    let mut reader = ArchiveReader::new(result, password).unwrap();
+    assert_eq!(reader.read_file("b.txt").unwrap(), b"content");
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn update_metadata_in_place_keeps_plain_header() {
+    let source = encrypted_archive_with_plain_header("secret");
+
+    let mut editor = ArchiveEditor::new(Cursor::new(source), "secret".into()).unwrap();
+    editor.entries_mut()[0].name = "renamed.txt".to_string();
+    let result = editor.update_in_place().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    assert_eq!(archive.files[0].name(), "renamed.txt");
+    let mut reader = ArchiveReader::new(Cursor::new(result), "secret".into()).unwrap();
+    assert_eq!(reader.read_file("renamed.txt").unwrap(), b"first entry");
+}
+
+#[cfg(feature = "compress")]
+fn assert_same_metadata(left: &[ArchiveEntry], right: &[ArchiveEntry]) {
+    assert_eq!(left.len(), right.len());
+    for (left, right) in left.iter().zip(right) {
+        assert_eq!(left.name(), right.name());
+        assert_eq!(left.has_stream, right.has_stream);
+        assert_eq!(left.is_directory, right.is_directory);
+        assert_eq!(left.is_anti_item, right.is_anti_item);
+        assert_eq!(left.has_last_modified_date, right.has_last_modified_date);
+        assert_eq!(left.last_modified_date, right.last_modified_date);
+        assert_eq!(left.has_creation_date, right.has_creation_date);
+        assert_eq!(left.creation_date, right.creation_date);
+        assert_eq!(left.has_access_date, right.has_access_date);
+        assert_eq!(left.access_date, right.access_date);
+        assert_eq!(left.has_windows_attributes, right.has_windows_attributes);
+        assert_eq!(left.windows_attributes, right.windows_attributes);
+        assert_eq!(left.size, right.size);
+        assert_eq!(left.crc, right.crc);
+    }
+}
+
+#[cfg(feature = "compress")]
+fn transcode(
+    source: &[u8],
+    grouping: BlockGrouping,
+    methods: Vec<EncoderConfiguration>,
+) -> Vec<u8> {
+    let mut editor = ArchiveEditor::new(Cursor::new(source), Password::empty()).unwrap();
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(methods);
+    editor.transcode_to(&mut writer, grouping).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn transcode_solid_to_non_solid() {
+    let source = std::fs::read("tests/resources/solid.7z").unwrap();
+    let source_archive = Archive::read(&mut Cursor::new(&source), &Password::empty()).unwrap();
+
+    let result = transcode(
+        &source,
+        BlockGrouping::NonSolid,
+        vec![EncoderMethod::LZMA.into()],
+    );
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    assert_same_metadata(&archive.files, &source_archive.files);
+    assert!(!archive.is_solid);
+    assert_eq!(
+        archive.blocks.len(),
+        archive.files.iter().filter(|file| file.has_stream).count()
+    );
+    for block in archive.blocks.iter() {
+        assert_eq!(block.coders[0].encoder_method_id(), EncoderMethod::ID_LZMA);
+    }
+    assert_eq!(
+        read_all_entries(Cursor::new(result)),
+        read_all_entries(Cursor::new(source))
+    );
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn transcode_non_solid_to_solid() {
+    let source = std::fs::read("tests/resources/non_solid.7z").unwrap();
+    let source_archive = Archive::read(&mut Cursor::new(&source), &Password::empty()).unwrap();
+    assert!(source_archive.blocks.len() > 1);
+
+    let result = transcode(
+        &source,
+        BlockGrouping::Solid,
+        vec![EncoderMethod::LZMA2.into()],
+    );
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    assert_same_metadata(&archive.files, &source_archive.files);
+    assert_eq!(archive.blocks.len(), 1);
+    assert_eq!(
+        read_all_entries(Cursor::new(result)),
+        read_all_entries(Cursor::new(source))
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "bzip2"))]
+#[test]
+fn transcode_preserves_entry_order_and_metadata() {
+    let mut directory = ArchiveEntry::new_directory("dir");
+    directory.has_windows_attributes = true;
+    directory.windows_attributes = 0x10;
+    let mut empty_file = ArchiveEntry::new_file("dir/empty.txt");
+    empty_file.has_last_modified_date = true;
+    empty_file.last_modified_date = NtTime::new(133_000_000_000_000_000);
+    let mut anti_item = ArchiveEntry::new_file("deleted.txt");
+    anti_item.is_anti_item = true;
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(b"a".as_slice()))
+        .unwrap();
+    writer.push_archive_entry::<&[u8]>(directory, None).unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("dir/b.txt"),
+                ArchiveEntry::new_file("dir/c.txt"),
+            ],
+            vec![
+                SourceReader::new(b"b".as_slice()),
+                SourceReader::new(b"c".as_slice()),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(empty_file, None)
+        .unwrap();
+    writer.push_archive_entry::<&[u8]>(anti_item, None).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("d.txt"), Some(b"d".as_slice()))
+        .unwrap();
+    let source = writer.finish().unwrap().into_inner();
+    let source_archive = Archive::read(&mut Cursor::new(&source), &Password::empty()).unwrap();
+
+    for grouping in [
+        BlockGrouping::Preserve,
+        BlockGrouping::NonSolid,
+        BlockGrouping::Solid,
+    ] {
+        let result = transcode(&source, grouping, vec![EncoderMethod::BZIP2.into()]);
+        let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+        assert_same_metadata(&archive.files, &source_archive.files);
+        let expected_blocks = match grouping {
+            BlockGrouping::Preserve => 3,
+            BlockGrouping::NonSolid => 4,
+            BlockGrouping::Solid => 1,
+        };
+        assert_eq!(archive.blocks.len(), expected_blocks);
+        assert_eq!(
+            read_all_entries(Cursor::new(result)),
+            read_all_entries(Cursor::new(&source))
+        );
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn rekey_encrypted_archive() {
+    let source = std::fs::read("tests/resources/encrypted.7z").unwrap();
+    let expected = read_all_entries_with_password(Cursor::new(&source), "sevenz-rust".into());
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "sevenz-rust".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    editor.write_to(&mut writer).unwrap();
+    let result = writer.finish().unwrap().into_inner();
+
+    assert_eq!(
+        read_all_entries_with_password(Cursor::new(&result), "new password".into()),
+        expected
+    );
+    // The header is encrypted with the new password as well.
+    assert!(Archive::read(&mut Cursor::new(&result), &Password::empty()).is_err());
+    assert!(ArchiveReader::new(Cursor::new(&result), "sevenz-rust".into()).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn rekey_re_encoded_block_requires_encryption() {
+    use sevenz_rust2::encoder_options::AesEncoderOptions;
+
+    let source = encrypted_archive_with_plain_header("secret");
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "secret".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    editor.remove(|entry| entry.name() == "a.txt");
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert!(editor.write_to(&mut writer).is_err());
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "secret".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    editor.remove(|entry| entry.name() == "a.txt");
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new("new password".into()).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    editor.write_to(&mut writer).unwrap();
+    let result = writer.finish().unwrap().into_inner();
+
+    let entries = read_all_entries_with_password(Cursor::new(&result), "new password".into());
+    assert_eq!(entries.len(), 2);
+    assert!(ArchiveReader::new(Cursor::new(&result), "secret".into()).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+fn encrypted_archive_with_plain_header(password: &str) -> Vec<u8> {
+    use sevenz_rust2::encoder_options::AesEncoderOptions;
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_encrypt_header(false);
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new(password.into()).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("a.txt"),
+                ArchiveEntry::new_file("b.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first entry".as_slice()),
+                SourceReader::new(b"second entry".as_slice()),
+            ],
+        )
+        .unwrap();
+    // A block which is only encrypted, without any compression.
+    writer.set_content_methods(vec![AesEncoderOptions::new(password.into()).into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("c.txt"),
+            Some(b"third entry".as_slice()),
+        )
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn rekey_removes_encryption() {
+    let source = encrypted_archive_with_plain_header("secret");
+    let expected = read_all_entries_with_password(Cursor::new(&source), "secret".into());
+    assert_eq!(expected.len(), 3);
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "secret".into()).unwrap();
+    editor.rekey(None);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    editor.write_to(&mut writer).unwrap();
+    let result = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(&result), &Password::empty()).unwrap();
+    assert!(
+        archive
+            .blocks
+            .iter()
+            .flat_map(|block| block.coders.iter())
+            .all(|coder| coder.encoder_method_id() != EncoderMethod::ID_AES256_SHA256)
+    );
+    assert_eq!(read_all_entries(Cursor::new(result)), expected);
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn rekey_with_wrong_password() {
+    let source = encrypted_archive_with_plain_header("secret");
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "wrong".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert!(editor.write_to(&mut writer).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn rekey_copy_block_with_wrong_password() {
+    use sevenz_rust2::encoder_options::AesEncoderOptions;
+
+    // A block which is only encrypted, with a first entry larger than the start of the block
+    // which a wrong password could be detected with.
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_encrypt_header(false);
+    writer.set_content_methods(vec![AesEncoderOptions::new("secret".into()).into()]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("a.txt"),
+                ArchiveEntry::new_file("b.txt"),
+            ],
+            vec![
+                SourceReader::new(vec![7u8; 256 * 1024].as_slice()),
+                SourceReader::new(b"second entry".as_slice()),
+            ],
+        )
+        .unwrap();
+    let source = writer.finish().unwrap().into_inner();
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "wrong".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert!(editor.write_to(&mut writer).is_err());
+
+    let mut editor = ArchiveEditor::new(Cursor::new(&source), "secret".into()).unwrap();
+    editor.rekey(Some("new password".into()));
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    editor.write_to(&mut writer).unwrap();
+    let result = writer.finish().unwrap().into_inner();
+    let mut reader = ArchiveReader::new(Cursor::new(result), "new password".into()).unwrap();
+    assert_eq!(reader.read_file("b.txt").unwrap(), b"second entry");
+}
@@ -0,0 +1,96 @@
+use sevenz_rust2::*;
+
+#[test]
+fn test_valid_archive() {
+    let mut reader = ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap();
+    let report = reader.test().unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.start_header_crc, CrcStatus::Ok);
+    assert_eq!(report.header_crc, CrcStatus::Ok);
+    assert_eq!(report.blocks.len(), reader.archive().blocks.len());
+
+    let files_with_data: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| file.has_stream)
+        .collect();
+    assert_eq!(report.entries.len(), files_with_data.len());
+    assert!(
+        report
+            .entries
+            .iter()
+            .all(|entry| entry.crc == CrcStatus::Ok)
+    );
+    assert!(report.bytes_checked >= files_with_data.iter().map(|file| file.size).sum::<u64>());
+}
+
+#[cfg(feature = "compress")]
+fn write_non_solid_archive(method: EncoderMethod, contents: &[&[u8]]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(std::io::Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![method.into()]);
+    for (i, content) in contents.iter().enumerate() {
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file(&format!("file{i}.txt")),
+                Some(*content),
+            )
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_reports_all_checksum_mismatches() {
+    let contents: [&[u8]; 3] = [b"first entry", b"second entry", b"third entry"];
+    let mut data = write_non_solid_archive(EncoderMethod::COPY, &contents);
+    let archive = Archive::read(&mut std::io::Cursor::new(&data), &Password::empty()).unwrap();
+    // Corrupt the first byte of the second entry, which is stored uncompressed.
+    let offset = 32 + archive.files[0].compressed_size as usize;
+    data[offset] ^= 0xFF;
+
+    let mut reader = ArchiveReader::new(std::io::Cursor::new(data), Password::empty()).unwrap();
+    let report = reader.test().unwrap();
+    assert!(!report.is_ok());
+    assert!(report.blocks.iter().all(|block| block.error.is_none()));
+
+    let statuses: Vec<_> = report.entries.iter().map(|entry| entry.crc).collect();
+    assert_eq!(statuses[0], CrcStatus::Ok);
+    assert!(matches!(statuses[1], CrcStatus::Mismatch { .. }));
+    assert_eq!(statuses[2], CrcStatus::Ok);
+
+    let pack_statuses: Vec<_> = report
+        .pack_streams
+        .iter()
+        .map(|stream| stream.crc)
+        .collect();
+    assert_eq!(pack_statuses[0], CrcStatus::Ok);
+    assert!(matches!(pack_statuses[1], CrcStatus::Mismatch { .. }));
+    assert_eq!(pack_statuses[2], CrcStatus::Ok);
+    assert_eq!(
+        report.bytes_checked,
+        2 * contents
+            .iter()
+            .map(|content| content.len() as u64)
+            .sum::<u64>()
+    );
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_continues_after_block_error() {
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i * 7 / 13) as u8).collect();
+    let mut data = write_non_solid_archive(EncoderMethod::LZMA2, &[&content, &content]);
+    // Corrupt the beginning of the first packed stream.
+    for byte in data[32..40].iter_mut() {
+        *byte ^= 0x55;
+    }
+
+    let mut reader = ArchiveReader::new(std::io::Cursor::new(data), Password::empty()).unwrap();
+    let report = reader.test().unwrap();
+    assert!(!report.is_ok());
+    assert!(report.blocks[0].error.is_some() || !report.entries[0].crc.is_ok());
+    assert!(report.blocks[1].error.is_none());
+    assert_eq!(report.entries[1].crc, CrcStatus::Ok);
+}
@@ -0,0 +1,130 @@
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use sevenz_rust2::{Archive, ArchiveReader, BlockDecoder, MultiVolumeReader, Password};
+use tempfile::tempdir;
+
+fn split_into_volumes(source: &Path, dest_dir: &Path, volume_size: usize) -> PathBuf {
+    let data = std::fs::read(source).unwrap();
+    let name = source.file_name().unwrap().to_string_lossy().to_string();
+    for (i, chunk) in data.chunks(volume_size).enumerate() {
+        let path = dest_dir.join(format!("{name}.{:03}", i + 1));
+        std::fs::write(path, chunk).unwrap();
+    }
+    dest_dir.join(format!("{name}.001"))
+}
+
+#[test]
+fn read_multi_volume_archive() {
+    let temp_dir = tempdir().unwrap();
+    let first_volume = split_into_volumes(
+        Path::new("tests/resources/non_solid.7z"),
+        temp_dir.path(),
+        1000,
+    );
+
+    let mut single_reader =
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap();
+    let mut multi_reader =
+        ArchiveReader::open_multi_volume(first_volume, Password::empty()).unwrap();
+
+    let paths: Vec<String> = single_reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| !file.is_directory)
+        .map(|file| file.name.clone())
+        .collect();
+
+    assert!(!paths.is_empty());
+    for path in paths.iter() {
+        let data0 = single_reader.read_file(path.as_str()).unwrap();
+        let data1 = multi_reader.read_file(path.as_str()).unwrap();
+        assert_eq!(&data0, &data1);
+    }
+}
+
+#[test]
+fn read_multi_volume_archive_with_block_decoder() {
+    let temp_dir = tempdir().unwrap();
+    let first_volume =
+        split_into_volumes(Path::new("tests/resources/solid.7z"), temp_dir.path(), 333);
+
+    let mut source = MultiVolumeReader::open(first_volume).unwrap();
+    assert_eq!(source.volume_count(), 8);
+
+    let password = Password::empty();
+    let archive = Archive::read(&mut source, &password).unwrap();
+    let mut count = 0;
+    for block_index in 0..archive.blocks.len() {
+        let block_decoder = BlockDecoder::new(1, block_index, &archive, &password, &mut source);
+        block_decoder
+            .for_each_entries(&mut |_entry, reader| {
+                std::io::copy(reader, &mut std::io::sink())?;
+                count += 1;
+                Ok(true)
+            })
+            .unwrap();
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn multi_volume_reader_skips_empty_volumes() {
+    let data: Vec<u8> = (0..=255).collect();
+    let volumes = vec![
+        Cursor::new(data[..100].to_vec()),
+        Cursor::new(Vec::new()),
+        Cursor::new(data[100..].to_vec()),
+    ];
+    let mut reader = MultiVolumeReader::new(volumes).unwrap();
+    assert_eq!(reader.total_len(), 256);
+
+    let mut result = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut result).unwrap();
+    assert_eq!(result, data);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn write_multi_volume_archive() {
+    use sevenz_rust2::{ArchiveEntry, ArchiveWriter};
+
+    let temp_dir = tempdir().unwrap();
+    let base_path = temp_dir.path().join("split.7z");
+    let volume_size = 512;
+
+    let contents: Vec<Vec<u8>> = (0..4u32)
+        .map(|i| {
+            (0..3000u32)
+                .map(|j| (j.wrapping_mul(7919).wrapping_add(i * 31) >> 3) as u8)
+                .collect()
+        })
+        .collect();
+
+    let mut writer = ArchiveWriter::create_multi_volume(&base_path, volume_size).unwrap();
+    for (i, content) in contents.iter().enumerate() {
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file(&format!("file{i}.bin")),
+                Some(content.as_slice()),
+            )
+            .unwrap();
+    }
+    writer.finish().unwrap();
+
+    let first_volume = temp_dir.path().join("split.7z.001");
+    let source = MultiVolumeReader::open(&first_volume).unwrap();
+    assert!(source.volume_count() > 1);
+    for volume in source.into_inner().iter().rev().skip(1) {
+        assert_eq!(volume.metadata().unwrap().len(), volume_size);
+    }
+
+    let mut reader = ArchiveReader::open_multi_volume(&first_volume, Password::empty()).unwrap();
+    for (i, content) in contents.iter().enumerate() {
+        let data = reader.read_file(&format!("file{i}.bin")).unwrap();
+        assert_eq!(&data, content);
+    }
+}
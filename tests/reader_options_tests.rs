@@ -0,0 +1,186 @@
+use std::{fs::File, io::Cursor};
+
+use sevenz_rust2::*;
+
+use crate::common::{build_archive, write_number};
+
+mod common;
+
+fn read_archive(path: &str, options: ReaderOptions) -> Result<Archive, Error> {
+    Archive::read_with_options(&mut File::open(path).unwrap(), &Password::empty(), options)
+}
+
+#[test]
+fn test_default_options_have_no_limits() {
+    let archive = read_archive("tests/resources/solid.7z", ReaderOptions::default()).unwrap();
+    assert!(!archive.files.is_empty());
+}
+
+#[test]
+fn test_max_header_size() {
+    let result = read_archive(
+        "tests/resources/solid.7z",
+        ReaderOptions::default().with_max_header_size(8),
+    );
+    assert!(matches!(result, Err(Error::HeaderTooLarge { max: 8, .. })));
+}
+
+#[test]
+fn test_max_entries() {
+    let result = read_archive(
+        "tests/resources/solid.7z",
+        ReaderOptions::default().with_max_entries(1),
+    );
+    assert!(matches!(result, Err(Error::TooManyEntries { max: 1, .. })));
+}
+
+#[test]
+fn test_max_blocks() {
+    let result = read_archive(
+        "tests/resources/non_solid.7z",
+        ReaderOptions::default().with_max_blocks(1),
+    );
+    assert!(matches!(result, Err(Error::TooManyBlocks { max: 1, .. })));
+}
+
+#[test]
+fn test_max_coders() {
+    // The block of the archive uses a copy and a LZMA2 coder.
+    let result = read_archive(
+        "tests/resources/copy.7z",
+        ReaderOptions::default().with_max_coders(1),
+    );
+    assert!(matches!(result, Err(Error::TooManyCoders { max: 1, .. })));
+}
+
+#[test]
+fn test_max_sizes() {
+    let archive = read_archive("tests/resources/solid.7z", ReaderOptions::default()).unwrap();
+    let largest = archive.files.iter().map(|file| file.size).max().unwrap();
+    let total: u64 = archive
+        .blocks
+        .iter()
+        .map(|block| block.get_unpack_size())
+        .sum();
+
+    let result = read_archive(
+        "tests/resources/solid.7z",
+        ReaderOptions::default().with_max_entry_size(largest - 1),
+    );
+    assert!(matches!(result, Err(Error::EntryTooLarge { size, .. }) if size == largest));
+
+    let result = read_archive(
+        "tests/resources/solid.7z",
+        ReaderOptions::default().with_max_total_unpack_size(total - 1),
+    );
+    assert!(matches!(result, Err(Error::UnpackSizeTooLarge { size, .. }) if size == total));
+
+    let options = ReaderOptions::default()
+        .with_max_entry_size(largest)
+        .with_max_total_unpack_size(total);
+    assert!(read_archive("tests/resources/solid.7z", options).is_ok());
+}
+
+#[test]
+fn test_max_decoder_memory() {
+    let options = ReaderOptions::default().with_max_decoder_memory(1024);
+    let result = ArchiveReader::with_options(
+        File::open("tests/resources/solid.7z").unwrap(),
+        Password::empty(),
+        options,
+    )
+    .and_then(|mut reader| reader.for_each_entries(|_, _| Ok(true)));
+    assert!(matches!(
+        result,
+        Err(Error::MaxMemLimited { max_kb: 1, .. })
+    ));
+}
+
+#[cfg(feature = "ppmd")]
+#[test]
+fn test_max_decoder_memory_of_ppmd() {
+    // The PPMD coder of the archive uses 256 KiB of memory.
+    let read_entries = |max_memory| {
+        let options = ReaderOptions::default().with_max_decoder_memory(max_memory);
+        ArchiveReader::with_options(
+            File::open("tests/resources/ppmd.7z").unwrap(),
+            Password::empty(),
+            options,
+        )
+        .and_then(|mut reader| reader.for_each_entries(|_, _| Ok(true)))
+    };
+    assert!(read_entries(256 * 1024).is_ok());
+    assert!(matches!(
+        read_entries(255 * 1024),
+        Err(Error::MaxMemLimited {
+            max_kb: 255,
+            actaul_kb: 256
+        })
+    ));
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_max_compression_ratio() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("zeros.bin"),
+            Some(vec![0u8; 1024 * 1024].as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let options = ReaderOptions::default().with_max_compression_ratio(100);
+    let result = Archive::read_with_options(&mut Cursor::new(&data), &Password::empty(), options);
+    assert!(matches!(
+        result,
+        Err(Error::CompressionRatioTooHigh {
+            block_index: 0,
+            max: 100,
+            ..
+        })
+    ));
+}
+
+/// Builds an archive with a block of `num_coders` chained copy coders.
+fn copy_chain_archive(num_coders: u64) -> Vec<u8> {
+    let packed = b"data";
+    let mut header = vec![0x01, 0x04, 0x06, 0x00, 0x01, 0x09];
+    write_number(&mut header, packed.len() as u64);
+    header.extend_from_slice(&[0x00, 0x07, 0x0B, 0x01, 0x00]);
+    write_number(&mut header, num_coders);
+    for _ in 0..num_coders {
+        header.extend_from_slice(&[0x01, 0x00]);
+    }
+    // Each coder reads the output of the previous one.
+    for i in 1..num_coders {
+        write_number(&mut header, i);
+        write_number(&mut header, i - 1);
+    }
+    header.push(0x0C);
+    for _ in 0..num_coders {
+        write_number(&mut header, packed.len() as u64);
+    }
+    header.extend_from_slice(&[0x00, 0x00, 0x00]);
+
+    build_archive(packed, &header)
+}
+
+#[test]
+fn test_max_block_coders() {
+    let data = copy_chain_archive(2);
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(archive.blocks[0].coders.len(), 2);
+
+    // Decoding the coders is recursive, so a long chain is rejected even without limits.
+    let data = copy_chain_archive(100_000);
+    let result = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty());
+    assert!(matches!(
+        result,
+        Err(Error::TooManyCoders {
+            count: 100_000,
+            max: 64
+        })
+    ));
+}